@@ -0,0 +1,304 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use crate::chat::ChatMessage;
+
+/// 允许附加的图片文件最大字节数
+const MAX_IMAGE_FILE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// 图片长边的最大像素数，超过时等比缩小
+pub const MAX_IMAGE_DIMENSION: u32 = 2048;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// 附加到消息中的图片，已缩放并重新编码为 data URL
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageAttachment {
+    /// 原始文件名
+    pub name: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub data_url: String,
+}
+
+/// 读取本地图片文件并编码为附件
+pub fn load_image(path: &Path) -> Result<ImageAttachment, String> {
+    let size = fs::metadata(path)
+        .map_err(|e| format!("无法读取图片 {}: {}", path.display(), e))?
+        .len();
+    if size > MAX_IMAGE_FILE_BYTES {
+        return Err(format!("图片过大（{} 字节），最大支持 {} 字节", size, MAX_IMAGE_FILE_BYTES));
+    }
+    let bytes = fs::read(path).map_err(|e| format!("无法读取图片 {}: {}", path.display(), e))?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    encode_image(&name, &bytes)
+}
+
+/// 解码图片，长边超过 `MAX_IMAGE_DIMENSION` 时缩小；带透明通道的编码为 PNG，其余编码为 JPEG
+pub fn encode_image(name: &str, bytes: &[u8]) -> Result<ImageAttachment, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("无法识别的图片格式 {}: {}", name, e))?;
+    let image = if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        image.resize(MAX_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    let mut encoded = Vec::new();
+    let mime_type = if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+            .map_err(|e| format!("图片编码失败: {}", e))?;
+        "image/png"
+    } else {
+        JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+            .map_err(|e| format!("图片编码失败: {}", e))?;
+        "image/jpeg"
+    };
+
+    Ok(ImageAttachment {
+        name: name.to_string(),
+        mime_type: mime_type.to_string(),
+        width: image.width(),
+        height: image.height(),
+        data_url: format!("data:{};base64,{}", mime_type, STANDARD.encode(&encoded)),
+    })
+}
+
+/// 允许附加的文档文件最大字节数
+const MAX_DOCUMENT_FILE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// 单个分块的目标字符数
+pub const DOCUMENT_CHUNK_CHARS: usize = 2000;
+
+/// 单个文档默认可注入的最大字符数
+pub const DEFAULT_DOCUMENT_BUDGET: usize = 24_000;
+
+/// 一条消息中全部文档合计可注入的最大字符数，超出时后面的文档只保留放得下的分块
+pub const MAX_DOCUMENTS_CHARS: usize = 48_000;
+
+/// 附加文档的类型
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentKind {
+    Text,
+    Markdown,
+    Code { language: String },
+    Pdf,
+}
+
+impl DocumentKind {
+    /// 按扩展名判断文档类型，未知扩展名按纯文本处理
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let language = match extension.as_str() {
+            "pdf" => return DocumentKind::Pdf,
+            "md" | "markdown" => return DocumentKind::Markdown,
+            "rs" => "rust",
+            "py" => "python",
+            "js" | "mjs" | "cjs" => "javascript",
+            "ts" | "tsx" => "typescript",
+            "jsx" => "jsx",
+            "go" => "go",
+            "java" => "java",
+            "kt" => "kotlin",
+            "c" | "h" => "c",
+            "cpp" | "cc" | "cxx" | "hpp" => "cpp",
+            "cs" => "csharp",
+            "rb" => "ruby",
+            "php" => "php",
+            "swift" => "swift",
+            "sh" | "bash" | "zsh" => "shell",
+            "sql" => "sql",
+            "html" | "htm" => "html",
+            "css" => "css",
+            "json" => "json",
+            "toml" => "toml",
+            "yaml" | "yml" => "yaml",
+            "xml" => "xml",
+            _ => return DocumentKind::Text,
+        };
+        DocumentKind::Code {
+            language: language.to_string(),
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            DocumentKind::Text => "text",
+            DocumentKind::Markdown => "markdown",
+            DocumentKind::Code { language } => language,
+            DocumentKind::Pdf => "pdf",
+        }
+    }
+}
+
+/// 附加到消息中的文档：文件信息和按上下文预算截取后的文本
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DocumentAttachment {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: DocumentKind,
+    /// 文件字节数
+    pub size: u64,
+    /// 提取出的全文字符数
+    pub total_chars: usize,
+    /// 全文分块数
+    pub total_chunks: usize,
+    /// 注入消息的分块数，小于 `total_chunks` 时说明内容被截断
+    pub included_chunks: usize,
+    /// 注入消息的文本
+    pub text: String,
+}
+
+impl DocumentAttachment {
+    pub fn is_truncated(&self) -> bool {
+        self.included_chunks < self.total_chunks
+    }
+}
+
+/// 将文本按行切分为不超过 `chunk_chars` 个字符的分块，超长的行按字符切开
+pub fn chunk_text(text: &str, chunk_chars: usize) -> Vec<String> {
+    let chunk_chars = chunk_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_chars = line.chars().count();
+        if current_chars + line_chars > chunk_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if line_chars > chunk_chars {
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(chunk_chars) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        current.push_str(line);
+        current_chars += line_chars;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 提取文档文本，PDF 通过 pdf-extract 解析，其余文件按 UTF-8 文本读取
+pub fn extract_text(path: &Path, kind: &DocumentKind) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("无法读取文件 {}: {}", path.display(), e))?;
+    if *kind == DocumentKind::Pdf {
+        return pdf_extract::extract_text_from_mem(&bytes).map_err(|e| format!("解析 PDF 失败 {}: {}", path.display(), e));
+    }
+    if bytes[..bytes.len().min(8192)].contains(&0) {
+        return Err(format!("不支持附加二进制文件: {}", path.display()));
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// 读取本地文档，提取文本并按 `budget` 个字符的预算截取开头的分块
+pub fn load_document(path: &Path, budget: usize) -> Result<DocumentAttachment, String> {
+    let size = fs::metadata(path)
+        .map_err(|e| format!("无法读取文件 {}: {}", path.display(), e))?
+        .len();
+    if size > MAX_DOCUMENT_FILE_BYTES {
+        return Err(format!("文件过大（{} 字节），最大支持 {} 字节", size, MAX_DOCUMENT_FILE_BYTES));
+    }
+
+    let kind = DocumentKind::from_path(path);
+    let text = extract_text(path, &kind)?;
+    let chunks = chunk_text(&text, DOCUMENT_CHUNK_CHARS);
+
+    let mut included = String::new();
+    let mut included_chars = 0;
+    let mut included_chunks = 0;
+    for chunk in &chunks {
+        let chunk_chars = chunk.chars().count();
+        if included_chars + chunk_chars > budget {
+            break;
+        }
+        included.push_str(chunk);
+        included_chars += chunk_chars;
+        included_chunks += 1;
+    }
+
+    Ok(DocumentAttachment {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: path.to_path_buf(),
+        kind,
+        size,
+        total_chars: text.chars().count(),
+        total_chunks: chunks.len(),
+        included_chunks,
+        text: included,
+    })
+}
+
+/// 将文档内容以带文件名的分隔标记拼接在用户消息之前。
+///
+/// 全部文档合计不超过 [`MAX_DOCUMENTS_CHARS`] 个字符，按顺序放入完整的分块，放不下的部分省略并在文档末尾注明。
+pub fn compose_with_documents(message: &str, documents: &[DocumentAttachment]) -> String {
+    if documents.is_empty() {
+        return message.to_string();
+    }
+
+    let mut composed = String::new();
+    let mut remaining = MAX_DOCUMENTS_CHARS;
+    for document in documents {
+        composed.push_str(&format!(
+            "<document name=\"{}\" type=\"{}\">\n",
+            document.name,
+            document.kind.label()
+        ));
+        let chunks = chunk_text(&document.text, DOCUMENT_CHUNK_CHARS);
+        let mut text = String::new();
+        let mut included = 0;
+        for chunk in &chunks {
+            let chunk_chars = chunk.chars().count();
+            if chunk_chars > remaining {
+                break;
+            }
+            text.push_str(chunk);
+            remaining -= chunk_chars;
+            included += 1;
+        }
+        composed.push_str(text.trim_end());
+        if included < chunks.len() {
+            composed.push_str(&format!(
+                "\n[附件总长度超出上下文预算，仅包含前 {}/{} 个分块]",
+                included, document.total_chunks
+            ));
+        } else if document.is_truncated() {
+            composed.push_str(&format!(
+                "\n[文件过长，仅包含前 {}/{} 个分块]",
+                document.included_chunks, document.total_chunks
+            ));
+        }
+        composed.push_str("\n</document>\n\n");
+    }
+    composed.push_str(message);
+    composed
+}
+
+/// 构造附带图片和文档的消息，文档内容拼接在文本之前，图片作为 `image_url` 内容块
+pub fn attachment_message(role: &str, text: &str, images: &[ImageAttachment], documents: &[DocumentAttachment]) -> ChatMessage {
+    let image_urls: Vec<String> = images.iter().map(|image| image.data_url.clone()).collect();
+    ChatMessage::with_images(role, &compose_with_documents(text, documents), &image_urls)
+}
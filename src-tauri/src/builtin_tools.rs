@@ -0,0 +1,469 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+use chrono::{Local, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde_json::{json, Value};
+use crate::storage::{read_setting, with_storage, Storage};
+use crate::tools::{register_tool, Tool};
+
+pub const APPROVED_DIRS_KEY: &str = "approved_dirs";
+
+/// 读取文件的最大字节数
+const MAX_READ_BYTES: u64 = 256 * 1024;
+
+/// 表达式最大嵌套层数（括号、一元符号和乘方），避免过深的递归导致栈溢出
+const MAX_EXPRESSION_DEPTH: usize = 64;
+
+/// 列目录时最多返回的条目数
+const MAX_LIST_ENTRIES: usize = 500;
+
+/// 用户允许工具访问的目录
+pub fn load_approved_dirs(storage: &dyn Storage) -> Result<Vec<PathBuf>, String> {
+    read_setting(storage, APPROVED_DIRS_KEY)
+}
+
+/// 解析请求的路径，只允许访问已批准目录内的文件（含符号链接解析后的真实路径）
+pub fn resolve_sandboxed_path(approved: &[PathBuf], requested: &str) -> Result<PathBuf, String> {
+    let path = fs::canonicalize(requested).map_err(|e| format!("无法访问 {}: {}", requested, e))?;
+    let allowed = approved
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .any(|dir| path.starts_with(dir));
+    if allowed {
+        Ok(path)
+    } else {
+        Err(format!("路径不在已批准的目录中: {}", requested))
+    }
+}
+
+fn string_argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments[name]
+        .as_str()
+        .ok_or_else(|| format!("缺少参数: {}", name))
+}
+
+/// 读取已批准目录中的文本文件
+pub struct ReadFileTool;
+
+impl ReadFileTool {
+    fn read(&self, arguments: &Value) -> Result<String, String> {
+        let approved = with_storage(|storage| load_approved_dirs(storage))?;
+        let path = resolve_sandboxed_path(&approved, string_argument(arguments, "path")?)?;
+        if !path.is_file() {
+            return Err(format!("不是文件: {}", path.display()));
+        }
+
+        // 只读取前 MAX_READ_BYTES 字节，避免把大文件整个读入内存
+        let file = File::open(&path).map_err(|e| format!("读取文件失败: {}", e))?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut bytes = Vec::with_capacity(size.min(MAX_READ_BYTES) as usize);
+        file.take(MAX_READ_BYTES)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("读取文件失败: {}", e))?;
+        if bytes[..bytes.len().min(8192)].contains(&0) {
+            return Err("不支持读取二进制文件".to_string());
+        }
+
+        let mut content = String::from_utf8_lossy(&bytes).into_owned();
+        if size > MAX_READ_BYTES {
+            content.push_str(&format!("\n[文件共 {} 字节，仅返回前 {} 字节]", size, MAX_READ_BYTES));
+        }
+        Ok(content)
+    }
+}
+
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "读取用户已批准目录中的文本文件内容"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "文件的绝对路径" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move { self.read(&arguments) }.boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
+    }
+}
+
+/// 列出已批准目录中的目录内容
+pub struct ListDirectoryTool;
+
+impl ListDirectoryTool {
+    fn list(&self, arguments: &Value) -> Result<String, String> {
+        let approved = with_storage(|storage| load_approved_dirs(storage))?;
+        let path = resolve_sandboxed_path(&approved, string_argument(arguments, "path")?)?;
+        if !path.is_dir() {
+            return Err(format!("不是目录: {}", path.display()));
+        }
+
+        let mut entries: Vec<(bool, String, u64)> = fs::read_dir(&path)
+            .map_err(|e| format!("读取目录失败: {}", e))?
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let metadata = entry.metadata().ok();
+                let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+                let size = metadata.map(|m| m.len()).unwrap_or_default();
+                (is_dir, entry.file_name().to_string_lossy().into_owned(), size)
+            })
+            .collect();
+        // 目录在前，同类按名称排序
+        entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        let total = entries.len();
+        let mut lines: Vec<String> = entries
+            .into_iter()
+            .take(MAX_LIST_ENTRIES)
+            .map(|(is_dir, name, size)| {
+                if is_dir {
+                    format!("[目录] {}", name)
+                } else {
+                    format!("[文件] {} ({} 字节)", name, size)
+                }
+            })
+            .collect();
+        if total > MAX_LIST_ENTRIES {
+            lines.push(format!("[共 {} 项，仅列出前 {} 项]", total, MAX_LIST_ENTRIES));
+        }
+        if lines.is_empty() {
+            return Ok("目录为空".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Tool for ListDirectoryTool {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+
+    fn description(&self) -> &str {
+        "列出用户已批准目录中某个目录下的文件和子目录"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "目录的绝对路径" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move { self.list(&arguments) }.boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
+    }
+}
+
+/// 计算算术表达式
+pub struct CalculatorTool;
+
+impl Tool for CalculatorTool {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn description(&self) -> &str {
+        "计算算术表达式，支持 + - * / % ^、括号、常量 pi/e 以及 sqrt、abs、sin、cos、tan、ln、log、exp、floor、ceil、round、min、max 等函数"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": { "type": "string", "description": "要计算的表达式，例如 (1 + 2) * sqrt(16)" }
+            },
+            "required": ["expression"]
+        })
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move {
+            let expression = string_argument(&arguments, "expression")?;
+            evaluate_expression(expression).map(format_number)
+        }
+        .boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
+    }
+}
+
+/// 获取当前日期和时间
+pub struct ClockTool;
+
+impl Tool for ClockTool {
+    fn name(&self) -> &str {
+        "current_time"
+    }
+
+    fn description(&self) -> &str {
+        "获取当前的本地日期、时间、星期和时区，以及对应的 UTC 时间"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move {
+            let local = Local::now();
+            let result = json!({
+                "local": local.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "weekday": local.format("%A").to_string(),
+                "timezone": local.format("%:z").to_string(),
+                "utc": Utc::now().to_rfc3339(),
+                "unix": local.timestamp(),
+            });
+            Ok(result.to_string())
+        }
+        .boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        true
+    }
+}
+
+/// 注册全部内置工具，应用启动时调用
+pub fn register_builtin_tools() {
+    register_tool(Arc::new(ReadFileTool));
+    register_tool(Arc::new(ListDirectoryTool));
+    register_tool(Arc::new(CalculatorTool));
+    register_tool(Arc::new(ClockTool));
+}
+
+/// 整数结果不带小数点，其余保留有效数字
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// 计算算术表达式
+pub fn evaluate_expression(expression: &str) -> Result<f64, String> {
+    let mut parser = ExpressionParser::new(expression);
+    let value = parser.parse_expression()?;
+    parser.skip_whitespace();
+    if let Some(c) = parser.peek() {
+        return Err(format!("无法解析的字符: {}", c));
+    }
+    if !value.is_finite() {
+        return Err("计算结果不是有限数（可能除以了零）".to_string());
+    }
+    Ok(value)
+}
+
+/// 递归下降的表达式解析器，`^` 为右结合且优先级高于一元负号
+struct ExpressionParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    depth: usize,
+}
+
+impl<'a> ExpressionParser<'a> {
+    fn new(expression: &'a str) -> Self {
+        ExpressionParser {
+            chars: expression.chars().peekable(),
+            depth: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.chars.next();
+        }
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<f64, String> {
+        let mut value = self.parse_term()?;
+        loop {
+            if self.consume('+') {
+                value += self.parse_term()?;
+            } else if self.consume('-') {
+                value -= self.parse_term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<f64, String> {
+        let mut value = self.parse_unary()?;
+        loop {
+            if self.consume('*') {
+                value *= self.parse_unary()?;
+            } else if self.consume('/') {
+                value /= self.parse_unary()?;
+            } else if self.consume('%') {
+                value %= self.parse_unary()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// 括号、一元符号和乘方的递归都经过这里，在此限制嵌套层数
+    fn parse_unary(&mut self) -> Result<f64, String> {
+        if self.depth >= MAX_EXPRESSION_DEPTH {
+            return Err(format!("表达式嵌套超过 {} 层", MAX_EXPRESSION_DEPTH));
+        }
+        self.depth += 1;
+        let value = self.parse_signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_signed(&mut self) -> Result<f64, String> {
+        if self.consume('-') {
+            Ok(-self.parse_unary()?)
+        } else if self.consume('+') {
+            self.parse_unary()
+        } else {
+            self.parse_power()
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<f64, String> {
+        let base = self.parse_primary()?;
+        if self.consume('^') {
+            Ok(base.powf(self.parse_unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<f64, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.parse_expression()?;
+                if !self.consume(')') {
+                    return Err("缺少右括号".to_string());
+                }
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.parse_number(),
+            Some(c) if c.is_ascii_alphabetic() => self.parse_identifier(),
+            Some(c) => Err(format!("无法解析的字符: {}", c)),
+            None => Err("表达式不完整".to_string()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, String> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '+' || c == '-') && text.ends_with(['e', 'E']);
+            if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                text.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        text.parse().map_err(|_| format!("无效的数字: {}", text))
+    }
+
+    fn parse_identifier(&mut self) -> Result<f64, String> {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+            self.chars.next();
+        }
+        let name = name.to_lowercase();
+
+        if !self.consume('(') {
+            return match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "e" => Ok(std::f64::consts::E),
+                _ => Err(format!("未知的常量: {}", name)),
+            };
+        }
+
+        let mut args = Vec::new();
+        if !self.consume(')') {
+            loop {
+                args.push(self.parse_expression()?);
+                if self.consume(')') {
+                    break;
+                }
+                if !self.consume(',') {
+                    return Err(format!("函数 {} 的参数列表格式错误", name));
+                }
+            }
+        }
+        apply_function(&name, &args)
+    }
+}
+
+fn apply_function(name: &str, args: &[f64]) -> Result<f64, String> {
+    let unary = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => Err(format!("函数 {} 需要 1 个参数", name)),
+    };
+    let binary = |f: fn(f64, f64) -> f64| match args {
+        [x, y] => Ok(f(*x, *y)),
+        _ => Err(format!("函数 {} 需要 2 个参数", name)),
+    };
+
+    match name {
+        "sqrt" => unary(f64::sqrt),
+        "abs" => unary(f64::abs),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" => unary(f64::atan),
+        "ln" => unary(f64::ln),
+        "log" | "log10" => unary(f64::log10),
+        "log2" => unary(f64::log2),
+        "exp" => unary(f64::exp),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "round" => unary(f64::round),
+        "pow" => binary(f64::powf),
+        "min" => binary(f64::min),
+        "max" => binary(f64::max),
+        _ => Err(format!("未知的函数: {}", name)),
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::env;
 use std::sync::Mutex;
 use log::error;
 use lazy_static::lazy_static;
-use crate::models::ModelFrequency;
+use crate::persist::{write_atomic, FileLock};
+use crate::storage::with_storage;
+use serde::{de::DeserializeOwned, Serialize};
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
@@ -14,8 +16,8 @@ use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use rand::RngCore;
 
 const KEY_FILE: &str = "encryption.key";
-const API_KEYS_FILE: &str = "api_keys.enc";
-const API_URL_FILE: &str = "api_url.enc";
+pub(crate) const API_KEY_SETTING: &str = "api_key";
+pub(crate) const API_URL_SETTING: &str = "api_url";
 
 lazy_static! {
     static ref CIPHER: Mutex<Option<Aes256Gcm>> = Mutex::new(None);
@@ -30,6 +32,8 @@ fn init_cipher() -> Result<(), String> {
     let cache_dir = get_cache_dir();
     let key_path = cache_dir.join(KEY_FILE);
 
+    // 持有锁再判断密钥是否存在，避免多个实例同时生成不同的密钥
+    let _lock = FileLock::acquire(&key_path)?;
     let key = if key_path.exists() {
         // 读取现有密钥
         fs::read(&key_path).map_err(|e| format!("无法读取加密密钥: {}", e))?
@@ -37,8 +41,7 @@ fn init_cipher() -> Result<(), String> {
         // 生成新密钥
         let mut key = [0u8; 32];
         rand::rng().fill_bytes(&mut key);
-        fs::create_dir_all(&cache_dir).map_err(|e| format!("无法创建缓存目录: {}", e))?;
-        fs::write(&key_path, &key).map_err(|e| format!("无法保存加密密钥: {}", e))?;
+        write_atomic(&key_path, key).map_err(|e| format!("无法保存加密密钥: {}", e))?;
         key.to_vec()
     };
 
@@ -75,24 +78,47 @@ pub fn get_cache_dir() -> PathBuf {
     }
 }
 
-pub fn save_frequencies(frequency_file: PathBuf) {
-    let frequencies = MODEL_FREQUENCIES.lock().unwrap();
-    let frequency_data = ModelFrequency {
-        frequencies: frequencies.clone(),
-    };
-    
-    // 确保父目录存在
-    if let Some(parent) = frequency_file.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            error!("创建目录失败: {}", e);
-            return;
-        }
+/// 读取 JSON 文件，文件不存在时返回默认值
+pub fn read_json_file<T: Default + DeserializeOwned>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
     }
-    
-    if let Ok(json) = serde_json::to_string_pretty(&frequency_data) {
-        if let Err(e) = fs::write(&frequency_file, json) {
-            error!("保存频率数据失败: {}", e);
-        }
+    let content = fs::read_to_string(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))
+}
+
+/// 将数据序列化为 JSON 原子地写入文件，自动创建父目录
+pub fn write_json_file<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("序列化失败: {}", e))?;
+    write_atomic(path, json)
+}
+
+/// 持有文件锁读取、修改并写回 JSON 文件，多个实例同时修改时不会丢失更新；`f` 返回错误时不写回
+pub fn update_json_file<T, R, F>(path: &Path, f: F) -> Result<R, String>
+where
+    T: Default + DeserializeOwned + Serialize,
+    F: FnOnce(&mut T) -> Result<R, String>,
+{
+    let _lock = FileLock::acquire(path)?;
+    let mut value = read_json_file(path)?;
+    let result = f(&mut value)?;
+    write_json_file(path, &value)?;
+    Ok(result)
+}
+
+/// 启动时从数据库读取模型使用次数，之后在内存中累计
+pub fn load_frequencies() {
+    match with_storage(|storage| storage.load_models()) {
+        Ok(stored) => MODEL_FREQUENCIES.lock().unwrap().extend(stored),
+        Err(e) => error!("读取频率数据失败: {}", e),
+    }
+}
+
+/// 将内存中的模型使用次数写入数据库
+pub fn save_frequencies() {
+    let frequencies = MODEL_FREQUENCIES.lock().unwrap().clone();
+    if let Err(e) = with_storage(|storage| storage.save_models(&frequencies)) {
+        error!("保存频率数据失败: {}", e);
     }
 }
 
@@ -106,156 +132,81 @@ pub fn update_frequency(model: String, success: bool) {
     }
 }
 
-pub fn encrypt_api_key(api_key: &str) -> Result<(), String> {
+/// 加密后以 base64 编码保存到设置项 `key`
+fn encrypt_setting(key: &str, value: &str) -> Result<(), String> {
     init_cipher()?;
-    
+
     let cipher = CIPHER.lock().map_err(|e| e.to_string())?;
     let cipher = cipher.as_ref().unwrap();
-    
+
     // 生成随机 nonce
     let mut nonce = [0u8; 12];
     rand::rng().fill_bytes(&mut nonce);
     let nonce = Nonce::from_slice(&nonce);
-    
+
     // 加密数据
     let encrypted = cipher
-        .encrypt(nonce, api_key.as_bytes())
+        .encrypt(nonce, value.as_bytes())
         .map_err(|e| format!("加密失败: {}", e))?;
-    
+
     // 将 nonce 和加密数据合并并进行 base64 编码
     let mut combined = nonce.to_vec();
     combined.extend(encrypted);
     let encoded = BASE64.encode(combined);
-    
-    // 保存加密数据
-    let cache_dir = get_cache_dir();
-    fs::create_dir_all(&cache_dir).map_err(|e| format!("无法创建缓存目录: {}", e))?;
-    fs::write(cache_dir.join(API_KEYS_FILE), encoded).map_err(|e| format!("无法保存加密数据: {}", e))?;
-    
-    Ok(())
+
+    with_storage(|storage| storage.set_setting(key, &encoded)).map_err(|e| format!("无法保存加密数据: {}", e))
 }
 
-pub fn decrypt_api_key(api_keys_path: PathBuf) -> Result<String, String> {
+/// 读取并解密设置项 `key`，未设置时返回 `missing` 错误
+fn decrypt_setting(key: &str, missing: &str) -> Result<String, String> {
     init_cipher()?;
-    
+
     let cipher = CIPHER.lock().map_err(|e| e.to_string())?;
     let cipher = cipher.as_ref().unwrap();
-    
-    if !api_keys_path.exists() {
-        return Err("API key 未设置".to_string());
-    }
-    
-    let encrypted = fs::read(&api_keys_path)
-        .map_err(|e| format!("无法读取加密数据: {}", e))?;
-    
+
+    let encrypted = with_storage(|storage| storage.get_setting(key))?.ok_or_else(|| missing.to_string())?;
+
     // base64 解码
     let decoded = BASE64.decode(encrypted)
         .map_err(|e| format!("无法解码数据: {}", e))?;
-    
+
     if decoded.len() < 12 {
         return Err("无效的加密数据".to_string());
     }
-    
+
     // 分离 nonce 和加密数据
     let (nonce, encrypted_data) = decoded.split_at(12);
     let nonce = Nonce::from_slice(nonce);
-    
+
     // 解密数据
     let decrypted = cipher
         .decrypt(nonce, encrypted_data)
         .map_err(|e| format!("解密失败: {}", e))?;
-    
+
     String::from_utf8(decrypted)
         .map_err(|e| format!("无法解析解密数据: {}", e))
 }
 
+pub fn encrypt_api_key(api_key: &str) -> Result<(), String> {
+    encrypt_setting(API_KEY_SETTING, api_key)
+}
+
+pub fn decrypt_api_key() -> Result<String, String> {
+    decrypt_setting(API_KEY_SETTING, "API key 未设置")
+}
+
 pub fn encrypt_api_url(api_url: &str) -> Result<(), String> {
-    init_cipher()?;
-    
-    let cipher = CIPHER.lock().map_err(|e| e.to_string())?;
-    let cipher = cipher.as_ref().unwrap();
-    
-    // 生成随机 nonce
-    let mut nonce = [0u8; 12];
-    rand::rng().fill_bytes(&mut nonce);
-    let nonce = Nonce::from_slice(&nonce);
-    
-    // 加密数据
-    let encrypted = cipher
-        .encrypt(nonce, api_url.as_bytes())
-        .map_err(|e| format!("加密失败: {}", e))?;
-    
-    // 将 nonce 和加密数据合并并进行 base64 编码
-    let mut combined = nonce.to_vec();
-    combined.extend(encrypted);
-    let encoded = BASE64.encode(combined);
-    
-    // 保存加密数据
-    let cache_dir = get_cache_dir();
-    fs::create_dir_all(&cache_dir).map_err(|e| format!("无法创建缓存目录: {}", e))?;
-    fs::write(cache_dir.join(API_URL_FILE), encoded).map_err(|e| format!("无法保存加密数据: {}", e))?;
-    
-    Ok(())
+    encrypt_setting(API_URL_SETTING, api_url)
 }
 
 pub fn decrypt_api_url() -> Result<String, String> {
-    init_cipher()?;
-    
-    let cipher = CIPHER.lock().map_err(|e| e.to_string())?;
-    let cipher = cipher.as_ref().unwrap();
-    
-    // 读取加密数据
-    let cache_dir = get_cache_dir();
-    let api_url_path = cache_dir.join(API_URL_FILE);
-    
-    if !api_url_path.exists() {
-        return Err("API URL 未设置".to_string());
-    }
-    
-    let encrypted = fs::read(api_url_path)
-        .map_err(|e| format!("无法读取加密数据: {}", e))?;
-    
-    // base64 解码
-    let decoded = BASE64.decode(encrypted)
-        .map_err(|e| format!("无法解码数据: {}", e))?;
-    
-    if decoded.len() < 12 {
-        return Err("无效的加密数据".to_string());
-    }
-    
-    // 分离 nonce 和加密数据
-    let (nonce, encrypted_data) = decoded.split_at(12);
-    let nonce = Nonce::from_slice(nonce);
-    
-    // 解密数据
-    let decrypted = cipher
-        .decrypt(nonce, encrypted_data)
-        .map_err(|e| format!("解密失败: {}", e))?;
-    
-    String::from_utf8(decrypted)
-        .map_err(|e| format!("无法解析解密数据: {}", e))
+    decrypt_setting(API_URL_SETTING, "API URL 未设置")
 }
 
 pub fn delete_api_key() -> Result<(), String> {
-    let cache_dir = get_cache_dir();
-    let api_keys_path = cache_dir.join(API_KEYS_FILE);
-    
-    if api_keys_path.exists() {
-        fs::remove_file(api_keys_path)
-            .map_err(|e| format!("无法删除 API key: {}", e))?;
-    }
-    
-    Ok(())
+    with_storage(|storage| storage.delete_setting(API_KEY_SETTING)).map_err(|e| format!("无法删除 API key: {}", e))
 }
 
 pub fn delete_api_url() -> Result<(), String> {
-    let cache_dir = get_cache_dir();
-    let api_url_path = cache_dir.join(API_URL_FILE);
-    
-    if api_url_path.exists() {
-        fs::remove_file(api_url_path)
-            .map_err(|e| format!("无法删除 API URL: {}", e))?;
-    }
-    
-    Ok(())
-}
\ No newline at end of file
+    with_storage(|storage| storage.delete_setting(API_URL_SETTING)).map_err(|e| format!("无法删除 API URL: {}", e))
+}
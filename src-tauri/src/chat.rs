@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatRequest {
@@ -9,10 +10,185 @@ pub struct ChatRequest {
     pub history: Vec<ChatMessage>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ChatMessage {
     pub role: String,
-    pub content: String,
+    pub content: MessageContent,
+    /// 助手消息中模型请求的工具调用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// `tool` 消息对应的工具调用 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: &str) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// 附带图片的消息，`image_urls` 为 URL 或 base64 data URL
+    pub fn with_images(role: &str, text: &str, image_urls: &[String]) -> Self {
+        ChatMessage {
+            content: MessageContent::with_images(text, image_urls),
+            ..ChatMessage::new(role, "")
+        }
+    }
+
+    /// 携带工具调用请求的助手消息
+    pub fn assistant_tool_calls(content: &str, tool_calls: Vec<ToolCall>) -> Self {
+        ChatMessage {
+            tool_calls: Some(tool_calls),
+            ..ChatMessage::new("assistant", content)
+        }
+    }
+
+    /// 工具执行结果消息
+    pub fn tool_result(tool_call_id: &str, content: &str) -> Self {
+        ChatMessage {
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..ChatMessage::new("tool", content)
+        }
+    }
+}
+
+/// 消息内容：纯文本，或 OpenAI 格式的多段内容（文本和图片）。
+/// 纯文本按字符串序列化，与只支持字符串内容的接口和旧数据兼容。
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageUrl {
+    pub url: String,
+    /// 图片精度：`low`、`high` 或 `auto`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl PartialEq<str> for MessageContent {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, MessageContent::Text(text) if text == other)
+    }
+}
+
+impl PartialEq<&str> for MessageContent {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl MessageContent {
+    /// 没有图片时退化为纯文本
+    pub fn with_images(text: &str, image_urls: &[String]) -> Self {
+        if image_urls.is_empty() {
+            return text.into();
+        }
+        let mut parts = vec![ContentPart::Text { text: text.to_string() }];
+        parts.extend(image_urls.iter().map(|url| ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: url.clone(),
+                detail: None,
+            },
+        }));
+        MessageContent::Parts(parts)
+    }
+
+    /// 全部文本内容，多段文本以换行连接
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    pub fn image_urls(&self) -> Vec<&str> {
+        match self {
+            MessageContent::Text(_) => vec![],
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::ImageUrl { image_url } => Some(image_url.url.as_str()),
+                    ContentPart::Text { .. } => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 模型请求的一次工具调用，`arguments` 为 JSON 字符串
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+/// 请求中声明的可用工具
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolDefinition {
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    /// 参数的 JSON Schema
+    pub parameters: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,11 +196,101 @@ pub struct ChatPayload {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(flatten)]
+    pub params: GenerationParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
+}
+
+/// 生成参数，未设置的字段不会出现在请求 JSON 中，由服务端使用默认值
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// 输出格式，对应 OpenAI 的 `response_format` 字段
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: serde_json::Value },
+}
+
+impl GenerationParams {
+    /// 以 `self` 为准，未设置的字段使用 `base` 中的值
+    pub fn merged_over(self, base: &GenerationParams) -> GenerationParams {
+        GenerationParams {
+            temperature: self.temperature.or(base.temperature),
+            top_p: self.top_p.or(base.top_p),
+            max_tokens: self.max_tokens.or(base.max_tokens),
+            presence_penalty: self.presence_penalty.or(base.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(base.frequency_penalty),
+            stop: self.stop.or_else(|| base.stop.clone()),
+            seed: self.seed.or(base.seed),
+            response_format: self.response_format.or_else(|| base.response_format.clone()),
+        }
+    }
+
+    /// 按 OpenAI 接口的取值范围校验参数
+    pub fn validate(&self) -> Result<(), String> {
+        fn check_range(name: &str, value: Option<f32>, min: f32, max: f32) -> Result<(), String> {
+            match value {
+                Some(v) if !(min..=max).contains(&v) => {
+                    Err(format!("{} 必须在 {} 到 {} 之间，当前为 {}", name, min, max, v))
+                }
+                _ => Ok(()),
+            }
+        }
+
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens 必须大于 0".to_string());
+        }
+        if let Some(stop) = &self.stop {
+            if stop.len() > 4 {
+                return Err("stop 最多支持 4 个停止序列".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 流式请求选项，`include_usage` 让服务端在最后一个分片中返回 token 用量
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamOptions {
+    pub include_usage: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StreamResponse {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
     pub choices: Vec<StreamChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,4 +302,292 @@ pub struct StreamChoice {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeltaContent {
     pub content: Option<String>,
-}
\ No newline at end of file
+    /// 推理模型（如 deepseek-reasoner）输出的思考过程
+    #[serde(default, alias = "reasoning")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// 流式响应中的工具调用片段，同一调用的片段通过 `index` 关联，参数分多次拼接
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl Usage {
+    /// 累加另一次请求的用量（续写时合并多轮请求）
+    pub fn accumulate(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// 生成结束的原因
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// 模型自然结束或命中停止词
+    Stop,
+    /// 达到 max_tokens 或上下文长度上限，回答被截断
+    Length,
+    /// 被服务端内容过滤拦截
+    ContentFilter,
+    /// 模型请求调用工具
+    ToolCalls,
+    #[serde(other)]
+    Other,
+}
+
+impl FinishReason {
+    pub fn parse(reason: &str) -> Self {
+        match reason {
+            "stop" | "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "length" | "max_tokens" => FinishReason::Length,
+            "content_filter" => FinishReason::ContentFilter,
+            "tool_calls" | "function_call" | "tool_use" => FinishReason::ToolCalls,
+            _ => FinishReason::Other,
+        }
+    }
+}
+
+/// 请求耗时统计（毫秒）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ChatTimings {
+    /// 从发出请求到收到第一个内容分片的耗时
+    pub first_token_ms: Option<u64>,
+    /// 整个流式响应的总耗时
+    pub total_ms: u64,
+}
+
+/// `chat` 命令的返回结果，供前端和测试直接使用
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatResult {
+    /// 本地生成的消息 ID，用于会话中的消息和账本记录
+    pub message_id: String,
+    /// 服务端返回的响应 ID，仅供排查问题，不保证唯一
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+    pub content: String,
+    /// 推理模型的思考过程，不会作为历史消息再次发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// 模型请求的工具调用，最终回答中为空
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<Usage>,
+    pub timings: ChatTimings,
+    /// 服务端实际使用的模型，未返回时为请求的模型
+    pub model: String,
+}
+
+impl ChatResult {
+    /// 模型是否请求调用工具
+    pub fn wants_tools(&self) -> bool {
+        !self.tool_calls.is_empty()
+    }
+
+    /// 回答是否因长度限制被截断
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == Some(FinishReason::Length)
+    }
+
+    /// 合并续写结果：内容拼接、用量和耗时累加，结束原因以最后一轮为准
+    pub fn append_continuation(&mut self, continuation: ChatResult) {
+        self.content.push_str(&continuation.content);
+        if let Some(extra) = &continuation.reasoning {
+            self.reasoning.get_or_insert_with(String::new).push_str(extra);
+        }
+        self.finish_reason = continuation.finish_reason;
+        self.add_metrics(&continuation);
+    }
+
+    /// 合并工具调用循环中前序请求的用量和耗时，首字耗时以最早的请求为准
+    pub fn absorb_step_metrics(&mut self, step: &ChatResult) {
+        self.add_metrics(step);
+        if step.timings.first_token_ms.is_some() {
+            self.timings.first_token_ms = step.timings.first_token_ms;
+        }
+    }
+
+    fn add_metrics(&mut self, other: &ChatResult) {
+        self.timings.total_ms += other.timings.total_ms;
+        match (&mut self.usage, &other.usage) {
+            (Some(usage), Some(extra)) => usage.accumulate(extra),
+            (usage @ None, extra) => *usage = extra.clone(),
+            _ => {}
+        }
+    }
+}
+
+/// 单个分片中新增的内容
+#[derive(Debug, Default, PartialEq)]
+pub struct StreamDelta {
+    pub reasoning: Option<String>,
+    pub content: Option<String>,
+}
+
+/// 逐个分片累积流式响应
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    pub id: Option<String>,
+    pub model: Option<String>,
+    pub content: String,
+    pub reasoning: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<Usage>,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 合并一个分片，返回其中新增的思考内容和回答内容
+    pub fn push(&mut self, response: StreamResponse) -> StreamDelta {
+        if self.id.is_none() {
+            self.id = response.id;
+        }
+        if self.model.is_none() {
+            self.model = response.model;
+        }
+        if response.usage.is_some() {
+            self.usage = response.usage;
+        }
+
+        let Some(choice) = response.choices.into_iter().next() else {
+            return StreamDelta::default();
+        };
+        if let Some(reason) = &choice.finish_reason {
+            self.finish_reason = Some(FinishReason::parse(reason));
+        }
+
+        let reasoning = choice.delta.reasoning_content.filter(|r| !r.is_empty());
+        if let Some(reasoning) = &reasoning {
+            self.reasoning.push_str(reasoning);
+        }
+        let content = choice.delta.content.filter(|c| !c.is_empty());
+        if let Some(content) = &content {
+            self.content.push_str(content);
+        }
+        for fragment in choice.delta.tool_calls.unwrap_or_default() {
+            self.push_tool_call(fragment);
+        }
+        StreamDelta { reasoning, content }
+    }
+
+    /// 按 `index` 拼接工具调用片段：ID 和名称通常只在第一个片段出现，参数分多次到达
+    fn push_tool_call(&mut self, fragment: ToolCallDelta) {
+        while self.tool_calls.len() <= fragment.index {
+            self.tool_calls.push(ToolCall {
+                kind: default_tool_type(),
+                ..Default::default()
+            });
+        }
+        let call = &mut self.tool_calls[fragment.index];
+        if let Some(id) = fragment.id.filter(|id| !id.is_empty()) {
+            call.id = id;
+        }
+        if let Some(function) = fragment.function {
+            if let Some(name) = function.name {
+                call.function.name.push_str(&name);
+            }
+            if let Some(arguments) = function.arguments {
+                call.function.arguments.push_str(&arguments);
+            }
+        }
+    }
+
+    pub fn into_result(self, requested_model: &str, timings: ChatTimings) -> ChatResult {
+        ChatResult {
+            message_id: generate_message_id(),
+            response_id: self.id.filter(|id| !id.is_empty()),
+            content: self.content,
+            reasoning: Some(self.reasoning).filter(|r| !r.is_empty()),
+            tool_calls: self
+                .tool_calls
+                .into_iter()
+                .filter(|call| !call.function.name.is_empty())
+                .collect(),
+            finish_reason: self.finish_reason,
+            usage: self.usage,
+            timings,
+            model: self.model.unwrap_or_else(|| requested_model.to_string()),
+        }
+    }
+}
+
+/// 按行切分 SSE 字节流。
+///
+/// 只解码完整的行，多字节字符被拆到两个分片时不会变成替换字符。
+#[derive(Debug, Default)]
+pub struct SseLineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl SseLineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个分片，返回其中已经完整的行，不含行尾的换行符
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        lines
+    }
+
+    /// 流结束后剩余的最后一行（没有以换行结尾）
+    pub fn finish(self) -> Option<String> {
+        let line = String::from_utf8_lossy(&self.buffer).trim_end().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+/// 解析一行 SSE 数据，`[DONE]` 和无法解析的行返回 None
+pub fn parse_stream_line(line: &str) -> Option<StreamResponse> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    serde_json::from_str(data).ok()
+}
+
+/// 生成本地 ID：前缀 + 毫秒时间戳 + 随机后缀
+pub fn generate_id(prefix: &str) -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{}-{:x}{:08x}", prefix, millis, rand::random::<u32>())
+}
+
+pub fn generate_message_id() -> String {
+    generate_id("msg")
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::cache::{get_cache_dir, read_json_file, write_json_file};
+use crate::chat::{generate_id, ChatResult, ChatTimings, FinishReason, Usage};
+use crate::session::now_millis;
+
+const COMPARISONS_DIR: &str = "comparisons";
+
+/// 一次对比最多同时请求的模型数
+pub const MAX_COMPARE_MODELS: usize = 8;
+
+/// 对比模式下推送给前端的分片，`stream_id` 区分不同模型的回答
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CompareDelta {
+    pub stream_id: String,
+    /// 模型在请求列表中的位置
+    pub index: usize,
+    pub text: String,
+}
+
+/// 对比中一个模型的回答及统计
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ComparisonEntry {
+    pub stream_id: String,
+    pub model: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub timings: ChatTimings,
+    /// 生成速度（token/秒），按首个分片之后的耗时计算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_second: Option<f64>,
+    /// 请求失败时的错误信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ComparisonEntry {
+    pub fn new(stream_id: &str, model: &str, outcome: Result<ChatResult, String>) -> Self {
+        let mut entry = ComparisonEntry {
+            stream_id: stream_id.to_string(),
+            model: model.to_string(),
+            content: String::new(),
+            reasoning: None,
+            finish_reason: None,
+            usage: None,
+            timings: ChatTimings::default(),
+            tokens_per_second: None,
+            error: None,
+        };
+        match outcome {
+            Ok(result) => {
+                entry.content = result.content;
+                entry.reasoning = result.reasoning;
+                entry.finish_reason = result.finish_reason;
+                entry.usage = result.usage;
+                entry.timings = result.timings;
+                entry.tokens_per_second = tokens_per_second(entry.usage.as_ref(), &entry.timings);
+            }
+            Err(e) => entry.error = Some(e),
+        }
+        entry
+    }
+}
+
+/// 没有用量或耗时为 0 时为空
+fn tokens_per_second(usage: Option<&Usage>, timings: &ChatTimings) -> Option<f64> {
+    let completion_tokens = usage?.completion_tokens;
+    let generation_ms = timings.total_ms.saturating_sub(timings.first_token_ms.unwrap_or_default());
+    (generation_ms > 0 && completion_tokens > 0).then(|| completion_tokens as f64 * 1000.0 / generation_ms as f64)
+}
+
+/// 同一问题发给多个模型的对比记录
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Comparison {
+    pub id: String,
+    pub prompt: String,
+    /// 提供历史消息的会话
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub created_at: i64,
+    pub entries: Vec<ComparisonEntry>,
+}
+
+/// 对比列表中展示的摘要信息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ComparisonSummary {
+    pub id: String,
+    pub prompt: String,
+    pub models: Vec<String>,
+    pub created_at: i64,
+}
+
+impl Comparison {
+    pub fn new(prompt: &str, session_id: Option<String>) -> Self {
+        Comparison {
+            id: generate_id("compare"),
+            prompt: prompt.to_string(),
+            session_id,
+            created_at: now_millis(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// 第 `index` 个模型的流 ID
+    pub fn stream_id(&self, index: usize) -> String {
+        format!("{}-{}", self.id, index)
+    }
+
+    pub fn summary(&self) -> ComparisonSummary {
+        ComparisonSummary {
+            id: self.id.clone(),
+            prompt: self.prompt.clone(),
+            models: self.entries.iter().map(|entry| entry.model.clone()).collect(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// 校验对比的模型列表
+pub fn validate_models(models: &[String]) -> Result<(), String> {
+    if models.len() < 2 {
+        return Err("至少需要选择两个模型进行对比".to_string());
+    }
+    if models.len() > MAX_COMPARE_MODELS {
+        return Err(format!("一次最多对比 {} 个模型", MAX_COMPARE_MODELS));
+    }
+    if models.iter().any(|model| model.trim().is_empty()) {
+        return Err("模型名称不能为空".to_string());
+    }
+    Ok(())
+}
+
+pub fn comparisons_dir() -> PathBuf {
+    get_cache_dir().join(COMPARISONS_DIR)
+}
+
+fn comparison_file(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    // 对比 ID 直接作为文件名，拒绝可能跳出目录的 ID
+    if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+        return Err(format!("无效的对比 ID: {}", id));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+pub fn load_comparison(dir: &Path, id: &str) -> Result<Comparison, String> {
+    let path = comparison_file(dir, id)?;
+    if !path.exists() {
+        return Err(format!("对比记录不存在: {}", id));
+    }
+    read_json_file(&path)
+}
+
+pub fn save_comparison(dir: &Path, comparison: &Comparison) -> Result<(), String> {
+    write_json_file(&comparison_file(dir, &comparison.id)?, comparison)
+}
+
+pub fn delete_comparison(dir: &Path, id: &str) -> Result<(), String> {
+    let path = comparison_file(dir, id)?;
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| format!("删除对比记录失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 列出所有对比记录的摘要，按创建时间倒序
+pub fn list_comparisons(dir: &Path) -> Result<Vec<ComparisonSummary>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut summaries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("读取对比目录失败: {}", e))? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match read_json_file::<Comparison>(&path) {
+            Ok(comparison) => summaries.push(comparison.summary()),
+            Err(e) => warn!("跳过无法解析的对比记录: {}", e),
+        }
+    }
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.created_at));
+    Ok(summaries)
+}
@@ -0,0 +1,376 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{Local, TimeZone};
+use lazy_static::lazy_static;
+use printpdf::{Color, Greyscale, IndirectFontRef, Mm, PdfDocument, Pt};
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use crate::persist::write_atomic;
+use crate::search::escape_html;
+use crate::session::{Session, StoredMessage};
+
+/// 代码高亮使用的 syntect 内置主题
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME: Theme = ThemeSet::load_defaults().themes.remove(HIGHLIGHT_THEME).unwrap_or_default();
+}
+
+/// 未指定字体时依次尝试的系统中文字体，PDF 只能嵌入单个 TrueType 字体，不支持 `.ttc`
+const PDF_FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\Deng.ttf",
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/usr/share/fonts/truetype/arphic-gbsn00lp/gbsn00lp.ttf",
+    "/usr/share/fonts/wqy-microhei/wqy-microhei.ttf",
+];
+
+// A4 纸，单位毫米
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const PAGE_MARGIN: f32 = 20.0;
+
+const TITLE_FONT_SIZE: f32 = 16.0;
+const BODY_FONT_SIZE: f32 = 10.5;
+const LINE_SPACING: f32 = 1.5;
+
+/// 导出格式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+    Pdf,
+}
+
+impl ExportFormat {
+    /// 按扩展名判断导出格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "json" => Some(ExportFormat::Json),
+            "pdf" => Some(ExportFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "用户",
+        "assistant" => "助手",
+        "system" => "系统",
+        "tool" => "工具",
+        other => other,
+    }
+}
+
+fn format_time(millis: i64) -> String {
+    Local
+        .timestamp_millis_opt(millis)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// 消息标题行：角色、时间和模型
+fn message_heading(message: &StoredMessage) -> String {
+    let mut heading = format!("{} · {}", role_label(&message.role), format_time(message.created_at));
+    if let Some(model) = &message.model {
+        heading.push_str(&format!(" · {}", model));
+    }
+    heading
+}
+
+/// 附件说明，每个附件一行
+fn attachment_lines(message: &StoredMessage) -> Vec<String> {
+    let images = message
+        .images
+        .iter()
+        .map(|image| format!("图片：{}（{}×{}）", image.name, image.width, image.height));
+    let documents = message
+        .documents
+        .iter()
+        .map(|document| format!("文档：{}（{} 字节）", document.name, document.size));
+    images.chain(documents).collect()
+}
+
+/// 会话中使用过的模型，按名称排序
+fn session_models(session: &Session) -> Vec<&str> {
+    session
+        .active_path()
+        .into_iter()
+        .filter_map(|message| message.model.as_deref())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn session_info_lines(session: &Session) -> Vec<String> {
+    let mut lines = vec![
+        format!("创建时间：{}", format_time(session.created_at)),
+        format!("更新时间：{}", format_time(session.updated_at)),
+    ];
+    let models = session_models(session);
+    if !models.is_empty() {
+        lines.push(format!("模型：{}", models.join(", ")));
+    }
+    lines
+}
+
+/// 导出为 Markdown，消息内容原样保留
+pub fn to_markdown(session: &Session) -> String {
+    let mut markdown = format!("# {}\n\n", session.title);
+    for line in session_info_lines(session) {
+        markdown.push_str(&format!("- {}\n", line));
+    }
+
+    for message in session.active_path() {
+        markdown.push_str(&format!("\n---\n\n### {}\n\n", message_heading(message)));
+        markdown.push_str(message.content.trim_end());
+        markdown.push('\n');
+        let attachments = attachment_lines(message);
+        if !attachments.is_empty() {
+            markdown.push('\n');
+            for line in attachments {
+                markdown.push_str(&format!("> {}\n", line));
+            }
+        }
+    }
+    markdown
+}
+
+/// 高亮代码块，语言未知时按纯文本处理
+fn highlight_code(code: &str, language: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    highlighted_html_for_string(code, &SYNTAX_SET, syntax, &THEME)
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", escape_html(code)))
+}
+
+/// 将 Markdown 渲染为 HTML，代码块按语言高亮，消息中的原始 HTML 按文本转义
+pub fn render_markdown(content: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut events = Vec::new();
+    let mut code_block: Option<(String, String)> = None;
+    for event in Parser::new_ext(content, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default().to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code_block.take() {
+                    events.push(Event::Html(highlight_code(&code, &language).into()));
+                }
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = &mut code_block {
+                    code.push_str(&text);
+                }
+            }
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            event => events.push(event),
+        }
+    }
+    let mut output = String::new();
+    html::push_html(&mut output, events.into_iter());
+    output
+}
+
+const HTML_STYLE: &str = r#"
+body { max-width: 860px; margin: 2em auto; padding: 0 1em; font-family: -apple-system, "Segoe UI", "PingFang SC", "Microsoft YaHei", sans-serif; line-height: 1.6; color: #24292f; }
+header { border-bottom: 1px solid #d0d7de; margin-bottom: 1.5em; }
+header ul { list-style: none; padding: 0; color: #57606a; font-size: 0.9em; }
+.message { margin: 1.5em 0; padding: 0.8em 1.2em; border-radius: 8px; }
+.message.user { background: #f6f8fa; }
+.message.assistant { border: 1px solid #d0d7de; }
+.meta { color: #57606a; font-size: 0.85em; }
+.attachments { color: #57606a; font-size: 0.9em; }
+.attachments img { max-width: 100%; border-radius: 4px; }
+pre { padding: 0.8em; border-radius: 6px; overflow-x: auto; }
+code { font-family: "SFMono-Regular", Consolas, "Liberation Mono", monospace; font-size: 0.9em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 4px 8px; }
+"#;
+
+/// 导出为独立的 HTML 页面，样式和图片均内嵌在文件中
+pub fn to_html(session: &Session) -> String {
+    let title = escape_html(&session.title);
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<header>\n<h1>{}</h1>\n<ul>\n",
+        title, HTML_STYLE, title
+    );
+    for line in session_info_lines(session) {
+        page.push_str(&format!("<li>{}</li>\n", escape_html(&line)));
+    }
+    page.push_str("</ul>\n</header>\n");
+
+    for message in session.active_path() {
+        page.push_str(&format!(
+            "<section class=\"message {}\">\n<div class=\"meta\">{}</div>\n",
+            escape_html(&message.role),
+            escape_html(&message_heading(message))
+        ));
+        page.push_str(&render_markdown(&message.content));
+        if !message.images.is_empty() || !message.documents.is_empty() {
+            page.push_str("<div class=\"attachments\">\n");
+            for image in &message.images {
+                page.push_str(&format!(
+                    "<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+                    escape_html(&image.data_url),
+                    escape_html(&image.name),
+                    escape_html(&image.name)
+                ));
+            }
+            for document in &message.documents {
+                page.push_str(&format!("<p>文档：{}（{} 字节）</p>\n", escape_html(&document.name), document.size));
+            }
+            page.push_str("</div>\n");
+        }
+        page.push_str("</section>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+/// 导出为 JSON，包含会话的全部字段
+pub fn to_json(session: &Session) -> Result<String, String> {
+    serde_json::to_string_pretty(session).map_err(|e| format!("序列化失败: {}", e))
+}
+
+/// PDF 中的一行文本
+struct PdfLine {
+    text: String,
+    font_size: f32,
+    /// 灰度，0 为黑色
+    gray: f32,
+}
+
+/// 估算字符宽度（单位为字号），中日韩等宽字符按全角计算
+fn char_width(c: char) -> f32 {
+    if c > '\u{2E7F}' {
+        1.0
+    } else {
+        0.6
+    }
+}
+
+/// 按可用宽度（毫米）折行
+fn wrap_text(text: &str, font_size: f32, max_width: f32) -> Vec<String> {
+    let max_em = max_width / Mm::from(Pt(font_size)).0;
+    let mut lines = Vec::new();
+    for raw_line in text.replace('\t', "    ").lines() {
+        let mut line = String::new();
+        let mut width = 0.0;
+        for c in raw_line.chars() {
+            let w = char_width(c);
+            if width + w > max_em && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                width = 0.0;
+            }
+            line.push(c);
+            width += w;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn pdf_lines(session: &Session) -> Vec<PdfLine> {
+    let content_width = PAGE_WIDTH - 2.0 * PAGE_MARGIN;
+    let mut lines = Vec::new();
+    let mut push = |text: &str, font_size: f32, gray: f32| {
+        for text in wrap_text(text, font_size, content_width) {
+            lines.push(PdfLine { text, font_size, gray });
+        }
+    };
+
+    push(&session.title, TITLE_FONT_SIZE, 0.0);
+    for line in session_info_lines(session) {
+        push(&line, BODY_FONT_SIZE, 0.45);
+    }
+    for message in session.active_path() {
+        push("", BODY_FONT_SIZE, 0.0);
+        push(&message_heading(message), BODY_FONT_SIZE, 0.45);
+        push(message.content.trim_end(), BODY_FONT_SIZE, 0.0);
+        for line in attachment_lines(message) {
+            push(&line, BODY_FONT_SIZE, 0.45);
+        }
+    }
+    lines
+}
+
+/// 查找可用于 PDF 的系统中文字体
+pub fn find_pdf_font() -> Option<PathBuf> {
+    PDF_FONT_CANDIDATES.iter().map(PathBuf::from).find(|path| path.is_file())
+}
+
+/// 导出为 PDF，消息内容按纯文本排版，`font` 为嵌入的 TrueType 字体数据（整个字体嵌入，不做子集化）
+pub fn to_pdf(session: &Session, font: &[u8]) -> Result<Vec<u8>, String> {
+    let (document, page, layer) = PdfDocument::new(session.title.as_str(), Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "内容");
+    let font: IndirectFontRef = document.add_external_font(font).map_err(|e| format!("加载字体失败: {}", e))?;
+
+    let mut layer = document.get_page(page).get_layer(layer);
+    let mut y = PAGE_HEIGHT - PAGE_MARGIN;
+    for line in pdf_lines(session) {
+        let line_height = Mm::from(Pt(line.font_size * LINE_SPACING)).0;
+        if y - line_height < PAGE_MARGIN {
+            let (page, layer_index) = document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "内容");
+            layer = document.get_page(page).get_layer(layer_index);
+            y = PAGE_HEIGHT - PAGE_MARGIN;
+        }
+        y -= line_height;
+        if !line.text.is_empty() {
+            layer.set_fill_color(Color::Greyscale(Greyscale::new(line.gray, None)));
+            layer.use_text(line.text, line.font_size, Mm(PAGE_MARGIN), Mm(y), &font);
+        }
+    }
+
+    document.save_to_bytes().map_err(|e| format!("生成 PDF 失败: {}", e))
+}
+
+/// 按格式渲染会话，JSON 包含所有分支，其余格式只包含当前分支。PDF 使用 `font_path` 指定的字体，未指定时查找系统中文字体
+pub fn render_session(session: &Session, format: ExportFormat, font_path: Option<&Path>) -> Result<Vec<u8>, String> {
+    match format {
+        ExportFormat::Markdown => Ok(to_markdown(session).into_bytes()),
+        ExportFormat::Html => Ok(to_html(session).into_bytes()),
+        ExportFormat::Json => to_json(session).map(String::into_bytes),
+        ExportFormat::Pdf => {
+            let path = font_path
+                .map(Path::to_path_buf)
+                .or_else(find_pdf_font)
+                .ok_or("未找到可用的中文字体，请指定 TrueType 字体文件")?;
+            let font = fs::read(&path).map_err(|e| format!("读取字体失败 {}: {}", path.display(), e))?;
+            to_pdf(session, &font)
+        }
+    }
+}
+
+/// 将会话导出到文件，未指定格式时按扩展名判断
+pub fn export_session(
+    session: &Session,
+    path: &Path,
+    format: Option<ExportFormat>,
+    font_path: Option<&Path>,
+) -> Result<(), String> {
+    let format = format
+        .or_else(|| ExportFormat::from_path(path))
+        .ok_or_else(|| format!("无法根据文件名判断导出格式: {}", path.display()))?;
+    let bytes = render_session(session, format, font_path)?;
+    write_atomic(path, bytes)
+}
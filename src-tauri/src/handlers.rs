@@ -1,57 +1,134 @@
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use log::{debug, error};
+use log::{debug, error, warn};
 use std::time::Instant;
+use futures_util::future::join_all;
 use futures_util::StreamExt;
 use std::fs;
 use tauri::{Window, Emitter};
-use crate::chat::{ChatMessage, ChatPayload, StreamResponse};
-use crate::models::{AvailableModelsResponse, ModelsResponse, ModelFrequency};
+use crate::chat::{
+    generate_id, parse_stream_line, ChatMessage, ChatPayload, ChatResult, ChatTimings, GenerationParams,
+    SseLineBuffer, StreamAccumulator, StreamDelta, StreamOptions, ToolCall, ToolDefinition,
+};
+use crate::models::{AvailableModelsResponse, ModelsResponse};
+use crate::provider::{build_headers, endpoint_url, EmbeddingResult, OpenAiProvider, Provider};
 use crate::cache::{get_cache_dir, MODEL_FREQUENCIES, update_frequency, encrypt_api_key, decrypt_api_key, delete_api_key, encrypt_api_url, decrypt_api_url, delete_api_url};
+use crate::usage::{
+    ensure_within_budget, load_budgets, load_prices, record_usage, records_to_csv, save_budgets, summarize,
+    BudgetConfig, ModelPrice, SpendGroup, SpendSummary, PRICES_KEY,
+};
+use crate::persona::{
+    apply_system_prompt, build_pack, find_persona, load_personas, merge_pack, upsert_persona, Persona, PersonaImportReport, PersonaPack,
+    PERSONAS_KEY,
+};
+use crate::tools::{
+    execute_tool_call, register_pending_approval, registry_snapshot, resolve_approval, wait_for_approval,
+    ApprovalRequest, ToolOutcome, ToolRegistry, DEFAULT_MAX_TOOL_STEPS,
+};
+use crate::builtin_tools::{load_approved_dirs, APPROVED_DIRS_KEY};
+use crate::attachment::{
+    attachment_message, load_document, load_image, DocumentAttachment, ImageAttachment, DEFAULT_DOCUMENT_BUDGET,
+};
+use crate::rag::{
+    compose_with_context, delete_index, indexes_dir, list_indexes, load_index, lock_index, save_index, update_index, FolderIndex,
+    IndexReport, IndexSummary, RetrievedChunk, DEFAULT_TOP_K,
+};
+use crate::compare::{
+    self, comparisons_dir, load_comparison, save_comparison, validate_models, CompareDelta, Comparison,
+    ComparisonEntry, ComparisonSummary,
+};
+use crate::export::{self, ExportFormat};
+use crate::importer::{import_file, ImportFormat, ImportReport};
+use crate::search::{SearchFilters, SearchHit, DEFAULT_SEARCH_LIMIT, SEARCH_INDEX};
+use crate::organize::{
+    self, apply_action, load_folders, load_trash_config, purge_expired_trash, purge_session, remove_folder, save_trash_config,
+    tag_counts, BulkAction, BulkFailure, BulkReport, Folder, SessionQuery,
+    TagCount, TrashConfig, FOLDERS_KEY,
+};
+use crate::persist::write_atomic;
+use crate::storage::{update_setting, with_storage};
+use crate::summary::{
+    self, clean_title, load_summary_config, summary_cutoff, title_messages, SummaryConfig,
+    SummaryEvent, TitleEvent,
+};
+use crate::semantic::{
+    load_message_vectors, lock_message_vectors, message_vectors_file, save_message_vectors, update_message_vectors, MessageVectorReport,
+    MessageVectorStore, MessageVectorSummary, SemanticHit, DEFAULT_SEMANTIC_TOP_K,
+};
+use crate::mcp::{self, load_mcp_servers, McpServerConfig, McpServerStatus, MCP_SERVERS_KEY};
+use crate::template::{
+    find_template, load_templates, render_saved_template, PromptTemplate,
+    TemplateInvocation, TEMPLATES_KEY,
+};
+use crate::profile::{load_profiles, profile_params, Profile, PROFILES_KEY};
+use crate::session::{
+    self, load_session, save_session, update_session, BranchMessage, ContextSummary, Session, SessionSummary,
+    StoredMessage, DEFAULT_SESSION_TITLE,
+};
+use chrono::NaiveDate;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[tauri::command]
 pub fn get_cache_directory() -> PathBuf {
     get_cache_dir()
 }
 
-#[tauri::command]
-pub async fn chat(window: Window, message: String, api_key: String, api_url: String, model: String, history: Vec<ChatMessage>) -> Result<String, String> {
-    debug!("收到请求:");
-    debug!("API URL: {}", api_url);
-    debug!("Model: {}", model);
-    debug!("Message: {}", message);
-    debug!("API Key: {}****", &api_key[..4]);
-    
+/// 续写被截断回答时发送给模型的提示
+const CONTINUE_PROMPT: &str = "请从上次中断的地方继续输出，不要重复已经输出的内容。";
+
+/// 续写的默认最大轮数
+const DEFAULT_CONTINUE_ROUNDS: u32 = 3;
+
+/// 等待用户确认工具调用的最长时间，超时视为拒绝
+const TOOL_APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 流式分片推送的通道
+#[derive(Clone, Copy)]
+enum StreamChannel<'a> {
+    /// 普通聊天：思考过程走 `stream-reasoning`，回答内容走 `stream-response`
+    Chat,
+    /// 对比中的一个模型：分别走 `compare-reasoning` 和 `compare-response`，载荷为 `CompareDelta`
+    Compare { stream_id: &'a str, index: usize },
+    /// 后台请求（生成标题、摘要），不推送分片
+    Silent,
+}
+
+/// 将分片推送到前端
+fn emit_delta(window: &Window, channel: StreamChannel, delta: StreamDelta) -> Result<(), String> {
+    let events = [("stream-reasoning", "compare-reasoning", delta.reasoning), ("stream-response", "compare-response", delta.content)];
+    for (chat_event, compare_event, text) in events {
+        let Some(text) = text else { continue };
+        match channel {
+            StreamChannel::Chat => window.emit(chat_event, &text),
+            StreamChannel::Compare { stream_id, index } => {
+                window.emit(compare_event, &CompareDelta { stream_id: stream_id.to_string(), index, text })
+            }
+            StreamChannel::Silent => Ok(()),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 发送流式请求，将内容分片通过 `channel` 对应的事件推送到前端，返回累积后的结果
+async fn stream_chat(
+    window: &Window,
+    channel: StreamChannel<'_>,
+    api_url: &str,
+    api_key: &str,
+    payload: &ChatPayload,
+) -> Result<ChatResult, String> {
     let start_time = Instant::now();
     
     let client = reqwest::Client::new();
-    
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| e.to_string())?
-    );
-
-    let mut messages = history;
-    messages.push(ChatMessage {
-        role: "user".to_string(),
-        content: message.clone(),
-    });
-
-    let payload = ChatPayload {
-        model: model.clone(),
-        messages,
-        stream: true,
-    };
+    let headers = build_headers(api_key)?;
 
     debug!("发送到 API 的数据: {:?}", payload);
 
     let response = client
-        .post(&api_url)
+        .post(api_url)
         .headers(headers)
-        .json(&payload)
+        .json(payload)
         .send()
         .await
         .map_err(|e| e.to_string())?;
@@ -59,67 +136,692 @@ pub async fn chat(window: Window, message: String, api_key: String, api_url: Str
     if !response.status().is_success() {
         let error_msg = format!("API request failed with status: {}", response.status());
         error!("请求失败: {}", error_msg);
-        update_frequency(model.clone(), false);
         return Err(error_msg);
     }
 
     let mut stream = response.bytes_stream();
-    let mut total_content = String::new();
+    let mut accumulator = StreamAccumulator::new();
+    let mut first_token_ms = None;
+    // 分片可能在行中间甚至字符中间截断，未完整的行留到下一个分片再解析
+    let mut lines = SseLineBuffer::new();
 
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| e.to_string())?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        
-        for line in chunk_str.lines() {
-            if line.starts_with("data: ") {
-                let data = &line["data: ".len()..];
-                if data == "[DONE]" {
-                    continue;
+        for line in lines.push(&chunk) {
+            if let Some(stream_response) = parse_stream_line(&line) {
+                let delta = accumulator.push(stream_response);
+                if delta.reasoning.is_some() || delta.content.is_some() {
+                    first_token_ms.get_or_insert(start_time.elapsed().as_millis() as u64);
                 }
-                
-                if let Ok(stream_response) = serde_json::from_str::<StreamResponse>(data) {
-                    if let Some(choice) = stream_response.choices.first() {
-                        if let Some(content) = &choice.delta.content {
-                            // 发送流式内容到前端
-                            window.emit("stream-response", &content).map_err(|e| e.to_string())?;
-                            total_content.push_str(content);
+                emit_delta(window, channel, delta)?;
+            }
+        }
+    }
+    if let Some(stream_response) = lines.finish().as_deref().and_then(parse_stream_line) {
+        emit_delta(window, channel, accumulator.push(stream_response))?;
+    }
+
+    let timings = ChatTimings {
+        first_token_ms,
+        total_ms: start_time.elapsed().as_millis() as u64,
+    };
+    let result = accumulator.into_result(&payload.model, timings);
+    debug!("响应完成: finish_reason={:?}, usage={:?}, 耗时 {}ms", result.finish_reason, result.usage, result.timings.total_ms);
+    if result.is_truncated() {
+        warn!("回答因长度限制被截断: {}", result.message_id);
+    }
+    Ok(result)
+}
+
+/// 将一次请求的 token 用量记入账本，记账失败只记录日志，不影响聊天结果
+fn record_result_usage(result: &ChatResult, profile: Option<&str>) {
+    let Some(usage) = &result.usage else {
+        warn!("响应未返回 token 用量，跳过记账: {}", result.message_id);
+        return;
+    };
+    if let Err(e) = record_usage(&result.message_id, &result.model, profile, usage) {
+        error!("记录用量失败: {}", e);
+    }
+}
+
+/// 合并生成参数：请求参数 > 会话参数 > 人设参数 > 配置参数 > 默认配置参数
+fn resolve_params(
+    request: Option<GenerationParams>,
+    session: Option<&Session>,
+    persona: Option<&Persona>,
+    profile: Option<&str>,
+) -> Result<GenerationParams, String> {
+    let profiles = with_storage(|storage| load_profiles(storage))?;
+    let mut params = profile_params(&profiles, profile);
+    if let Some(persona) = persona {
+        params = persona.params.clone().merged_over(&params);
+    }
+    if let Some(session) = session {
+        params = session.params.clone().merged_over(&params);
+    }
+    if let Some(request) = request {
+        params = request.merged_over(&params);
+    }
+    params.validate()?;
+    Ok(params)
+}
+
+fn load_optional_session(session_id: Option<&str>) -> Result<Option<Session>, String> {
+    session_id
+        .map(load_session)
+        .transpose()
+}
+
+/// 被编辑的用户消息的父消息，新问答挂在它下面
+fn edited_parent(session: &Session, message_id: &str) -> Result<Option<String>, String> {
+    session
+        .find_message(message_id)
+        .filter(|m| m.role == "user")
+        .map(|m| m.parent_id.clone())
+        .ok_or_else(|| format!("找不到要编辑的用户消息: {}", message_id))
+}
+
+/// 读取会话选择的人设，人设已被删除时忽略并记录警告
+fn session_persona(session: Option<&Session>) -> Result<Option<Persona>, String> {
+    let Some(name) = session.and_then(|s| s.persona.as_deref()) else {
+        return Ok(None);
+    };
+    let persona = find_persona(&with_storage(|storage| load_personas(storage))?, name);
+    if persona.is_none() {
+        warn!("会话使用的人设不存在: {}", name);
+    }
+    Ok(persona)
+}
+
+/// 通过 `tool-approval-request` 事件请求用户确认工具调用，返回是否允许执行
+async fn request_tool_approval(window: &Window, registry: &ToolRegistry, call: &ToolCall) -> Result<bool, String> {
+    let request = ApprovalRequest {
+        request_id: generate_id("approval"),
+        tool_call: call.clone(),
+        description: registry
+            .get(&call.function.name)
+            .map(|tool| tool.description().to_string())
+            .unwrap_or_default(),
+    };
+    let receiver = register_pending_approval(&request.request_id);
+    window.emit("tool-approval-request", &request).map_err(|e| e.to_string())?;
+    Ok(wait_for_approval(&request.request_id, receiver, TOOL_APPROVAL_TIMEOUT).await)
+}
+
+/// 执行带工具调用的对话循环：模型请求工具时执行工具并以 `tool` 消息回传结果，
+/// 直到模型给出最终回答。达到 `max_steps` 后不再提供工具，要求模型直接作答。
+///
+/// 每次工具调用和执行结果分别通过 `tool-call`、`tool-result` 事件推送到前端，
+/// 需要确认的工具在用户允许后才会执行。
+async fn run_tool_loop(
+    window: &Window,
+    api_url: &str,
+    api_key: &str,
+    mut payload: ChatPayload,
+    registry: &ToolRegistry,
+    max_steps: u32,
+    profile: Option<&str>,
+) -> Result<ChatResult, String> {
+    let mut steps: Vec<ChatResult> = Vec::new();
+
+    let mut result = loop {
+        if steps.len() as u32 >= max_steps && payload.tools.is_some() {
+            warn!("工具调用达到步数上限 {}，要求模型直接回答", max_steps);
+            payload.tools = None;
+        }
+
+        let result = stream_chat(window, StreamChannel::Chat, api_url, api_key, &payload).await?;
+        record_result_usage(&result, profile);
+        if !result.wants_tools() || payload.tools.is_none() {
+            break result;
+        }
+
+        payload
+            .messages
+            .push(ChatMessage::assistant_tool_calls(&result.content, result.tool_calls.clone()));
+        for call in &result.tool_calls {
+            debug!("调用工具 {}: {}", call.function.name, call.function.arguments);
+            window.emit("tool-call", call).map_err(|e| e.to_string())?;
+            let approved = !registry.requires_approval(&call.function.name)
+                || request_tool_approval(window, registry, call).await?;
+            let outcome = if approved {
+                execute_tool_call(registry, call).await
+            } else {
+                ToolOutcome::denied(call)
+            };
+            window.emit("tool-result", &outcome).map_err(|e| e.to_string())?;
+            payload
+                .messages
+                .push(ChatMessage::tool_result(&outcome.tool_call_id, &outcome.content));
+        }
+        steps.push(result);
+    };
+
+    // 倒序合并，首字耗时取最早一次请求
+    for step in steps.iter().rev() {
+        result.absorb_step_metrics(step);
+    }
+    Ok(result)
+}
+
+/// 生成向量并记录用量
+async fn embed_texts(provider: &dyn Provider, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+    let result = provider.embed(model, input).await?;
+    record_embedding_usage(&result, None);
+    Ok(result.embeddings)
+}
+
+fn record_embedding_usage(result: &EmbeddingResult, profile: Option<&str>) {
+    if let Some(usage) = &result.usage {
+        if let Err(e) = record_usage(&generate_id("embedding"), &result.model, profile, usage) {
+            error!("记录用量失败: {}", e);
+        }
+    }
+}
+
+/// 增量更新索引后检索与查询最相关的分块，索引有变化时写回磁盘
+async fn retrieve_from_index(
+    api_url: &str,
+    api_key: &str,
+    index_id: &str,
+    query: &str,
+    top_k: usize,
+) -> Result<Vec<RetrievedChunk>, String> {
+    let dir = indexes_dir();
+    let _lock = lock_index(&dir, index_id).await?;
+    let mut index = load_index(&dir, index_id)?;
+    let model = index.model.clone();
+    let provider = OpenAiProvider::new(api_url, api_key);
+    let report = update_index(&mut index, |input| embed_texts(&provider, &model, input)).await?;
+    if report.has_changes() {
+        debug!("索引 {} 已增量更新: {:?}", index_id, report);
+        save_index(&dir, &index)?;
+    }
+
+    let query_embedding = embed_texts(&provider, &model, vec![query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    Ok(index.search(&query_embedding, top_k))
+}
+
+/// 增量更新会话消息的向量并写回磁盘，未启用语义搜索时不做任何事。
+///
+/// `all_sessions` 为 true 时 `sessions` 为全部会话，同时删除已删除会话的向量。
+async fn sync_message_vectors(
+    api_url: &str,
+    api_key: &str,
+    store: &mut MessageVectorStore,
+    sessions: &[Session],
+    all_sessions: bool,
+) -> Result<MessageVectorReport, String> {
+    if !store.is_enabled() {
+        return Ok(MessageVectorReport::default());
+    }
+    let provider = OpenAiProvider::new(api_url, api_key);
+    let model = store.model.clone();
+    let mut report = update_message_vectors(store, sessions, |input| embed_texts(&provider, &model, input)).await?;
+    if all_sessions {
+        report.removed += store.retain_sessions(&sessions.iter().map(|s| s.id.as_str()).collect());
+    }
+    if report.has_changes() {
+        debug!("消息向量已增量更新: {:?}", report);
+        save_message_vectors(&message_vectors_file(), store)?;
+    }
+    Ok(report)
+}
+
+/// 在后台为会话中新写入的消息生成向量，失败时只记录日志
+fn spawn_message_vector_update(api_url: &str, api_key: &str, session: Session) {
+    let api_url = api_url.to_string();
+    let api_key = api_key.to_string();
+    tauri::async_runtime::spawn(async move {
+        let result = async {
+            let _lock = lock_message_vectors().await?;
+            let mut store = load_message_vectors(&message_vectors_file())?;
+            sync_message_vectors(&api_url, &api_key, &mut store, &[session], false).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("更新消息向量失败: {}", e);
+        }
+    });
+}
+
+/// 后台请求一次回答（生成标题、摘要），不推送分片，用量计入会话的配置
+async fn complete_silently(
+    window: &Window,
+    api_url: &str,
+    api_key: &str,
+    model: &str,
+    messages: Vec<ChatMessage>,
+    profile: Option<&str>,
+) -> Result<ChatResult, String> {
+    ensure_within_budget(profile)?;
+    let payload = ChatPayload {
+        model: model.to_string(),
+        messages,
+        stream: true,
+        stream_options: Some(StreamOptions { include_usage: true }),
+        params: GenerationParams::default(),
+        tools: None,
+    };
+    let result = stream_chat(window, StreamChannel::Silent, api_url, api_key, &payload).await?;
+    record_result_usage(&result, profile);
+    Ok(result)
+}
+
+/// 会话仍为默认标题时在后台生成标题，完成后通过 `session-title-updated` 事件推送；失败只记录日志
+fn spawn_auto_title(window: &Window, api_url: &str, api_key: &str, session: &Session, chat_model: &str) {
+    let config = match with_storage(|storage| load_summary_config(storage)) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("读取摘要配置失败: {}", e);
+            return;
+        }
+    };
+    if !config.auto_title || !summary::needs_title(session) {
+        return;
+    }
+    let Some(messages) = title_messages(session) else {
+        return;
+    };
+    let window = window.clone();
+    let api_url = api_url.to_string();
+    let api_key = api_key.to_string();
+    let model = config.model_or(chat_model).to_string();
+    let session_id = session.id.clone();
+    let profile = session.profile.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = async {
+            let answer = complete_silently(&window, &api_url, &api_key, &model, messages, profile.as_deref()).await?;
+            let title = clean_title(&answer.content).ok_or("模型返回的标题为空")?;
+            // 重新读取会话，避免覆盖生成期间的修改；用户已手动改名时保留用户的标题
+            let renamed = update_session(&session_id, |session| {
+                if session.title != DEFAULT_SESSION_TITLE {
+                    return Ok(false);
+                }
+                session.title = title.clone();
+                Ok(true)
+            })?;
+            if !renamed {
+                return Ok(());
+            }
+            window
+                .emit("session-title-updated", &TitleEvent { session_id: session_id.clone(), title })
+                .map_err(|e| e.to_string())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("自动生成会话标题失败: {}", e);
+        }
+    });
+}
+
+/// 会话关联了目录索引时，检索相关分块拼接在用户消息之前
+async fn compose_prompt(api_url: &str, api_key: &str, session: Option<&Session>, message: &str) -> Result<String, String> {
+    match session.and_then(|s| s.index_id.as_deref()) {
+        Some(index_id) => {
+            let chunks = retrieve_from_index(api_url, api_key, index_id, message, DEFAULT_TOP_K).await?;
+            Ok(compose_with_context(message, &chunks))
+        }
+        None => Ok(message.to_string()),
+    }
+}
+
+/// 构造流式请求，会话启用工具时附带全局注册表中的工具，同时返回工具调用步数上限
+fn build_payload(
+    session: Option<&Session>,
+    registry: &ToolRegistry,
+    model: &str,
+    messages: Vec<ChatMessage>,
+    params: GenerationParams,
+) -> (ChatPayload, u32) {
+    let tools_enabled = session.is_some_and(|s| s.tools_enabled) && !registry.is_empty();
+    let max_tool_steps = session.and_then(|s| s.max_tool_steps).unwrap_or(DEFAULT_MAX_TOOL_STEPS);
+    let payload = ChatPayload {
+        model: model.to_string(),
+        messages,
+        stream: true,
+        stream_options: Some(StreamOptions { include_usage: true }),
+        params,
+        tools: tools_enabled.then(|| registry.definitions()),
+    };
+    (payload, max_tool_steps)
+}
+
+/// 发送消息并流式返回回答。
+///
+/// 指定 `session_id` 且 `history` 为空时使用会话中的历史消息，完成后将本轮问答写入会话。
+/// 生成参数按 `params` > 会话 > 人设 > 配置 > 默认配置的顺序合并。
+/// 指定 `template` 时以渲染后的模板作为用户消息，忽略 `message`。
+/// `images` 和 `documents` 为通过 `attach_image`、`attach_document` 读取的附件，与用户消息一起发送并保存到会话。
+/// 会话关联了目录索引时，检索相关分块拼接在发送的用户消息之前，会话中只保存原始消息。
+/// 会话启用工具时进入工具调用循环，会话只保存用户消息和最终回答。
+/// 指定 `edit_message_id` 时视为编辑会话中的这条用户消息：历史只取到它之前，新的问答作为它的兄弟分支保存，必须同时指定 `session_id`。
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn chat(
+    window: Window,
+    message: String,
+    api_key: String,
+    api_url: String,
+    model: String,
+    history: Vec<ChatMessage>,
+    profile: Option<String>,
+    session_id: Option<String>,
+    params: Option<GenerationParams>,
+    template: Option<TemplateInvocation>,
+    images: Option<Vec<ImageAttachment>>,
+    documents: Option<Vec<DocumentAttachment>>,
+    edit_message_id: Option<String>,
+) -> Result<ChatResult, String> {
+    let images = images.unwrap_or_default();
+    let documents = documents.unwrap_or_default();
+    let message = match &template {
+        Some(template) => render_saved_template(template)?,
+        None => message,
+    };
+
+    debug!("收到请求:");
+    debug!("API URL: {}", api_url);
+    debug!("Model: {}", model);
+    debug!("Message: {}", message);
+    debug!("API Key: {}****", &api_key[..4]);
+
+    if edit_message_id.is_some() && session_id.is_none() {
+        return Err("编辑消息需要指定会话".to_string());
+    }
+    let session = load_optional_session(session_id.as_deref())?;
+    let profile = profile.or_else(|| session.as_ref().and_then(|s| s.profile.clone()));
+    ensure_within_budget(profile.as_deref())?;
+    let persona = session_persona(session.as_ref())?;
+    let params = resolve_params(params, session.as_ref(), persona.as_ref(), profile.as_deref())?;
+    // 未指定模型时使用人设的默认模型
+    let model = match persona.as_ref().and_then(|p| p.default_model.clone()) {
+        Some(default_model) if model.trim().is_empty() => default_model,
+        _ => model,
+    };
+
+    if let (Some(session), Some(id)) = (&session, &edit_message_id) {
+        edited_parent(session, id)?;
+    }
+
+    let mut messages = match (&session, &edit_message_id) {
+        (Some(session), Some(id)) if history.is_empty() => session.history_before(id),
+        (Some(session), None) if history.is_empty() => session.history(),
+        _ => history,
+    };
+    if let Some(persona) = &persona {
+        apply_system_prompt(persona, &mut messages);
+    }
+    let prompt = compose_prompt(&api_url, &api_key, session.as_ref(), &message).await?;
+    messages.push(attachment_message("user", &prompt, &images, &documents));
+
+    let registry = registry_snapshot();
+    let (payload, max_tool_steps) = build_payload(session.as_ref(), &registry, &model, messages, params);
+    match run_tool_loop(&window, &api_url, &api_key, payload, &registry, max_tool_steps, profile.as_deref()).await {
+        Ok(result) => {
+            update_frequency(model, true);
+            if let Some(session_id) = &session_id {
+                // 回答可能生成了很久，重新读取会话后只追加这一轮问答，保留期间对会话的其他修改
+                let session = update_session(session_id, |session| {
+                    match &edit_message_id {
+                        Some(id) => {
+                            let parent_id = edited_parent(session, id)?;
+                            session.push_exchange_under(parent_id, &message, images, documents, &result)
                         }
+                        None => session.push_exchange_with_attachments(&message, images, documents, &result),
                     }
-                }
+                    Ok(session.clone())
+                })
+                .map_err(|e| format!("保存会话失败: {}", e))?;
+                spawn_auto_title(&window, &api_url, &api_key, &session, &result.model);
+                spawn_message_vector_update(&api_url, &api_key, session);
             }
+            Ok(result)
+        }
+        Err(e) => {
+            update_frequency(model, false);
+            Err(e)
         }
     }
+}
+
+/// 重新生成会话当前分支中最后一个问题的回答，可换用其他模型或生成参数。
+///
+/// 新回答与原回答同为该问题的子消息，各自保留模型、用量和耗时，可通过 `list_alternatives` 对比、
+/// 通过 `switch_branch` 切换。未指定 `model` 时沿用原回答的模型。
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn regenerate(
+    window: Window,
+    session_id: String,
+    api_key: String,
+    api_url: String,
+    model: Option<String>,
+    params: Option<GenerationParams>,
+    profile: Option<String>,
+) -> Result<ChatResult, String> {
+    let session = load_session(&session_id)?;
+    let path = session.active_path();
+    let question = path
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| (*m).clone())
+        .ok_or("会话中没有可以重新生成的问题")?;
+    let previous_model = path
+        .iter()
+        .find(|m| m.parent_id.as_deref() == Some(question.id.as_str()))
+        .and_then(|m| m.model.clone());
+
+    let profile = profile.or_else(|| session.profile.clone());
+    ensure_within_budget(profile.as_deref())?;
+    let persona = session_persona(Some(&session))?;
+    let params = resolve_params(params, Some(&session), persona.as_ref(), profile.as_deref())?;
+    let model = model
+        .filter(|m| !m.trim().is_empty())
+        .or(previous_model)
+        .or_else(|| persona.as_ref().and_then(|p| p.default_model.clone()))
+        .ok_or("未指定模型")?;
+    debug!("重新生成 {} 的回答，模型: {}", question.id, model);
+
+    let mut messages = session.history_before(&question.id);
+    if let Some(persona) = &persona {
+        apply_system_prompt(persona, &mut messages);
+    }
+    let prompt = compose_prompt(&api_url, &api_key, Some(&session), &question.content).await?;
+    messages.push(attachment_message("user", &prompt, &question.images, &question.documents));
+
+    let registry = registry_snapshot();
+    let (payload, max_tool_steps) = build_payload(Some(&session), &registry, &model, messages, params);
+    match run_tool_loop(&window, &api_url, &api_key, payload, &registry, max_tool_steps, profile.as_deref()).await {
+        Ok(result) => {
+            update_frequency(model, true);
+            let session = update_session(&session_id, |session| {
+                if session.find_message(&question.id).is_none() {
+                    return Err(format!("要重新生成的问题已被删除: {}", question.id));
+                }
+                session.push_answer(&question.id, &result);
+                Ok(session.clone())
+            })
+            .map_err(|e| format!("保存会话失败: {}", e))?;
+            spawn_message_vector_update(&api_url, &api_key, session);
+            Ok(result)
+        }
+        Err(e) => {
+            update_frequency(model, false);
+            Err(e)
+        }
+    }
+}
+
+/// 回答因长度限制被截断时自动请求模型续写，并将续写内容拼接到同一条助手消息中。
+///
+/// `history` 为产生该回答时发送的消息（含最后一条用户消息），`partial` 为 `chat` 返回的结果。
+/// 指定 `session_id` 时可省略 `history`，续写结果会更新会话中的同一条消息。
+/// 续写内容同样通过 `stream-response` 事件推送，前端可直接追加到当前消息。
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn continue_response(
+    window: Window,
+    api_key: String,
+    api_url: String,
+    model: String,
+    history: Vec<ChatMessage>,
+    partial: ChatResult,
+    max_rounds: Option<u32>,
+    profile: Option<String>,
+    session_id: Option<String>,
+    params: Option<GenerationParams>,
+) -> Result<ChatResult, String> {
+    let session = load_optional_session(session_id.as_deref())?;
+    let profile = profile.or_else(|| session.as_ref().and_then(|s| s.profile.clone()));
+    let persona = session_persona(session.as_ref())?;
+    let params = resolve_params(params, session.as_ref(), persona.as_ref(), profile.as_deref())?;
+
+    let mut history = match &session {
+        Some(session) if history.is_empty() && session.find_message(&partial.message_id).is_some() => {
+            session.history_before(&partial.message_id)
+        }
+        Some(session) if history.is_empty() => session.history(),
+        _ => history,
+    };
+    if let Some(persona) = &persona {
+        apply_system_prompt(persona, &mut history);
+    }
+
+    let mut result = partial;
+    let max_rounds = max_rounds.unwrap_or(DEFAULT_CONTINUE_ROUNDS);
+
+    for round in 0..max_rounds {
+        if !result.is_truncated() {
+            break;
+        }
+        debug!("续写第 {} 轮: {}", round + 1, result.message_id);
+
+        let mut messages = history.clone();
+        messages.push(ChatMessage::new("assistant", &result.content));
+        messages.push(ChatMessage::new("user", CONTINUE_PROMPT));
+
+        let payload = ChatPayload {
+            model: model.clone(),
+            messages,
+            stream: true,
+            stream_options: Some(StreamOptions { include_usage: true }),
+            params: params.clone(),
+            tools: None,
+        };
+
+        ensure_within_budget(profile.as_deref())?;
+        let continuation = stream_chat(&window, StreamChannel::Chat, &api_url, &api_key, &payload).await?;
+        record_result_usage(&continuation, profile.as_deref());
+        result.append_continuation(continuation);
+    }
 
     update_frequency(model, true);
+    if let Some(session_id) = &session_id {
+        let updated = update_session(session_id, |session| Ok(session.update_message(&result).then(|| session.clone())))
+            .map_err(|e| format!("保存会话失败: {}", e))?;
+        if let Some(session) = updated {
+            spawn_message_vector_update(&api_url, &api_key, session);
+        }
+    }
+    Ok(result)
+}
+
+/// 将同一问题并发发送给多个模型，分别流式返回回答，完成后保存为对比记录。
+///
+/// 开始请求前通过 `compare-started` 事件推送对比记录（不含回答），前端按其中的 ID 和
+/// `compare-response`、`compare-reasoning` 事件载荷中的 `stream_id` 区分各模型的输出。
+/// 指定 `session_id` 时使用会话当前分支的历史消息和人设，但不会把对比写入会话。
+/// 单个模型失败不影响其他模型，错误记录在对应条目中。
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn compare_models(
+    window: Window,
+    prompt: String,
+    models: Vec<String>,
+    api_key: String,
+    api_url: String,
+    session_id: Option<String>,
+    params: Option<GenerationParams>,
+    profile: Option<String>,
+) -> Result<Comparison, String> {
+    validate_models(&models)?;
+    let session = load_optional_session(session_id.as_deref())?;
+    let profile = profile.or_else(|| session.as_ref().and_then(|s| s.profile.clone()));
+    ensure_within_budget(profile.as_deref())?;
+    let persona = session_persona(session.as_ref())?;
+    let params = resolve_params(params, session.as_ref(), persona.as_ref(), profile.as_deref())?;
+
+    let mut messages = session.as_ref().map(Session::history).unwrap_or_default();
+    if let Some(persona) = &persona {
+        apply_system_prompt(persona, &mut messages);
+    }
+    let composed = compose_prompt(&api_url, &api_key, session.as_ref(), &prompt).await?;
+    messages.push(ChatMessage::new("user", &composed));
+
+    let mut comparison = Comparison::new(&prompt, session_id);
+    window.emit("compare-started", &comparison).map_err(|e| e.to_string())?;
+    debug!("开始对比 {}: {:?}", comparison.id, models);
+
+    let stream_ids: Vec<String> = (0..models.len()).map(|index| comparison.stream_id(index)).collect();
+    let requests = models.iter().zip(&stream_ids).enumerate().map(|(index, (model, stream_id))| {
+        let payload = ChatPayload {
+            model: model.clone(),
+            messages: messages.clone(),
+            stream: true,
+            stream_options: Some(StreamOptions { include_usage: true }),
+            params: params.clone(),
+            tools: None,
+        };
+        let window = &window;
+        let (api_url, api_key) = (&api_url, &api_key);
+        async move {
+            let channel = StreamChannel::Compare { stream_id, index };
+            stream_chat(window, channel, api_url, api_key, &payload).await
+        }
+    });
+    let outcomes = join_all(requests).await;
+
+    for ((model, stream_id), outcome) in models.iter().zip(&stream_ids).zip(outcomes) {
+        match &outcome {
+            Ok(result) => record_result_usage(result, profile.as_deref()),
+            Err(e) => warn!("对比中模型 {} 请求失败: {}", model, e),
+        }
+        update_frequency(model.clone(), outcome.is_ok());
+        comparison.entries.push(ComparisonEntry::new(stream_id, model, outcome));
+    }
+    if let Err(e) = save_comparison(&comparisons_dir(), &comparison) {
+        error!("保存对比记录失败: {}", e);
+    }
+    Ok(comparison)
+}
+
+/// 列出所有对比记录
+#[tauri::command]
+pub fn list_comparisons() -> Result<Vec<ComparisonSummary>, String> {
+    compare::list_comparisons(&comparisons_dir())
+}
 
-    let elapsed = start_time.elapsed();
-    Ok(format!("流式响应完成\n<p style=\"color: green\">响应耗时: {:.2}秒</p>", elapsed.as_secs_f64()))
+#[tauri::command]
+pub fn get_comparison(comparison_id: String) -> Result<Comparison, String> {
+    load_comparison(&comparisons_dir(), &comparison_id)
+}
+
+#[tauri::command]
+pub fn delete_comparison(comparison_id: String) -> Result<(), String> {
+    compare::delete_comparison(&comparisons_dir(), &comparison_id)
 }
 
 /// 从 API 获取模型列表
 async fn fetch_models_from_api(api_url: &str, api_key: &str) -> Result<Vec<String>, String> {
     let client = reqwest::Client::new();
-    
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| e.to_string())?
-    );
-
-    // 构建 models API URL
-    let models_url = if api_url.ends_with("/v1/chat/completions") {
-        api_url.replace("/chat/completions", "/models")
-    } else if api_url.ends_with("/chat/completions") {
-        api_url.replace("/chat/completions", "/models")
-    } else if api_url.ends_with("/v1") {
-        format!("{}/models", api_url)
-    } else if api_url.ends_with("/v1/") {
-        format!("{}models", api_url)
-    } else {
-        format!("{}/v1/models", api_url.trim_end_matches('/'))
-    };
+    let headers = build_headers(api_key)?;
+
+    let models_url = endpoint_url(api_url, "models");
 
     debug!("Models API URL: {}", models_url);
 
@@ -162,74 +864,37 @@ async fn fetch_models_from_api(api_url: &str, api_key: &str) -> Result<Vec<Strin
     Ok(models)
 }
 
-/// 将模型列表写入配置文件
-fn write_models_to_file(models: &[String], frequency_file: &std::path::Path) -> Result<(), String> {
+/// 将模型列表写入数据库，已有的使用次数保持不变
+fn save_model_catalog(models: &[String]) -> Result<(), String> {
     let mut frequencies = MODEL_FREQUENCIES.lock().unwrap();
-    
+    let stored = with_storage(|storage| storage.load_models())?;
+
     // 初始化或更新模型频率
     for model in models {
-        frequencies.entry(model.clone()).or_insert(0);
+        let count = stored.get(model).copied().unwrap_or(0);
+        frequencies.entry(model.clone()).or_insert(count);
     }
-    
-    // 将频率数据写入文件
-    let frequency_data = ModelFrequency {
-        frequencies: frequencies.clone(),
-    };
-    
-    serde_json::to_string_pretty(&frequency_data)
-        .map_err(|e| format!("序列化频率数据失败: {}", e))
-        .and_then(|json| {
-            fs::write(frequency_file, json)
-                .map_err(|e| format!("写入频率文件失败: {}", e))
-        })
+
+    with_storage(|storage| storage.save_models(&frequencies))
 }
 
 #[tauri::command]
 pub async fn fetch_models(api_url: String, api_key: String) -> Result<AvailableModelsResponse, String> {
-    let cache_dir = get_cache_dir();
-    let frequency_file = cache_dir.join("frequency.json");
-    
-    // 尝试从配置文件读取模型列表
-    let models = if frequency_file.exists() {
-        // 读取并解析配置文件
-        match fs::read_to_string(&frequency_file)
-            .map_err(|e| format!("读取配置文件失败: {}", e))
-            .and_then(|content| {
-                serde_json::from_str::<ModelFrequency>(&content)
-                    .map_err(|e| format!("解析配置文件失败: {}", e))
-            }) {
-            Ok(frequency_data) => {
-                // 过滤掉 value=-1 的模型
-                let valid_models: Vec<String> = frequency_data.frequencies
-                    .into_iter()
-                    .filter(|(_, freq)| *freq != -1)
-                    .map(|(model, _)| model)
-                    .collect();
-                
-                if valid_models.is_empty() {
-                    // 如果过滤后没有有效模型，从 API 获取
-                    debug!("配置文件中没有有效模型，从 API 获取");
-                    let models = fetch_models_from_api(&api_url, &api_key).await?;
-                    write_models_to_file(&models, &frequency_file)?;
-                    models
-                } else {
-                    valid_models
-                }
-            }
-            Err(e) => {
-                // 如果解析失败，从 API 获取
-                debug!("解析配置文件失败: {}，从 API 获取", e);
-                let models = fetch_models_from_api(&api_url, &api_key).await?;
-                write_models_to_file(&models, &frequency_file)?;
-                models
-            }
-        }
-    } else {
-        // 如果配置文件不存在，从 API 获取并写入
-        debug!("配置文件不存在，从 API 获取");
+    // 过滤掉 value=-1 的模型
+    let valid_models: Vec<String> = with_storage(|storage| storage.load_models())?
+        .into_iter()
+        .filter(|(_, freq)| *freq != -1)
+        .map(|(model, _)| model)
+        .collect();
+
+    let models = if valid_models.is_empty() {
+        // 数据库中没有有效模型时从 API 获取并写入
+        debug!("数据库中没有有效模型，从 API 获取");
         let models = fetch_models_from_api(&api_url, &api_key).await?;
-        write_models_to_file(&models, &frequency_file)?;
+        save_model_catalog(&models)?;
         models
+    } else {
+        valid_models
     };
 
     Ok(AvailableModelsResponse { models })
@@ -244,8 +909,8 @@ pub fn save_api_key(api_key: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn get_api_key(api_keys_path: std::path::PathBuf) -> Result<String, String> {
-    decrypt_api_key(api_keys_path)
+pub fn get_api_key() -> Result<String, String> {
+    decrypt_api_key()
 }
 
 #[tauri::command]
@@ -269,4 +934,837 @@ pub fn get_api_url() -> Result<String, String> {
 #[tauri::command]
 pub fn remove_api_url() -> Result<(), String> {
     delete_api_url()
-}
\ No newline at end of file
+}
+
+fn parse_date(date: Option<String>) -> Result<Option<NaiveDate>, String> {
+    date.map(|date| {
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| format!("日期格式错误（应为 YYYY-MM-DD）: {}", e))
+    })
+    .transpose()
+}
+
+/// 按天、模型或配置查询花费，`from`/`to` 为 YYYY-MM-DD 格式的包含边界
+#[tauri::command]
+pub fn query_spend(group_by: SpendGroup, from: Option<String>, to: Option<String>) -> Result<Vec<SpendSummary>, String> {
+    let records = with_storage(|storage| storage.load_usage())?;
+    Ok(summarize(&records, group_by, parse_date(from)?, parse_date(to)?))
+}
+
+#[tauri::command]
+pub fn get_model_prices() -> Result<HashMap<String, ModelPrice>, String> {
+    with_storage(|storage| load_prices(storage))
+}
+
+#[tauri::command]
+pub fn set_model_price(model: String, price: ModelPrice) -> Result<(), String> {
+    if model.trim().is_empty() {
+        return Err("模型名称不能为空".to_string());
+    }
+    if !(price.prompt >= 0.0 && price.completion >= 0.0 && price.prompt.is_finite() && price.completion.is_finite()) {
+        return Err("价格不能为负数".to_string());
+    }
+    with_storage(|storage| {
+        update_setting(storage, PRICES_KEY, |prices: &mut HashMap<String, ModelPrice>| {
+            prices.insert(model, price);
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn remove_model_price(model: String) -> Result<(), String> {
+    with_storage(|storage| {
+        update_setting(storage, PRICES_KEY, |prices: &mut HashMap<String, ModelPrice>| {
+            prices.remove(&model);
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn get_budgets() -> Result<BudgetConfig, String> {
+    with_storage(|storage| load_budgets(storage))
+}
+
+#[tauri::command]
+pub fn set_budgets(budgets: BudgetConfig) -> Result<(), String> {
+    budgets.validate()?;
+    with_storage(|storage| save_budgets(storage, &budgets))
+}
+
+/// 将全部用量记录导出为 CSV 文件
+#[tauri::command]
+pub fn export_usage_csv(path: PathBuf) -> Result<usize, String> {
+    let records = with_storage(|storage| storage.load_usage())?;
+    write_atomic(&path, records_to_csv(&records)).map_err(|e| format!("导出 CSV 失败: {}", e))?;
+    Ok(records.len())
+}
+
+#[tauri::command]
+pub fn list_profiles() -> Result<HashMap<String, Profile>, String> {
+    with_storage(|storage| load_profiles(storage))
+}
+
+#[tauri::command]
+pub fn save_profile(name: String, profile: Profile) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("配置名称不能为空".to_string());
+    }
+    profile.params.validate()?;
+    with_storage(|storage| {
+        update_setting(storage, PROFILES_KEY, |profiles: &mut HashMap<String, Profile>| {
+            profiles.insert(name, profile);
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn delete_profile(name: String) -> Result<(), String> {
+    with_storage(|storage| {
+        update_setting(storage, PROFILES_KEY, |profiles: &mut HashMap<String, Profile>| {
+            profiles.remove(&name);
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn create_session(title: Option<String>, profile: Option<String>) -> Result<Session, String> {
+    let session = Session::new(title.as_deref().unwrap_or(DEFAULT_SESSION_TITLE), profile);
+    save_session(&session)?;
+    Ok(session)
+}
+
+#[tauri::command]
+pub fn get_session(session_id: String) -> Result<Session, String> {
+    load_session(&session_id)
+}
+
+/// 列出未归档、不在回收站中的会话，置顶的在前
+#[tauri::command]
+pub fn list_sessions() -> Result<Vec<SessionSummary>, String> {
+    let query = SessionQuery {
+        archived: Some(false),
+        ..Default::default()
+    };
+    Ok(organize::query_sessions(&session::list_sessions()?, &[], &query))
+}
+
+/// 将会话移入回收站，可通过 `update_sessions` 恢复或永久删除
+#[tauri::command]
+pub fn delete_session(session_id: String) -> Result<(), String> {
+    let mut session = load_session(&session_id)?;
+    if apply_action(&mut session, &BulkAction::Trash, session::now_millis()) {
+        save_session(&session)?;
+    }
+    Ok(())
+}
+
+/// 修改会话使用的配置和会话级生成参数
+#[tauri::command]
+pub fn update_session_settings(session_id: String, profile: Option<String>, params: GenerationParams) -> Result<Session, String> {
+    params.validate()?;
+    let mut session = load_session(&session_id)?;
+    session.profile = profile;
+    session.params = params;
+    session.updated_at = session::now_millis();
+    save_session(&session)?;
+    Ok(session)
+}
+
+/// 设置会话使用的人设，传 None 取消
+#[tauri::command]
+pub fn set_session_persona(session_id: String, persona: Option<String>) -> Result<Session, String> {
+    if let Some(name) = &persona {
+        if find_persona(&with_storage(|storage| load_personas(storage))?, name).is_none() {
+            return Err(format!("人设不存在: {}", name));
+        }
+    }
+    let mut session = load_session(&session_id)?;
+    session.persona = persona;
+    session.updated_at = session::now_millis();
+    save_session(&session)?;
+    Ok(session)
+}
+
+#[tauri::command]
+pub fn list_personas() -> Result<Vec<Persona>, String> {
+    with_storage(|storage| load_personas(storage))
+}
+
+#[tauri::command]
+pub fn get_persona(name: String) -> Result<Persona, String> {
+    find_persona(&with_storage(|storage| load_personas(storage))?, &name).ok_or_else(|| format!("人设不存在: {}", name))
+}
+
+/// 新增或更新人设，按名称匹配
+#[tauri::command]
+pub fn save_persona(persona: Persona) -> Result<(), String> {
+    persona.validate()?;
+    with_storage(|storage| {
+        update_setting(storage, PERSONAS_KEY, |personas: &mut Vec<Persona>| {
+            upsert_persona(personas, persona);
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn delete_persona(name: String) -> Result<(), String> {
+    with_storage(|storage| {
+        update_setting(storage, PERSONAS_KEY, |personas: &mut Vec<Persona>| {
+            personas.retain(|p| p.name != name);
+            Ok(())
+        })
+    })
+}
+
+/// 导出人设预设包，`names` 为空时导出全部
+#[tauri::command]
+pub fn export_personas(path: PathBuf, names: Option<Vec<String>>) -> Result<usize, String> {
+    let personas = with_storage(|storage| load_personas(storage))?;
+    let pack = build_pack(&personas, names.as_deref());
+    let json = serde_json::to_string_pretty(&pack).map_err(|e| format!("序列化预设包失败: {}", e))?;
+    write_atomic(&path, json).map_err(|e| format!("写入预设包失败: {}", e))?;
+    Ok(pack.personas.len())
+}
+
+/// 导入人设预设包，同名人设仅在 `overwrite` 为 true 时覆盖
+#[tauri::command]
+pub fn import_personas(path: PathBuf, overwrite: Option<bool>) -> Result<PersonaImportReport, String> {
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取预设包失败: {}", e))?;
+    let pack: PersonaPack = serde_json::from_str(&content).map_err(|e| format!("解析预设包失败: {}", e))?;
+
+    with_storage(|storage| {
+        update_setting(storage, PERSONAS_KEY, |personas: &mut Vec<Persona>| {
+            merge_pack(personas, pack, overwrite.unwrap_or(false))
+        })
+    })
+}
+
+#[tauri::command]
+pub fn list_templates() -> Result<Vec<PromptTemplate>, String> {
+    with_storage(|storage| load_templates(storage))
+}
+
+/// 新增或更新提示词模板，按名称匹配
+#[tauri::command]
+pub fn save_template(template: PromptTemplate) -> Result<(), String> {
+    template.validate()?;
+    with_storage(|storage| {
+        update_setting(storage, TEMPLATES_KEY, |templates: &mut Vec<PromptTemplate>| {
+            match templates.iter_mut().find(|t| t.name == template.name) {
+                Some(existing) => *existing = template,
+                None => templates.push(template),
+            }
+            Ok(())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn delete_template(name: String) -> Result<(), String> {
+    with_storage(|storage| {
+        update_setting(storage, TEMPLATES_KEY, |templates: &mut Vec<PromptTemplate>| {
+            templates.retain(|t| t.name != name);
+            Ok(())
+        })
+    })
+}
+
+/// 渲染已保存的模板，供前端预览
+#[tauri::command]
+pub fn render_template(name: String, values: HashMap<String, String>) -> Result<String, String> {
+    let templates = with_storage(|storage| load_templates(storage))?;
+    let template = find_template(&templates, &name).ok_or_else(|| format!("模板不存在: {}", name))?;
+    template.render(&values)
+}
+
+/// 列出当前注册的全部工具
+#[tauri::command]
+pub fn list_tools() -> Vec<ToolDefinition> {
+    registry_snapshot().definitions()
+}
+
+/// 设置会话是否启用工具调用及每轮对话的工具调用步数上限
+#[tauri::command]
+pub fn update_session_tools(session_id: String, enabled: bool, max_steps: Option<u32>) -> Result<Session, String> {
+    if max_steps == Some(0) {
+        return Err("工具调用步数上限必须大于 0".to_string());
+    }
+    let mut session = load_session(&session_id)?;
+    session.tools_enabled = enabled;
+    session.max_tool_steps = max_steps;
+    session.updated_at = session::now_millis();
+    save_session(&session)?;
+    Ok(session)
+}
+
+/// 读取本地图片，缩放并编码为可附加到消息中的 data URL
+#[tauri::command]
+pub fn attach_image(path: PathBuf) -> Result<ImageAttachment, String> {
+    load_image(&path)
+}
+
+/// 读取本地文本、Markdown、代码或 PDF 文件并提取文本，`max_chars` 为可注入消息的字符预算
+#[tauri::command]
+pub fn attach_document(path: PathBuf, max_chars: Option<usize>) -> Result<DocumentAttachment, String> {
+    load_document(&path, max_chars.unwrap_or(DEFAULT_DOCUMENT_BUDGET))
+}
+
+/// 前端回复工具调用确认请求
+#[tauri::command]
+pub fn respond_tool_approval(request_id: String, approved: bool) -> Result<(), String> {
+    resolve_approval(&request_id, approved)
+}
+
+/// 列出允许文件工具访问的目录
+#[tauri::command]
+pub fn list_approved_directories() -> Result<Vec<PathBuf>, String> {
+    with_storage(|storage| load_approved_dirs(storage))
+}
+
+/// 批准文件工具访问某个目录及其子目录
+#[tauri::command]
+pub fn add_approved_directory(path: PathBuf) -> Result<Vec<PathBuf>, String> {
+    let path = fs::canonicalize(&path).map_err(|e| format!("无法访问目录 {}: {}", path.display(), e))?;
+    if !path.is_dir() {
+        return Err(format!("不是目录: {}", path.display()));
+    }
+    with_storage(|storage| {
+        update_setting(storage, APPROVED_DIRS_KEY, |dirs: &mut Vec<PathBuf>| {
+            if !dirs.contains(&path) {
+                dirs.push(path);
+            }
+            Ok(dirs.clone())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn remove_approved_directory(path: PathBuf) -> Result<Vec<PathBuf>, String> {
+    with_storage(|storage| {
+        update_setting(storage, APPROVED_DIRS_KEY, |dirs: &mut Vec<PathBuf>| {
+            dirs.retain(|dir| dir != &path);
+            Ok(dirs.clone())
+        })
+    })
+}
+
+#[tauri::command]
+pub fn list_mcp_servers() -> Result<Vec<McpServerConfig>, String> {
+    with_storage(|storage| load_mcp_servers(storage))
+}
+
+/// 新增或更新 MCP 服务配置，已启用的服务会立即（重新）连接
+#[tauri::command]
+pub async fn save_mcp_server(config: McpServerConfig) -> Result<Option<McpServerStatus>, String> {
+    config.validate()?;
+    with_storage(|storage| {
+        update_setting(storage, MCP_SERVERS_KEY, |servers: &mut Vec<McpServerConfig>| {
+            match servers.iter_mut().find(|s| s.name == config.name) {
+                Some(existing) => *existing = config.clone(),
+                None => servers.push(config.clone()),
+            }
+            Ok(())
+        })
+    })?;
+
+    if config.enabled {
+        mcp::connect_server(&config).await.map(Some)
+    } else {
+        mcp::disconnect_server(&config.name).await;
+        Ok(None)
+    }
+}
+
+#[tauri::command]
+pub async fn delete_mcp_server(name: String) -> Result<(), String> {
+    mcp::disconnect_server(&name).await;
+    with_storage(|storage| {
+        update_setting(storage, MCP_SERVERS_KEY, |servers: &mut Vec<McpServerConfig>| {
+            servers.retain(|s| s.name != name);
+            Ok(())
+        })
+    })
+}
+
+/// 按名称连接已配置的 MCP 服务
+#[tauri::command]
+pub async fn connect_mcp_server(name: String) -> Result<McpServerStatus, String> {
+    let servers = with_storage(|storage| load_mcp_servers(storage))?;
+    let config = servers
+        .iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("MCP 服务不存在: {}", name))?;
+    mcp::connect_server(config).await
+}
+
+#[tauri::command]
+pub async fn disconnect_mcp_server(name: String) -> Result<bool, String> {
+    Ok(mcp::disconnect_server(&name).await)
+}
+
+/// 列出已连接的 MCP 服务及其工具、资源和提示词
+#[tauri::command]
+pub async fn list_mcp_connections() -> Result<Vec<McpServerStatus>, String> {
+    Ok(mcp::server_statuses().await)
+}
+
+#[tauri::command]
+pub async fn read_mcp_resource(server: String, uri: String) -> Result<String, String> {
+    mcp::read_resource(&server, &uri).await
+}
+
+/// 获取 MCP 服务的提示词，返回的消息可作为对话历史发送
+#[tauri::command]
+pub async fn get_mcp_prompt(server: String, name: String, arguments: HashMap<String, String>) -> Result<Vec<ChatMessage>, String> {
+    mcp::get_prompt(&server, &name, &arguments).await
+}
+
+/// 为本地目录创建向量索引，同一目录已有索引时直接返回已有索引
+#[tauri::command]
+pub fn create_folder_index(path: PathBuf, model: String) -> Result<IndexSummary, String> {
+    let root = fs::canonicalize(&path).map_err(|e| format!("无法访问目录 {}: {}", path.display(), e))?;
+    if !root.is_dir() {
+        return Err(format!("不是目录: {}", root.display()));
+    }
+    if model.trim().is_empty() {
+        return Err("嵌入模型不能为空".to_string());
+    }
+
+    let dir = indexes_dir();
+    if let Some(existing) = list_indexes(&dir)?.into_iter().find(|summary| summary.root == root) {
+        return Ok(existing);
+    }
+    let index = FolderIndex::new(root, &model);
+    save_index(&dir, &index)?;
+    Ok(index.summary())
+}
+
+/// 增量更新目录索引，指定 `model` 且与索引使用的模型不同时全量重建
+#[tauri::command]
+pub async fn index_folder(index_id: String, api_key: String, api_url: String, model: Option<String>) -> Result<IndexReport, String> {
+    let dir = indexes_dir();
+    let _lock = lock_index(&dir, &index_id).await?;
+    let mut index = load_index(&dir, &index_id)?;
+    if let Some(model) = model.filter(|m| !m.trim().is_empty()) {
+        index.set_model(&model);
+    }
+    let embedding_model = index.model.clone();
+    let provider = OpenAiProvider::new(&api_url, &api_key);
+    let report = update_index(&mut index, |input| embed_texts(&provider, &embedding_model, input)).await?;
+    save_index(&dir, &index)?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn list_folder_indexes() -> Result<Vec<IndexSummary>, String> {
+    list_indexes(&indexes_dir())
+}
+
+#[tauri::command]
+pub fn delete_folder_index(index_id: String) -> Result<(), String> {
+    delete_index(&indexes_dir(), &index_id)
+}
+
+/// 在目录索引中检索与查询最相关的分块
+#[tauri::command]
+pub async fn search_folder_index(
+    index_id: String,
+    query: String,
+    top_k: Option<usize>,
+    api_key: String,
+    api_url: String,
+) -> Result<Vec<RetrievedChunk>, String> {
+    retrieve_from_index(&api_url, &api_key, &index_id, &query, top_k.unwrap_or(DEFAULT_TOP_K)).await
+}
+
+/// 设置会话关联的目录索引，为空时取消关联
+#[tauri::command]
+pub fn set_session_index(session_id: String, index_id: Option<String>) -> Result<Session, String> {
+    if let Some(index_id) = &index_id {
+        load_index(&indexes_dir(), index_id)?;
+    }
+    let mut session = load_session(&session_id)?;
+    session.index_id = index_id;
+    session.updated_at = session::now_millis();
+    save_session(&session)?;
+    Ok(session)
+}
+
+/// 调用嵌入接口生成文本向量，输入较多时自动分批，返回向量及其维度
+#[tauri::command]
+pub async fn embed(
+    api_key: String,
+    api_url: String,
+    model: String,
+    input: Vec<String>,
+    profile: Option<String>,
+) -> Result<EmbeddingResult, String> {
+    ensure_within_budget(profile.as_deref())?;
+    let result = OpenAiProvider::new(&api_url, &api_key).embed(&model, input).await?;
+    record_embedding_usage(&result, profile.as_deref());
+    Ok(result)
+}
+
+/// 全文搜索所有会话中的消息，支持 `"短语"` 查询，可按模型、会话、角色和日期（YYYY-MM-DD）过滤
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn search_messages(
+    query: String,
+    model: Option<String>,
+    session_id: Option<String>,
+    role: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    let filters = SearchFilters {
+        model,
+        session_id,
+        role,
+        from: parse_date(from)?,
+        to: parse_date(to)?,
+    };
+    let mut sessions = session::list_sessions()?;
+    sessions.retain(|session| !session.is_trashed());
+    let mut index = SEARCH_INDEX.lock().map_err(|e| e.to_string())?;
+    index.sync(&sessions);
+    Ok(index.search(&query, &filters, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)))
+}
+
+/// 启用历史消息的语义搜索并为全部会话生成向量；已启用时增量更新，`model` 与之前不同时全量重建
+#[tauri::command]
+pub async fn enable_semantic_search(api_key: String, api_url: String, model: String) -> Result<MessageVectorReport, String> {
+    if model.trim().is_empty() {
+        return Err("嵌入模型不能为空".to_string());
+    }
+    let _lock = lock_message_vectors().await?;
+    let path = message_vectors_file();
+    let mut store = load_message_vectors(&path)?;
+    store.set_model(&model);
+    save_message_vectors(&path, &store)?;
+    let sessions = session::list_sessions()?;
+    sync_message_vectors(&api_url, &api_key, &mut store, &sessions, true).await
+}
+
+/// 停用语义搜索并删除已生成的向量
+#[tauri::command]
+pub async fn disable_semantic_search() -> Result<(), String> {
+    let _lock = lock_message_vectors().await?;
+    let path = message_vectors_file();
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| format!("删除消息向量失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 语义搜索的状态，未启用时返回 `None`
+#[tauri::command]
+pub async fn get_semantic_search_status() -> Result<Option<MessageVectorSummary>, String> {
+    let _lock = lock_message_vectors().await?;
+    let store = load_message_vectors(&message_vectors_file())?;
+    Ok(store.is_enabled().then(|| store.summary()))
+}
+
+/// 按语义搜索历史消息，返回与查询最相关的几轮问答
+#[tauri::command]
+pub async fn semantic_search_messages(
+    query: String,
+    api_key: String,
+    api_url: String,
+    top_k: Option<usize>,
+) -> Result<Vec<SemanticHit>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let _lock = lock_message_vectors().await?;
+    let mut store = load_message_vectors(&message_vectors_file())?;
+    if !store.is_enabled() {
+        return Err("尚未启用语义搜索".to_string());
+    }
+    // 补齐后台更新失败或导入的会话
+    let mut sessions = session::list_sessions()?;
+    sync_message_vectors(&api_url, &api_key, &mut store, &sessions, true).await?;
+
+    let provider = OpenAiProvider::new(&api_url, &api_key);
+    let query_embedding = embed_texts(&provider, &store.model, vec![query])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    // 回收站中的会话保留向量以便恢复，但不出现在结果中
+    sessions.retain(|session| !session.is_trashed());
+    Ok(store.search(&sessions, &query_embedding, top_k.unwrap_or(DEFAULT_SEMANTIC_TOP_K)))
+}
+
+/// 将会话导出到 `path`，支持 Markdown、HTML、JSON 和 PDF，未指定 `format` 时按扩展名判断。
+/// 导出 PDF 时可通过 `font_path` 指定 TrueType 字体，未指定时查找系统中文字体。
+#[tauri::command]
+pub fn export_session(
+    session_id: String,
+    path: String,
+    format: Option<ExportFormat>,
+    font_path: Option<String>,
+) -> Result<(), String> {
+    let session = load_session(&session_id)?;
+    let font_path = font_path.filter(|p| !p.trim().is_empty()).map(PathBuf::from);
+    export::export_session(&session, &PathBuf::from(path), format, font_path.as_deref())
+}
+
+/// 从 ChatGPT 导出的 `conversations.json` 或 OpenAI 格式的 JSONL 导入会话，已导入过的会话会被跳过。
+/// 未指定 `format` 时按扩展名判断；`dry_run` 为 true 时只返回导入报告，不写入会话。
+#[tauri::command]
+pub fn import_conversations(path: String, format: Option<ImportFormat>, dry_run: Option<bool>) -> Result<ImportReport, String> {
+    let existing = session::list_sessions()?;
+    import_file(&PathBuf::from(path), format, &existing, dry_run.unwrap_or(false), |session| {
+        save_session(session)
+    })
+}
+
+/// 当前分支上的消息，每条消息附带兄弟消息的 ID，用于展示“第几个版本”并切换
+#[tauri::command]
+pub fn get_active_branch(session_id: String) -> Result<Vec<BranchMessage>, String> {
+    Ok(load_session(&session_id)?.active_branch())
+}
+
+/// 切换到包含 `message_id` 的分支，返回切换后的当前分支
+#[tauri::command]
+pub fn switch_branch(session_id: String, message_id: String) -> Result<Vec<BranchMessage>, String> {
+    let mut session = load_session(&session_id)?;
+    if !session.switch_branch(&message_id) {
+        return Err(format!("消息不存在: {}", message_id));
+    }
+    save_session(&session)?;
+    Ok(session.active_branch())
+}
+
+/// 与 `message_id` 同属一个父消息的所有版本（含自身），按生成顺序排列，附带各自的模型、用量和耗时
+#[tauri::command]
+pub fn list_alternatives(session_id: String, message_id: String) -> Result<Vec<StoredMessage>, String> {
+    let session = load_session(&session_id)?;
+    let message = session
+        .find_message(&message_id)
+        .ok_or_else(|| format!("消息不存在: {}", message_id))?;
+    Ok(session
+        .children(message.parent_id.as_deref())
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_summary_config() -> Result<SummaryConfig, String> {
+    with_storage(|storage| load_summary_config(storage))
+}
+
+#[tauri::command]
+pub fn save_summary_config(config: SummaryConfig) -> Result<(), String> {
+    with_storage(|storage| summary::save_summary_config(storage, &config))
+}
+
+/// 在后台为会话较早的消息生成滚动摘要，之后发送历史时用摘要替换这些消息。
+///
+/// 保留最近 `keep_recent` 条消息不摘要，已有摘要时将其与新的消息合并。
+/// 命令立即返回，完成或失败后通过 `session-summary` 事件推送 `SummaryEvent`。
+/// 未指定 `model` 时依次使用配置中的模型和当前分支最后一条回答的模型。
+#[tauri::command]
+pub fn summarize_session(
+    window: Window,
+    session_id: String,
+    api_key: String,
+    api_url: String,
+    model: Option<String>,
+) -> Result<(), String> {
+    let session = load_session(&session_id)?;
+    let config = with_storage(|storage| load_summary_config(storage))?;
+    let until_message_id = summary_cutoff(&session, config.keep_recent)
+        .map(|message| message.id.clone())
+        .ok_or("没有需要摘要的消息")?;
+    let last_model = session
+        .active_path()
+        .iter()
+        .rev()
+        .find_map(|message| message.model.clone())
+        .unwrap_or_default();
+    let model = model
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| config.model_or(&last_model).to_string());
+    if model.trim().is_empty() {
+        return Err("未指定模型".to_string());
+    }
+    let messages = summary::summary_messages(&session, &until_message_id);
+    let profile = session.profile.clone();
+    debug!("后台摘要会话 {} 至消息 {}，模型: {}", session_id, until_message_id, model);
+
+    tauri::async_runtime::spawn(async move {
+        let result = async {
+            let answer = complete_silently(&window, &api_url, &api_key, &model, messages, profile.as_deref()).await?;
+            if answer.content.trim().is_empty() {
+                return Err("模型返回的摘要为空".to_string());
+            }
+            let summary = ContextSummary {
+                content: answer.content.trim().to_string(),
+                until_message_id,
+                model: answer.model,
+                created_at: session::now_millis(),
+            };
+            // 重新读取会话，避免覆盖生成期间写入的新消息
+            update_session(&session_id, |session| {
+                session.context_summary = Some(summary.clone());
+                Ok(())
+            })?;
+            Ok(summary)
+        }
+        .await;
+        if let Err(e) = &result {
+            warn!("生成会话摘要失败: {}", e);
+        }
+        let (summary, error) = match result {
+            Ok(summary) => (Some(summary), None),
+            Err(e) => (None, Some(e)),
+        };
+        if let Err(e) = window.emit("session-summary", &SummaryEvent { session_id, summary, error }) {
+            error!("推送摘要事件失败: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// 删除会话摘要，之后重新发送完整的历史消息
+#[tauri::command]
+pub fn clear_session_summary(session_id: String) -> Result<Session, String> {
+    let mut session = load_session(&session_id)?;
+    session.context_summary = None;
+    save_session(&session)?;
+    Ok(session)
+}
+
+#[tauri::command]
+pub fn list_folders() -> Result<Vec<Folder>, String> {
+    with_storage(|storage| load_folders(storage))
+}
+
+/// 新建文件夹，`parent_id` 为空时建在根目录
+#[tauri::command]
+pub fn create_folder(name: String, parent_id: Option<String>) -> Result<Folder, String> {
+    with_storage(|storage| {
+        update_setting(storage, FOLDERS_KEY, |folders: &mut Vec<Folder>| organize::create_folder(folders, &name, parent_id))
+    })
+}
+
+#[tauri::command]
+pub fn rename_folder(folder_id: String, name: String) -> Result<Folder, String> {
+    with_storage(|storage| {
+        update_setting(storage, FOLDERS_KEY, |folders: &mut Vec<Folder>| organize::rename_folder(folders, &folder_id, &name))
+    })
+}
+
+/// 将文件夹移到 `parent_id` 下，为空时移到根目录
+#[tauri::command]
+pub fn move_folder(folder_id: String, parent_id: Option<String>) -> Result<Folder, String> {
+    with_storage(|storage| {
+        update_setting(storage, FOLDERS_KEY, |folders: &mut Vec<Folder>| organize::move_folder(folders, &folder_id, parent_id))
+    })
+}
+
+/// 删除文件夹，其中的会话和下级文件夹移到上一级
+#[tauri::command]
+pub fn delete_folder(folder_id: String) -> Result<(), String> {
+    // 删除文件夹和移动其中的会话在同一个事务中完成
+    with_storage(|storage| {
+        storage.transaction(&mut |storage| {
+            let parent_id =
+                update_setting(storage, FOLDERS_KEY, |folders: &mut Vec<Folder>| remove_folder(folders, &folder_id))?;
+            for mut session in storage.list_sessions()? {
+                if session.folder_id.as_deref() == Some(folder_id.as_str()) {
+                    session.folder_id = parent_id.clone();
+                    storage.save_session(&session)?;
+                }
+            }
+            Ok(())
+        })
+    })
+}
+
+/// 所有标签及使用它们的会话数
+#[tauri::command]
+pub fn list_tags() -> Result<Vec<TagCount>, String> {
+    Ok(tag_counts(&session::list_sessions()?))
+}
+
+/// 按文件夹、标签、置顶、归档和回收站状态查询会话
+#[tauri::command]
+pub fn query_sessions(query: SessionQuery) -> Result<Vec<SessionSummary>, String> {
+    let sessions = session::list_sessions()?;
+    Ok(organize::query_sessions(&sessions, &with_storage(|storage| load_folders(storage))?, &query))
+}
+
+/// 对多个会话执行同一操作，单个会话失败不影响其他会话
+#[tauri::command]
+pub fn update_sessions(session_ids: Vec<String>, action: BulkAction) -> Result<BulkReport, String> {
+    if let BulkAction::Move { folder_id: Some(folder_id) } = &action {
+        if !with_storage(|storage| load_folders(storage))?.iter().any(|folder| &folder.id == folder_id) {
+            return Err(format!("文件夹不存在: {}", folder_id));
+        }
+    }
+    let now = session::now_millis();
+    let mut report = BulkReport::default();
+    for session_id in session_ids {
+        let result = match action {
+            BulkAction::Purge => with_storage(|storage| purge_session(storage, &session_id)).map(|_| true),
+            _ => load_session(&session_id).and_then(|mut session| {
+                let changed = apply_action(&mut session, &action, now);
+                if changed {
+                    save_session(&session)?;
+                }
+                Ok(changed)
+            }),
+        };
+        match result {
+            Ok(true) => report.updated.push(session_id),
+            Ok(false) => {}
+            Err(error) => report.failed.push(BulkFailure { session_id, error }),
+        }
+    }
+    Ok(report)
+}
+
+/// 永久删除回收站中的全部会话，返回删除的会话 ID
+#[tauri::command]
+pub fn empty_trash() -> Result<Vec<String>, String> {
+    let mut purged = Vec::new();
+    for session in session::list_sessions()?.iter().filter(|session| session.is_trashed()) {
+        session::delete_session(&session.id)?;
+        purged.push(session.id.clone());
+    }
+    Ok(purged)
+}
+
+#[tauri::command]
+pub fn get_trash_config() -> Result<TrashConfig, String> {
+    with_storage(|storage| load_trash_config(storage))
+}
+
+/// 修改回收站保留天数，并立即清理已过期的会话
+#[tauri::command]
+pub fn set_trash_config(config: TrashConfig) -> Result<Vec<String>, String> {
+    with_storage(|storage| {
+        save_trash_config(storage, &config)?;
+        purge_expired_trash(storage, config.retention_days, session::now_millis())
+    })
+}
+
+/// 启动时清理回收站中过期的会话，失败只记录日志
+pub fn purge_expired_sessions() {
+    let result = with_storage(|storage| {
+        let config = load_trash_config(storage)?;
+        purge_expired_trash(storage, config.retention_days, session::now_millis())
+    });
+    match result {
+        Ok(purged) if !purged.is_empty() => debug!("已清理回收站中过期的会话: {:?}", purged),
+        Ok(_) => {}
+        Err(e) => warn!("清理回收站失败: {}", e),
+    }
+}
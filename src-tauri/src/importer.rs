@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chat::generate_message_id;
+use crate::semantic::content_hash;
+use crate::session::{now_millis, Session, StoredMessage};
+
+/// 从首条用户消息生成标题时保留的字符数
+const TITLE_CHARS: usize = 30;
+
+/// 导入文件的格式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// ChatGPT 导出的 `conversations.json`
+    Chatgpt,
+    /// 每行一个 `{"messages": [...]}` 的 OpenAI 格式 JSONL
+    Jsonl,
+}
+
+impl ImportFormat {
+    /// 按扩展名判断格式，`.jsonl` 以外的文件按 ChatGPT 导出处理
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).as_deref() {
+            Some("jsonl") => ImportFormat::Jsonl,
+            _ => ImportFormat::Chatgpt,
+        }
+    }
+}
+
+/// 解析出的一个会话
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImportedSession {
+    pub title: String,
+    pub message_count: usize,
+    pub created_at: i64,
+}
+
+/// 导入结果；`dry_run` 为 true 时只解析和去重，不写入会话
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub imported: Vec<ImportedSession>,
+    /// 已导入过的会话标题
+    pub duplicates: Vec<String>,
+    /// 无法解析或没有消息的条目及原因
+    pub skipped: Vec<String>,
+}
+
+fn millis_from_seconds(value: &Value) -> Option<i64> {
+    value.as_f64().map(|seconds| (seconds * 1000.0) as i64)
+}
+
+fn stored_message(role: &str, content: String, created_at: i64, model: Option<String>) -> StoredMessage {
+    StoredMessage {
+        id: generate_message_id(),
+        parent_id: None,
+        role: role.to_string(),
+        content,
+        images: vec![],
+        documents: vec![],
+        reasoning: None,
+        created_at,
+        model,
+        finish_reason: None,
+        usage: None,
+        timings: None,
+        response_id: None,
+    }
+}
+
+fn default_title(messages: &[StoredMessage]) -> String {
+    messages
+        .iter()
+        .find(|message| message.role == "user")
+        .map(|message| message.content.lines().next().unwrap_or_default().chars().take(TITLE_CHARS).collect())
+        .filter(|title: &String| !title.trim().is_empty())
+        .unwrap_or_else(|| "导入的会话".to_string())
+}
+
+/// 取出 ChatGPT 消息的文本：`parts` 中的字符串按行拼接，图片等非文本内容被忽略
+fn chatgpt_text(content: &Value) -> String {
+    match content["parts"].as_array() {
+        Some(parts) => parts.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("\n"),
+        None => content["text"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// 解析 ChatGPT 导出中的一个会话。
+///
+/// 消息以树的形式保存在 `mapping` 中，编辑或重新生成会产生分支；从 `current_node`（即最后查看的分支）
+/// 沿 `parent` 回溯到根节点，得到该分支上的消息。
+pub fn parse_chatgpt_conversation(conversation: &Value) -> Result<Session, String> {
+    let mapping = conversation["mapping"].as_object().ok_or("缺少 mapping 字段")?;
+    let mut node_id = conversation["current_node"]
+        .as_str()
+        .or_else(|| {
+            // 没有 current_node 时取第一个叶子节点
+            mapping
+                .iter()
+                .find(|(_, node)| node["children"].as_array().is_none_or(Vec::is_empty))
+                .map(|(id, _)| id.as_str())
+        })
+        .ok_or("找不到消息节点")?
+        .to_string();
+
+    let mut branch = Vec::new();
+    let mut visited = HashSet::new();
+    while let Some(node) = mapping.get(&node_id) {
+        if !visited.insert(node_id.clone()) {
+            return Err("消息节点存在循环引用".to_string());
+        }
+        branch.push(node);
+        match node["parent"].as_str() {
+            Some(parent) => node_id = parent.to_string(),
+            None => break,
+        }
+    }
+    branch.reverse();
+
+    let conversation_time = millis_from_seconds(&conversation["create_time"]).unwrap_or_else(now_millis);
+    let mut messages = Vec::new();
+    for node in branch {
+        let message = &node["message"];
+        let role = message["author"]["role"].as_str().unwrap_or_default();
+        if !matches!(role, "user" | "assistant" | "system")
+            || message["metadata"]["is_visually_hidden_from_conversation"].as_bool() == Some(true)
+        {
+            continue;
+        }
+        let text = chatgpt_text(&message["content"]);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let model = (role == "assistant")
+            .then(|| message["metadata"]["model_slug"].as_str().map(str::to_string))
+            .flatten();
+        let created_at = millis_from_seconds(&message["create_time"]).unwrap_or(conversation_time);
+        messages.push(stored_message(role, text, created_at, model));
+    }
+    if messages.is_empty() {
+        return Err("会话中没有消息".to_string());
+    }
+
+    let title = conversation["title"]
+        .as_str()
+        .filter(|title| !title.trim().is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| default_title(&messages));
+    let mut session = Session::new(&title, None);
+    session.created_at = conversation_time;
+    session.updated_at = millis_from_seconds(&conversation["update_time"])
+        .or_else(|| messages.last().map(|message| message.created_at))
+        .unwrap_or(conversation_time);
+    session.import_source = Some(match conversation["conversation_id"].as_str().or(conversation["id"].as_str()) {
+        Some(id) => format!("chatgpt:{}", id),
+        None => format!("chatgpt:{:016x}", content_hash(&conversation.to_string())),
+    });
+    session.messages = messages;
+    session.migrate_linear();
+    Ok(session)
+}
+
+/// 取出 OpenAI 格式消息的文本，`content` 可以是字符串或内容块数组
+fn openai_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| part["type"] == "text")
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 解析 JSONL 中的一行，即一个会话
+pub fn parse_jsonl_line(line: &str) -> Result<Session, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("解析失败: {}", e))?;
+    let entries = value["messages"].as_array().ok_or("缺少 messages 字段")?;
+    let model = value["model"].as_str().map(str::to_string);
+    let now = now_millis();
+
+    let messages: Vec<StoredMessage> = entries
+        .iter()
+        .filter_map(|entry| {
+            let role = entry["role"].as_str()?;
+            if !matches!(role, "user" | "assistant" | "system") {
+                return None;
+            }
+            let text = openai_text(&entry["content"]);
+            if text.trim().is_empty() {
+                return None;
+            }
+            let model = (role == "assistant").then(|| model.clone()).flatten();
+            Some(stored_message(role, text, now, model))
+        })
+        .collect();
+    if messages.is_empty() {
+        return Err("会话中没有消息".to_string());
+    }
+
+    let title = value["title"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| default_title(&messages));
+    let mut session = Session::new(&title, None);
+    session.import_source = Some(format!("jsonl:{:016x}", content_hash(line.trim())));
+    session.messages = messages;
+    session.migrate_linear();
+    Ok(session)
+}
+
+/// 解析导入文件，返回解析出的会话和无法解析的条目
+pub fn parse_import(content: &str, format: ImportFormat) -> Result<(Vec<Session>, Vec<String>), String> {
+    let mut sessions = Vec::new();
+    let mut skipped = Vec::new();
+    match format {
+        ImportFormat::Chatgpt => {
+            let value: Value = serde_json::from_str(content).map_err(|e| format!("解析 conversations.json 失败: {}", e))?;
+            let conversations = match value {
+                Value::Array(conversations) => conversations,
+                conversation @ Value::Object(_) => vec![conversation],
+                _ => return Err("conversations.json 格式不正确".to_string()),
+            };
+            for (index, conversation) in conversations.iter().enumerate() {
+                match parse_chatgpt_conversation(conversation) {
+                    Ok(session) => sessions.push(session),
+                    Err(e) => {
+                        let title = conversation["title"].as_str().unwrap_or_default();
+                        skipped.push(format!("第 {} 个会话 {}: {}", index + 1, title, e));
+                    }
+                }
+            }
+        }
+        ImportFormat::Jsonl => {
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match parse_jsonl_line(line) {
+                    Ok(session) => sessions.push(session),
+                    Err(e) => skipped.push(format!("第 {} 行: {}", index + 1, e)),
+                }
+            }
+        }
+    }
+    Ok((sessions, skipped))
+}
+
+/// 按导入来源去重：与已有会话或同一文件中前面的会话来源相同的会话视为重复
+pub fn deduplicate(sessions: Vec<Session>, existing: &[Session]) -> (Vec<Session>, Vec<Session>) {
+    let mut seen: HashSet<String> = existing.iter().filter_map(|session| session.import_source.clone()).collect();
+    sessions
+        .into_iter()
+        .partition(|session| session.import_source.as_ref().is_none_or(|source| seen.insert(source.clone())))
+}
+
+/// 读取并解析导入文件，去重后交给 `save` 保存；`dry_run` 时不保存
+pub fn import_file<F>(
+    path: &Path,
+    format: Option<ImportFormat>,
+    existing: &[Session],
+    dry_run: bool,
+    mut save: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(&Session) -> Result<(), String>,
+{
+    let content = fs::read_to_string(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    let format = format.unwrap_or_else(|| ImportFormat::from_path(path));
+    let (sessions, skipped) = parse_import(&content, format)?;
+    let (sessions, duplicates) = deduplicate(sessions, existing);
+
+    let mut report = ImportReport {
+        dry_run,
+        duplicates: duplicates.into_iter().map(|session| session.title).collect(),
+        skipped,
+        ..Default::default()
+    };
+    for session in &sessions {
+        if !dry_run {
+            save(session)?;
+        }
+        report.imported.push(ImportedSession {
+            title: session.title.clone(),
+            message_count: session.messages.len(),
+            created_at: session.created_at,
+        });
+    }
+    Ok(report)
+}
@@ -2,6 +2,26 @@ pub mod models;
 pub mod chat;
 pub mod cache;
 pub mod handlers;
+pub mod usage;
+pub mod profile;
+pub mod session;
+pub mod persona;
+pub mod template;
+pub mod tools;
+pub mod builtin_tools;
+pub mod mcp;
+pub mod attachment;
+pub mod rag;
+pub mod provider;
+pub mod search;
+pub mod semantic;
+pub mod export;
+pub mod importer;
+pub mod compare;
+pub mod summary;
+pub mod organize;
+pub mod storage;
+pub mod persist;
 
 // Re-export commonly used items
 pub use handlers::{chat, fetch_models};
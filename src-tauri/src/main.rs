@@ -7,6 +7,26 @@ mod models;
 mod chat;
 mod cache;
 mod handlers;
+mod usage;
+mod profile;
+mod session;
+mod persona;
+mod template;
+mod tools;
+mod builtin_tools;
+mod mcp;
+mod attachment;
+mod rag;
+mod provider;
+mod search;
+mod semantic;
+mod export;
+mod importer;
+mod compare;
+mod summary;
+mod organize;
+mod storage;
+mod persist;
 
 fn main() {
     #[cfg(debug_assertions)]
@@ -16,11 +36,97 @@ fn main() {
     }
 
     let app = tauri::Builder::default()
-        .setup(|_| Ok(()))
+        .setup(|_| {
+            cache::load_frequencies();
+            builtin_tools::register_builtin_tools();
+            handlers::purge_expired_sessions();
+            tauri::async_runtime::spawn(mcp::connect_enabled_servers());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             handlers::chat,
+            handlers::continue_response,
             handlers::fetch_models,
-            handlers::get_cache_directory
+            handlers::get_cache_directory,
+            handlers::query_spend,
+            handlers::get_model_prices,
+            handlers::set_model_price,
+            handlers::remove_model_price,
+            handlers::get_budgets,
+            handlers::set_budgets,
+            handlers::export_usage_csv,
+            handlers::list_profiles,
+            handlers::save_profile,
+            handlers::delete_profile,
+            handlers::create_session,
+            handlers::get_session,
+            handlers::list_sessions,
+            handlers::delete_session,
+            handlers::update_session_settings,
+            handlers::set_session_persona,
+            handlers::list_personas,
+            handlers::get_persona,
+            handlers::save_persona,
+            handlers::delete_persona,
+            handlers::export_personas,
+            handlers::import_personas,
+            handlers::list_templates,
+            handlers::save_template,
+            handlers::delete_template,
+            handlers::render_template,
+            handlers::list_tools,
+            handlers::update_session_tools,
+            handlers::respond_tool_approval,
+            handlers::list_approved_directories,
+            handlers::add_approved_directory,
+            handlers::remove_approved_directory,
+            handlers::list_mcp_servers,
+            handlers::save_mcp_server,
+            handlers::delete_mcp_server,
+            handlers::connect_mcp_server,
+            handlers::disconnect_mcp_server,
+            handlers::list_mcp_connections,
+            handlers::read_mcp_resource,
+            handlers::get_mcp_prompt,
+            handlers::attach_image,
+            handlers::attach_document,
+            handlers::create_folder_index,
+            handlers::index_folder,
+            handlers::list_folder_indexes,
+            handlers::delete_folder_index,
+            handlers::search_folder_index,
+            handlers::set_session_index,
+            handlers::embed,
+            handlers::search_messages,
+            handlers::enable_semantic_search,
+            handlers::disable_semantic_search,
+            handlers::get_semantic_search_status,
+            handlers::semantic_search_messages,
+            handlers::export_session,
+            handlers::import_conversations,
+            handlers::get_active_branch,
+            handlers::switch_branch,
+            handlers::regenerate,
+            handlers::list_alternatives,
+            handlers::compare_models,
+            handlers::list_comparisons,
+            handlers::get_comparison,
+            handlers::delete_comparison,
+            handlers::get_summary_config,
+            handlers::save_summary_config,
+            handlers::summarize_session,
+            handlers::clear_session_summary,
+            handlers::list_folders,
+            handlers::create_folder,
+            handlers::rename_folder,
+            handlers::move_folder,
+            handlers::delete_folder,
+            handlers::list_tags,
+            handlers::query_sessions,
+            handlers::update_sessions,
+            handlers::empty_trash,
+            handlers::get_trash_config,
+            handlers::set_trash_config
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
@@ -28,8 +134,7 @@ fn main() {
     app.run(|_app_handle, event| match event {
         tauri::RunEvent::Ready => {}
         tauri::RunEvent::ExitRequested { api, .. } => {
-            let cache_dir = cache::get_cache_dir();
-            cache::save_frequencies(cache_dir.join("frequency.json"));
+            cache::save_frequencies();
             api.prevent_exit();
         }
         _ => {}
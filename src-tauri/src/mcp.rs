@@ -0,0 +1,757 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, StreamExt};
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use crate::storage::{read_setting, with_storage, Storage};
+use crate::chat::{ChatMessage, SseLineBuffer};
+use crate::tools::{Tool, TOOL_REGISTRY};
+
+pub const MCP_SERVERS_KEY: &str = "mcp_servers";
+
+/// 客户端声明支持的协议版本
+pub const MCP_PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// 单个请求的超时时间
+const MCP_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// HTTP 传输中携带会话 ID 的请求头
+const MCP_SESSION_HEADER: &str = "mcp-session-id";
+
+/// 工具名称的最大长度，与 OpenAI 接口的限制一致
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+fn default_true() -> bool {
+    true
+}
+
+/// MCP 服务的连接方式
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpTransportConfig {
+    /// 以子进程启动，通过标准输入输出交换按行分隔的 JSON-RPC 消息
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        #[serde(default)]
+        cwd: Option<PathBuf>,
+    },
+    /// Streamable HTTP，响应可以是 JSON 或 SSE 流
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+/// 已配置的 MCP 服务
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct McpServerConfig {
+    /// 服务名称，同时作为工具名前缀
+    pub name: String,
+    pub transport: McpTransportConfig,
+    /// 启动时是否自动连接
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 调用该服务的工具前是否需要用户确认
+    #[serde(default = "default_true")]
+    pub require_approval: bool,
+}
+
+impl McpServerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("MCP 服务名称只能包含字母、数字、下划线和连字符: {}", self.name));
+        }
+        match &self.transport {
+            McpTransportConfig::Stdio { command, .. } if command.trim().is_empty() => {
+                Err("MCP 服务的启动命令不能为空".to_string())
+            }
+            McpTransportConfig::Http { url, .. } if !url.starts_with("http://") && !url.starts_with("https://") => {
+                Err(format!("无效的 MCP 服务地址: {}", url))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 服务端声明的工具
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: Value,
+}
+
+/// 服务端提供的资源
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct McpPromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// 服务端提供的提示词
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct McpServerInfo {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+/// `initialize` 的返回结果
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpInitializeResult {
+    #[serde(default)]
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: Value,
+    #[serde(default)]
+    pub server_info: McpServerInfo,
+}
+
+impl McpInitializeResult {
+    fn supports(&self, capability: &str) -> bool {
+        self.capabilities.get(capability).is_some()
+    }
+}
+
+/// 推送给前端的服务连接状态
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct McpServerStatus {
+    pub name: String,
+    pub server_info: McpServerInfo,
+    /// 注册到工具注册表中的工具名称
+    pub tools: Vec<String>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+}
+
+/// JSON-RPC 消息的收发通道
+pub trait McpTransport: Send + Sync {
+    /// 发送请求并等待 ID 相同的响应
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, String>>;
+
+    /// 发送不需要响应的通知
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), String>>;
+}
+
+/// 从 JSON-RPC 响应中取出结果，错误响应转换为错误信息
+fn response_result(response: Value) -> Result<Value, String> {
+    if let Some(error) = response.get("error") {
+        let code = error["code"].as_i64().unwrap_or_default();
+        let message = error["message"].as_str().unwrap_or("未知错误");
+        return Err(format!("MCP 错误 {}: {}", code, message));
+    }
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+fn is_response_to(message: &Value, id: &Value) -> bool {
+    message.get("id") == Some(id) && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+struct StdioChannel {
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+impl StdioChannel {
+    async fn send(&mut self, message: &Value) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.map_err(|e| format!("写入 MCP 服务失败: {}", e))?;
+        self.writer.flush().await.map_err(|e| format!("写入 MCP 服务失败: {}", e))
+    }
+}
+
+/// 标准输入输出传输，请求串行发送
+pub struct StdioTransport {
+    channel: Mutex<StdioChannel>,
+    // 持有子进程，传输释放时结束进程
+    _child: Option<Child>,
+}
+
+impl StdioTransport {
+    /// 基于任意读写流创建传输
+    pub fn new(reader: impl AsyncRead + Send + Unpin + 'static, writer: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        StdioTransport {
+            channel: Mutex::new(StdioChannel {
+                reader: BufReader::new(Box::new(reader)),
+                writer: Box::new(writer),
+            }),
+            _child: None,
+        }
+    }
+
+    /// 启动子进程并连接其标准输入输出
+    pub fn spawn(command: &str, args: &[String], env: &HashMap<String, String>, cwd: Option<&Path>) -> Result<Self, String> {
+        let mut command_builder = Command::new(command);
+        command_builder
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(cwd) = cwd {
+            command_builder.current_dir(cwd);
+        }
+
+        let mut child = command_builder.spawn().map_err(|e| format!("启动 MCP 服务失败 {}: {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("无法获取 MCP 服务的标准输入")?;
+        let stdout = child.stdout.take().ok_or("无法获取 MCP 服务的标准输出")?;
+        let mut transport = Self::new(stdout, stdin);
+        transport._child = Some(child);
+        Ok(transport)
+    }
+}
+
+impl McpTransport for StdioTransport {
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, String>> {
+        async move {
+            let id = message["id"].clone();
+            let mut channel = self.channel.lock().await;
+            channel.send(&message).await?;
+
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let read = channel
+                    .reader
+                    .read_line(&mut line)
+                    .await
+                    .map_err(|e| format!("读取 MCP 服务输出失败: {}", e))?;
+                if read == 0 {
+                    return Err("MCP 服务已关闭连接".to_string());
+                }
+                let Ok(incoming) = serde_json::from_str::<Value>(line.trim()) else {
+                    debug!("忽略 MCP 服务的非 JSON 输出: {}", line.trim());
+                    continue;
+                };
+                if is_response_to(&incoming, &id) {
+                    return Ok(incoming);
+                }
+                // 服务端发来的请求：响应 ping，其余请求回复不支持
+                if let (Some(method), Some(request_id)) = (incoming["method"].as_str(), incoming.get("id")) {
+                    let reply = if method == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                    } else {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "error": { "code": -32601, "message": "Method not found" } })
+                    };
+                    channel.send(&reply).await?;
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), String>> {
+        async move { self.channel.lock().await.send(&message).await }.boxed()
+    }
+}
+
+/// Streamable HTTP 传输
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    session_id: std::sync::Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str, headers: &HashMap<String, String>) -> Result<Self, String> {
+        let mut header_map = HeaderMap::new();
+        header_map.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        header_map.insert(ACCEPT, HeaderValue::from_static("application/json, text/event-stream"));
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("无效的请求头 {}: {}", name, e))?;
+            let value = HeaderValue::from_str(value).map_err(|e| format!("无效的请求头值: {}", e))?;
+            header_map.insert(name, value);
+        }
+        Ok(HttpTransport {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            headers: header_map,
+            session_id: std::sync::Mutex::new(None),
+        })
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response, String> {
+        let mut headers = self.headers.clone();
+        if let Some(session_id) = self.session_id.lock().ok().and_then(|id| id.clone()) {
+            if let Ok(value) = HeaderValue::from_str(&session_id) {
+                headers.insert(MCP_SESSION_HEADER, value);
+            }
+        }
+
+        let response = self
+            .client
+            .post(&self.url)
+            .headers(headers)
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| format!("请求 MCP 服务失败: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("MCP 服务返回错误状态: {}", response.status()));
+        }
+
+        if let Some(session_id) = response.headers().get(MCP_SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+            if let Ok(mut current) = self.session_id.lock() {
+                *current = Some(session_id.to_string());
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// 从 SSE 数据行中取出 JSON-RPC 消息
+fn parse_sse_message(line: &str) -> Option<Value> {
+    let data = line.strip_prefix("data:")?.trim();
+    serde_json::from_str(data).ok()
+}
+
+impl McpTransport for HttpTransport {
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, String>> {
+        async move {
+            let id = message["id"].clone();
+            let response = self.post(&message).await?;
+            let is_stream = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("text/event-stream"));
+
+            if !is_stream {
+                return response.json().await.map_err(|e| format!("解析 MCP 响应失败: {}", e));
+            }
+
+            // SSE 流中可能先出现通知，找到对应 ID 的响应即可返回
+            let mut stream = response.bytes_stream();
+            let mut lines = SseLineBuffer::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| e.to_string())?;
+                for line in lines.push(&chunk) {
+                    if let Some(incoming) = parse_sse_message(&line) {
+                        if is_response_to(&incoming, &id) {
+                            return Ok(incoming);
+                        }
+                    }
+                }
+            }
+            lines
+                .finish()
+                .as_deref()
+                .and_then(parse_sse_message)
+                .filter(|incoming| is_response_to(incoming, &id))
+                .ok_or_else(|| "MCP 服务未返回响应".to_string())
+        }
+        .boxed()
+    }
+
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), String>> {
+        async move { self.post(&message).await.map(|_| ()) }.boxed()
+    }
+}
+
+/// 从工具调用或提示词结果的内容块中提取文本
+fn content_to_text(content: &Value) -> String {
+    let blocks = match content {
+        Value::Array(blocks) => blocks.iter().collect(),
+        Value::Object(_) => vec![content],
+        _ => vec![],
+    };
+    blocks
+        .into_iter()
+        .map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().unwrap_or_default().to_string(),
+            Some("resource") => block["resource"]["text"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("[资源 {}]", block["resource"]["uri"].as_str().unwrap_or_default())),
+            Some(kind) => format!("[{} {}]", kind, block["mimeType"].as_str().unwrap_or_default()),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// MCP 客户端，负责握手和各类请求
+pub struct McpClient {
+    transport: Box<dyn McpTransport>,
+    next_id: AtomicU64,
+}
+
+impl McpClient {
+    pub fn new(transport: Box<dyn McpTransport>) -> Self {
+        McpClient {
+            transport,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// 按配置建立连接
+    pub fn from_config(config: &McpServerConfig) -> Result<Self, String> {
+        let transport: Box<dyn McpTransport> = match &config.transport {
+            McpTransportConfig::Stdio { command, args, env, cwd } => {
+                Box::new(StdioTransport::spawn(command, args, env, cwd.as_deref())?)
+            }
+            McpTransportConfig::Http { url, headers } => Box::new(HttpTransport::new(url, headers)?),
+        };
+        Ok(Self::new(transport))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let response = tokio::time::timeout(MCP_REQUEST_TIMEOUT, self.transport.request(message))
+            .await
+            .map_err(|_| format!("MCP 请求超时: {}", method))??;
+        response_result(response)
+    }
+
+    /// 分页请求列表，合并所有页中 `key` 字段的内容
+    async fn request_all<T: serde::de::DeserializeOwned>(&self, method: &str, key: &str) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut result = self.request(method, params).await?;
+            let page: Vec<T> = serde_json::from_value(result[key].take())
+                .map_err(|e| format!("解析 {} 结果失败: {}", method, e))?;
+            items.extend(page);
+            cursor = result["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// 握手并发送 `notifications/initialized`
+    pub async fn initialize(&self) -> Result<McpInitializeResult, String> {
+        let params = json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "chat-ai", "version": env!("CARGO_PKG_VERSION") }
+        });
+        let result = self.request("initialize", params).await?;
+        let result: McpInitializeResult =
+            serde_json::from_value(result).map_err(|e| format!("解析 MCP 握手结果失败: {}", e))?;
+        self.transport
+            .notify(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>, String> {
+        self.request_all("tools/list", "tools").await
+    }
+
+    /// 调用工具，返回文本内容；服务端标记为错误的结果作为错误返回
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await?;
+        let text = content_to_text(&result["content"]);
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(text);
+        }
+        Ok(text)
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<McpResource>, String> {
+        self.request_all("resources/list", "resources").await
+    }
+
+    /// 读取资源，二进制内容以占位文本表示
+    pub async fn read_resource(&self, uri: &str) -> Result<String, String> {
+        let result = self.request("resources/read", json!({ "uri": uri })).await?;
+        let contents = result["contents"].as_array().cloned().unwrap_or_default();
+        Ok(contents
+            .iter()
+            .map(|content| match content["text"].as_str() {
+                Some(text) => text.to_string(),
+                None => format!("[二进制资源 {}]", content["uri"].as_str().unwrap_or(uri)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    pub async fn list_prompts(&self) -> Result<Vec<McpPrompt>, String> {
+        self.request_all("prompts/list", "prompts").await
+    }
+
+    /// 获取填充参数后的提示词消息
+    pub async fn get_prompt(&self, name: &str, arguments: &HashMap<String, String>) -> Result<Vec<ChatMessage>, String> {
+        let result = self
+            .request("prompts/get", json!({ "name": name, "arguments": arguments }))
+            .await?;
+        let messages = result["messages"].as_array().cloned().unwrap_or_default();
+        Ok(messages
+            .iter()
+            .map(|message| {
+                let role = message["role"].as_str().unwrap_or("user");
+                ChatMessage::new(role, &content_to_text(&message["content"]))
+            })
+            .collect())
+    }
+}
+
+/// 生成注册到工具注册表中的名称：`服务名__工具名`，非法字符替换为下划线
+pub fn exposed_tool_name(server: &str, tool: &str) -> String {
+    format!("{}__{}", server, tool)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .take(MAX_TOOL_NAME_LEN)
+        .collect()
+}
+
+/// MCP 服务提供的工具
+struct McpTool {
+    client: Arc<McpClient>,
+    exposed_name: String,
+    description: String,
+    info: McpToolInfo,
+    require_approval: bool,
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.exposed_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        if self.info.input_schema.is_object() {
+            self.info.input_schema.clone()
+        } else {
+            json!({ "type": "object", "properties": {} })
+        }
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move { self.client.call_tool(&self.info.name, arguments).await }.boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.require_approval
+    }
+}
+
+/// 让模型按 URI 读取服务资源的工具
+struct McpResourceTool {
+    client: Arc<McpClient>,
+    exposed_name: String,
+    description: String,
+    uris: Vec<String>,
+    require_approval: bool,
+}
+
+impl Tool for McpResourceTool {
+    fn name(&self) -> &str {
+        &self.exposed_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string", "enum": self.uris, "description": "要读取的资源 URI" }
+            },
+            "required": ["uri"]
+        })
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move {
+            let uri = arguments["uri"].as_str().ok_or("缺少参数: uri")?;
+            self.client.read_resource(uri).await
+        }
+        .boxed()
+    }
+
+    fn requires_approval(&self) -> bool {
+        self.require_approval
+    }
+}
+
+struct McpConnection {
+    client: Arc<McpClient>,
+    status: McpServerStatus,
+}
+
+lazy_static! {
+    static ref MCP_CONNECTIONS: Mutex<HashMap<String, McpConnection>> = Mutex::new(HashMap::new());
+}
+
+pub fn load_mcp_servers(storage: &dyn Storage) -> Result<Vec<McpServerConfig>, String> {
+    read_setting(storage, MCP_SERVERS_KEY)
+}
+
+/// 握手并发现服务的工具、资源和提示词，将工具注册到全局工具注册表
+pub async fn attach_client(name: &str, client: McpClient, require_approval: bool) -> Result<McpServerStatus, String> {
+    let client = Arc::new(client);
+    let info = client.initialize().await?;
+    let tools = if info.supports("tools") { client.list_tools().await? } else { vec![] };
+    let resources = if info.supports("resources") { client.list_resources().await? } else { vec![] };
+    let prompts = if info.supports("prompts") { client.list_prompts().await? } else { vec![] };
+
+    let mut registered: Vec<Arc<dyn Tool>> = tools
+        .into_iter()
+        .map(|tool| {
+            Arc::new(McpTool {
+                client: client.clone(),
+                exposed_name: exposed_tool_name(name, &tool.name),
+                description: format!("[{}] {}", name, tool.description.clone().unwrap_or_default()),
+                info: tool,
+                require_approval,
+            }) as Arc<dyn Tool>
+        })
+        .collect();
+    if !resources.is_empty() {
+        registered.push(Arc::new(McpResourceTool {
+            client: client.clone(),
+            exposed_name: exposed_tool_name(name, "read_resource"),
+            description: format!("[{}] 读取 MCP 服务提供的资源", name),
+            uris: resources.iter().map(|r| r.uri.clone()).collect(),
+            require_approval,
+        }));
+    }
+
+    let status = McpServerStatus {
+        name: name.to_string(),
+        server_info: info.server_info,
+        tools: registered.iter().map(|tool| tool.name().to_string()).collect(),
+        resources,
+        prompts,
+    };
+
+    disconnect_server(name).await;
+    if let Ok(mut registry) = TOOL_REGISTRY.write() {
+        for tool in registered {
+            registry.register(tool);
+        }
+    }
+    MCP_CONNECTIONS.lock().await.insert(
+        name.to_string(),
+        McpConnection {
+            client,
+            status: status.clone(),
+        },
+    );
+    Ok(status)
+}
+
+/// 连接配置中的服务，已连接时重新连接
+pub async fn connect_server(config: &McpServerConfig) -> Result<McpServerStatus, String> {
+    config.validate()?;
+    let client = McpClient::from_config(config)?;
+    attach_client(&config.name, client, config.require_approval).await
+}
+
+/// 断开服务并从工具注册表中移除其工具，返回之前是否已连接
+pub async fn disconnect_server(name: &str) -> bool {
+    let Some(connection) = MCP_CONNECTIONS.lock().await.remove(name) else {
+        return false;
+    };
+    if let Ok(mut registry) = TOOL_REGISTRY.write() {
+        for tool in &connection.status.tools {
+            registry.unregister(tool);
+        }
+    }
+    true
+}
+
+/// 连接所有已启用的服务，应用启动时调用
+pub async fn connect_enabled_servers() {
+    let servers = match with_storage(|storage| load_mcp_servers(storage)) {
+        Ok(servers) => servers,
+        Err(e) => {
+            warn!("读取 MCP 服务配置失败: {}", e);
+            return;
+        }
+    };
+    for server in servers.iter().filter(|s| s.enabled) {
+        if let Err(e) = connect_server(server).await {
+            warn!("连接 MCP 服务 {} 失败: {}", server.name, e);
+        }
+    }
+}
+
+pub async fn server_statuses() -> Vec<McpServerStatus> {
+    let mut statuses: Vec<McpServerStatus> = MCP_CONNECTIONS
+        .lock()
+        .await
+        .values()
+        .map(|connection| connection.status.clone())
+        .collect();
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    statuses
+}
+
+async fn connected_client(server: &str) -> Result<Arc<McpClient>, String> {
+    MCP_CONNECTIONS
+        .lock()
+        .await
+        .get(server)
+        .map(|connection| connection.client.clone())
+        .ok_or_else(|| format!("MCP 服务未连接: {}", server))
+}
+
+pub async fn read_resource(server: &str, uri: &str) -> Result<String, String> {
+    connected_client(server).await?.read_resource(uri).await
+}
+
+pub async fn get_prompt(server: &str, name: &str, arguments: &HashMap<String, String>) -> Result<Vec<ChatMessage>, String> {
+    connected_client(server).await?.get_prompt(name, arguments).await
+}
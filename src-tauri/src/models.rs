@@ -19,4 +19,32 @@ pub struct AvailableModelsResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelFrequency {
     pub frequencies: HashMap<String, i32>,
-}
\ No newline at end of file
+}
+/// `/embeddings` 请求体
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingPayload {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingData {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
+/// 嵌入接口的用量，没有 `completion_tokens`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub data: Vec<EmbeddingData>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub usage: Option<EmbeddingUsage>,
+}
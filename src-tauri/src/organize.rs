@@ -0,0 +1,348 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::chat::generate_id;
+use crate::session::{now_millis, Session, SessionSummary};
+use crate::storage::{read_setting, write_setting, Storage};
+
+pub const FOLDERS_KEY: &str = "folders";
+pub(crate) const TRASH_CONFIG_KEY: &str = "trash_config";
+
+/// 回收站默认保留天数
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// 会话文件夹，通过 `parent_id` 嵌套
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Folder {
+    pub id: String,
+    pub name: String,
+    /// 上级文件夹 ID，为空表示在根目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashConfig {
+    /// 会话在回收站中保留的天数，为 0 时不自动清理
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_retention_days() -> u32 {
+    DEFAULT_TRASH_RETENTION_DAYS
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: default_retention_days(),
+        }
+    }
+}
+
+pub fn load_folders(storage: &dyn Storage) -> Result<Vec<Folder>, String> {
+    read_setting(storage, FOLDERS_KEY)
+}
+
+pub fn load_trash_config(storage: &dyn Storage) -> Result<TrashConfig, String> {
+    read_setting(storage, TRASH_CONFIG_KEY)
+}
+
+pub fn save_trash_config(storage: &mut dyn Storage, config: &TrashConfig) -> Result<(), String> {
+    write_setting(storage, TRASH_CONFIG_KEY, config)
+}
+
+fn find_folder<'a>(folders: &'a [Folder], id: &str) -> Result<&'a Folder, String> {
+    folders
+        .iter()
+        .find(|folder| folder.id == id)
+        .ok_or_else(|| format!("文件夹不存在: {}", id))
+}
+
+/// 同一上级文件夹下不允许重名
+fn check_folder_name(folders: &[Folder], name: &str, parent_id: Option<&str>, exclude: Option<&str>) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("文件夹名称不能为空".to_string());
+    }
+    let duplicate = folders.iter().any(|folder| {
+        folder.parent_id.as_deref() == parent_id && folder.name == name && Some(folder.id.as_str()) != exclude
+    });
+    if duplicate {
+        return Err(format!("文件夹已存在: {}", name));
+    }
+    Ok(name.to_string())
+}
+
+pub fn create_folder(folders: &mut Vec<Folder>, name: &str, parent_id: Option<String>) -> Result<Folder, String> {
+    if let Some(parent_id) = &parent_id {
+        find_folder(folders, parent_id)?;
+    }
+    let name = check_folder_name(folders, name, parent_id.as_deref(), None)?;
+    let folder = Folder {
+        id: generate_id("folder"),
+        name,
+        parent_id,
+        created_at: now_millis(),
+    };
+    folders.push(folder.clone());
+    Ok(folder)
+}
+
+pub fn rename_folder(folders: &mut [Folder], id: &str, name: &str) -> Result<Folder, String> {
+    let parent_id = find_folder(folders, id)?.parent_id.clone();
+    let name = check_folder_name(folders, name, parent_id.as_deref(), Some(id))?;
+    let folder = folders.iter_mut().find(|folder| folder.id == id).expect("文件夹已校验存在");
+    folder.name = name;
+    Ok(folder.clone())
+}
+
+/// 文件夹及其所有下级文件夹的 ID
+pub fn descendant_ids(folders: &[Folder], id: &str) -> HashSet<String> {
+    let mut ids = HashSet::from([id.to_string()]);
+    // 逐层向下查找，直到没有新的下级文件夹
+    loop {
+        let before = ids.len();
+        for folder in folders {
+            if folder.parent_id.as_ref().is_some_and(|parent| ids.contains(parent)) {
+                ids.insert(folder.id.clone());
+            }
+        }
+        if ids.len() == before {
+            return ids;
+        }
+    }
+}
+
+/// 移动文件夹，不能移动到自身或下级文件夹中
+pub fn move_folder(folders: &mut [Folder], id: &str, parent_id: Option<String>) -> Result<Folder, String> {
+    let name = find_folder(folders, id)?.name.clone();
+    if let Some(parent_id) = &parent_id {
+        find_folder(folders, parent_id)?;
+        if descendant_ids(folders, id).contains(parent_id) {
+            return Err("不能将文件夹移动到自身或其子文件夹中".to_string());
+        }
+    }
+    check_folder_name(folders, &name, parent_id.as_deref(), Some(id))?;
+    let folder = folders.iter_mut().find(|folder| folder.id == id).expect("文件夹已校验存在");
+    folder.parent_id = parent_id;
+    Ok(folder.clone())
+}
+
+/// 删除文件夹，其下级文件夹移到上一级；返回上一级文件夹 ID，文件夹中的会话应移到那里
+pub fn remove_folder(folders: &mut Vec<Folder>, id: &str) -> Result<Option<String>, String> {
+    let parent_id = find_folder(folders, id)?.parent_id.clone();
+    folders.retain(|folder| folder.id != id);
+    for folder in folders.iter_mut().filter(|folder| folder.parent_id.as_deref() == Some(id)) {
+        folder.parent_id = parent_id.clone();
+    }
+    Ok(parent_id)
+}
+
+/// 去掉首尾空白和空标签，去重并保持原有顺序
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.to_string()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// 标签及使用它的会话数
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// 统计回收站以外的会话使用的标签，按名称排序
+pub fn tag_counts(sessions: &[Session]) -> Vec<TagCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for session in sessions.iter().filter(|session| !session.is_trashed()) {
+        for tag in &session.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag: tag.to_string(), count })
+        .collect()
+}
+
+/// 按文件夹筛选会话
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FolderFilter {
+    /// 不在任何文件夹中的会话
+    Root,
+    Folder {
+        id: String,
+        /// 是否包含下级文件夹中的会话
+        #[serde(default)]
+        include_subfolders: bool,
+    },
+}
+
+/// 会话查询条件，未设置的条件不做筛选
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SessionQuery {
+    #[serde(default)]
+    pub folder: Option<FolderFilter>,
+    /// 会话须包含全部标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    #[serde(default)]
+    pub archived: Option<bool>,
+    /// 为 true 时只查询回收站中的会话，否则不包含回收站中的会话
+    #[serde(default)]
+    pub trashed: bool,
+    /// 标题包含的文本，不区分大小写
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl SessionQuery {
+    fn matches(&self, session: &Session, folder_ids: Option<&HashSet<String>>) -> bool {
+        let folder_matches = match (&self.folder, folder_ids) {
+            (None, _) => true,
+            (Some(FolderFilter::Root), _) => session.folder_id.is_none(),
+            (Some(FolderFilter::Folder { .. }), Some(ids)) => session.folder_id.as_ref().is_some_and(|id| ids.contains(id)),
+            (Some(FolderFilter::Folder { .. }), None) => false,
+        };
+        let title_matches = self
+            .title
+            .as_deref()
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+            .is_none_or(|title| session.title.to_lowercase().contains(&title.to_lowercase()));
+        folder_matches
+            && title_matches
+            && session.is_trashed() == self.trashed
+            && self.tags.iter().all(|tag| session.tags.contains(tag))
+            && self.pinned.is_none_or(|pinned| session.pinned == pinned)
+            && self.archived.is_none_or(|archived| session.archived == archived)
+    }
+}
+
+/// 查询会话，置顶的在前，其余按最近更新时间倒序
+pub fn query_sessions(sessions: &[Session], folders: &[Folder], query: &SessionQuery) -> Vec<SessionSummary> {
+    let folder_ids = match &query.folder {
+        Some(FolderFilter::Folder { id, include_subfolders: true }) => Some(descendant_ids(folders, id)),
+        Some(FolderFilter::Folder { id, include_subfolders: false }) => Some(HashSet::from([id.clone()])),
+        _ => None,
+    };
+    let mut summaries: Vec<SessionSummary> = sessions
+        .iter()
+        .filter(|session| query.matches(session, folder_ids.as_ref()))
+        .map(Session::summary)
+        .collect();
+    summaries.sort_by_key(|summary| (Reverse(summary.pinned), Reverse(summary.updated_at)));
+    summaries
+}
+
+/// 批量操作
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// 移动到文件夹，`folder_id` 为空时移到根目录
+    Move { folder_id: Option<String> },
+    AddTags { tags: Vec<String> },
+    RemoveTags { tags: Vec<String> },
+    Pin,
+    Unpin,
+    Archive,
+    Unarchive,
+    /// 移入回收站
+    Trash,
+    /// 从回收站恢复
+    Restore,
+    /// 永久删除回收站中的会话
+    Purge,
+}
+
+/// 批量操作中失败的会话
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BulkFailure {
+    pub session_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct BulkReport {
+    /// 发生变化的会话
+    pub updated: Vec<String>,
+    pub failed: Vec<BulkFailure>,
+}
+
+/// 对会话执行批量操作，返回会话是否发生变化；`Purge` 需要删除会话，由 [`purge_session`] 处理。
+///
+/// 整理操作不修改 `updated_at`，以免打乱按最近对话排序的列表。
+pub fn apply_action(session: &mut Session, action: &BulkAction, now: i64) -> bool {
+    match action {
+        BulkAction::Move { folder_id } => replace(&mut session.folder_id, folder_id.clone()),
+        BulkAction::AddTags { tags } => {
+            let merged: Vec<String> = session.tags.iter().chain(tags).cloned().collect();
+            replace(&mut session.tags, normalize_tags(&merged))
+        }
+        BulkAction::RemoveTags { tags } => {
+            let tags = normalize_tags(tags);
+            let remaining = session.tags.iter().filter(|tag| !tags.contains(tag)).cloned().collect();
+            replace(&mut session.tags, remaining)
+        }
+        BulkAction::Pin => replace(&mut session.pinned, true),
+        BulkAction::Unpin => replace(&mut session.pinned, false),
+        BulkAction::Archive => replace(&mut session.archived, true),
+        BulkAction::Unarchive => replace(&mut session.archived, false),
+        BulkAction::Trash => session.deleted_at.is_none() && replace(&mut session.deleted_at, Some(now)),
+        BulkAction::Restore => replace(&mut session.deleted_at, None),
+        BulkAction::Purge => false,
+    }
+}
+
+/// 永久删除回收站中的会话；不在回收站中的会话拒绝删除，需先移入回收站
+pub fn purge_session(storage: &mut dyn Storage, session_id: &str) -> Result<(), String> {
+    if !storage.load_session(session_id)?.is_trashed() {
+        return Err("会话不在回收站中，不能永久删除".to_string());
+    }
+    storage.delete_session(session_id)
+}
+
+fn replace<T: PartialEq>(field: &mut T, value: T) -> bool {
+    if *field == value {
+        return false;
+    }
+    *field = value;
+    true
+}
+
+/// 在回收站中超过保留天数的会话
+pub fn expired_trash(sessions: &[Session], retention_days: u32, now: i64) -> Vec<&Session> {
+    if retention_days == 0 {
+        return Vec::new();
+    }
+    let cutoff = now - retention_days as i64 * DAY_MILLIS;
+    sessions
+        .iter()
+        .filter(|session| session.deleted_at.is_some_and(|deleted_at| deleted_at <= cutoff))
+        .collect()
+}
+
+/// 永久删除回收站中过期的会话，返回删除的会话 ID
+pub fn purge_expired_trash(storage: &mut dyn Storage, retention_days: u32, now: i64) -> Result<Vec<String>, String> {
+    let sessions = storage.list_sessions()?;
+    let mut purged = Vec::new();
+    for session in expired_trash(&sessions, retention_days, now) {
+        match storage.delete_session(&session.id) {
+            Ok(()) => purged.push(session.id.clone()),
+            Err(e) => warn!("清理回收站中的会话 {} 失败: {}", session.id, e),
+        }
+    }
+    Ok(purged)
+}
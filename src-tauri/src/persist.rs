@@ -0,0 +1,113 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use fs4::fs_std::FileExt;
+
+/// 同一进程内生成不重复的临时文件名
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 异步等待文件锁时的重试间隔
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+fn sibling_path(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("{}{}{}", prefix, name, suffix))
+}
+
+fn create_parent(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 重命名后同步所在目录，确保断电后新的目录项仍然存在
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// 持有目标文件旁 `<文件名>.lock` 的排他锁，多个实例读-改-写同一个文件时依次进行，释放时自动解锁。
+///
+/// 只用于缓存目录中共享的文件；一次性写入（如导出）直接用 [`write_atomic`]，不会留下锁文件。
+pub struct FileLock {
+    lock: File,
+}
+
+impl FileLock {
+    /// 获取文件的锁，其他实例持有锁时阻塞等待
+    pub fn acquire(path: &Path) -> Result<Self, String> {
+        let lock = open_lock_file(path)?;
+        lock.lock_exclusive().map_err(|e| format!("锁定 {} 失败: {}", path.display(), e))?;
+        Ok(FileLock { lock })
+    }
+
+    /// 同 [`FileLock::acquire`]，在异步任务中等待锁，不占用运行时的线程
+    pub async fn acquire_async(path: &Path) -> Result<Self, String> {
+        let lock = open_lock_file(path)?;
+        while !lock.try_lock_exclusive().map_err(|e| format!("锁定 {} 失败: {}", path.display(), e))? {
+            tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+        }
+        Ok(FileLock { lock })
+    }
+}
+
+fn open_lock_file(path: &Path) -> Result<File, String> {
+    create_parent(path)?;
+    let lock_path = sibling_path(path, "", ".lock");
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("打开锁文件 {} 失败: {}", lock_path.display(), e))
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.lock);
+    }
+}
+
+/// 原子地替换文件内容，自动创建父目录
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    write_atomic_with(path, |file| file.write_all(contents.as_ref()))
+}
+
+/// 先写入同目录下的临时文件并落盘，再重命名覆盖目标文件。
+///
+/// 写入中途失败或进程崩溃时目标文件保持原样，不会出现只写了一半的文件。
+pub fn write_atomic_with<F>(path: &Path, write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    create_parent(path)?;
+    let temp = sibling_path(
+        path,
+        ".",
+        &format!(".tmp-{}-{}", std::process::id(), TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)),
+    );
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path))
+        .and_then(|_| sync_parent(path));
+    result.map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("写入 {} 失败: {}", path.display(), e)
+    })
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use crate::chat::{ChatMessage, GenerationParams};
+use crate::storage::{read_setting, Storage};
+
+pub const PERSONAS_KEY: &str = "personas";
+
+/// 预设包格式版本，导入时拒绝更高版本
+pub const PERSONA_PACK_VERSION: u32 = 1;
+
+/// 命名的系统提示词预设，可附带默认模型和生成参数
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Persona {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub system_prompt: String,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub params: GenerationParams,
+}
+
+/// 导入导出用的预设包
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PersonaPack {
+    pub version: u32,
+    pub personas: Vec<Persona>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PersonaImportReport {
+    pub imported: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl Persona {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("预设名称不能为空".to_string());
+        }
+        if self.system_prompt.trim().is_empty() {
+            return Err("系统提示词不能为空".to_string());
+        }
+        self.params.validate()
+    }
+
+    pub fn system_message(&self) -> ChatMessage {
+        ChatMessage::new("system", &self.system_prompt)
+    }
+}
+
+pub fn load_personas(storage: &dyn Storage) -> Result<Vec<Persona>, String> {
+    read_setting(storage, PERSONAS_KEY)
+}
+
+pub fn find_persona(personas: &[Persona], name: &str) -> Option<Persona> {
+    personas.iter().find(|p| p.name == name).cloned()
+}
+
+/// 新增或按名称替换预设
+pub fn upsert_persona(personas: &mut Vec<Persona>, persona: Persona) {
+    match personas.iter_mut().find(|p| p.name == persona.name) {
+        Some(existing) => *existing = persona,
+        None => personas.push(persona),
+    }
+}
+
+/// 在消息列表开头插入系统提示词，已有相同的系统消息时不重复插入。
+///
+/// 历史中可能已有其他系统消息（如会话摘要），人设的提示词仍放在最前面。
+pub fn apply_system_prompt(persona: &Persona, messages: &mut Vec<ChatMessage>) {
+    let system_message = persona.system_message();
+    if messages.iter().any(|m| m.role == "system" && m.content == system_message.content) {
+        return;
+    }
+    messages.insert(0, system_message);
+}
+
+/// 打包指定名称的预设，`names` 为 None 时导出全部
+pub fn build_pack(personas: &[Persona], names: Option<&[String]>) -> PersonaPack {
+    PersonaPack {
+        version: PERSONA_PACK_VERSION,
+        personas: personas
+            .iter()
+            .filter(|p| names.is_none_or(|names| names.contains(&p.name)))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// 合并预设包，同名预设仅在 `overwrite` 时覆盖
+pub fn merge_pack(personas: &mut Vec<Persona>, pack: PersonaPack, overwrite: bool) -> Result<PersonaImportReport, String> {
+    if pack.version > PERSONA_PACK_VERSION {
+        return Err(format!("不支持的预设包版本: {}", pack.version));
+    }
+
+    let mut report = PersonaImportReport::default();
+    for persona in pack.personas {
+        if persona.validate().is_err() || (!overwrite && personas.iter().any(|p| p.name == persona.name)) {
+            report.skipped.push(persona.name);
+            continue;
+        }
+        report.imported.push(persona.name.clone());
+        upsert_persona(personas, persona);
+    }
+    Ok(report)
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::chat::GenerationParams;
+use crate::storage::{read_setting, Storage};
+
+pub const PROFILES_KEY: &str = "profiles";
+
+/// 未指定配置时使用的配置名，其参数作为所有请求的默认值
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// 一组命名的请求配置，例如区分工作和个人使用的账号
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Profile {
+    #[serde(default)]
+    pub params: GenerationParams,
+}
+
+pub fn load_profiles(storage: &dyn Storage) -> Result<HashMap<String, Profile>, String> {
+    read_setting(storage, PROFILES_KEY)
+}
+
+/// 计算配置的生效参数：指定配置的参数优先，未设置的字段回落到默认配置
+pub fn profile_params(profiles: &HashMap<String, Profile>, profile: Option<&str>) -> GenerationParams {
+    let defaults = profiles
+        .get(DEFAULT_PROFILE)
+        .map(|p| p.params.clone())
+        .unwrap_or_default();
+
+    match profile.filter(|name| *name != DEFAULT_PROFILE).and_then(|name| profiles.get(name)) {
+        Some(profile) => profile.params.clone().merged_over(&defaults),
+        None => defaults,
+    }
+}
@@ -0,0 +1,163 @@
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use log::{debug, error};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use crate::chat::Usage;
+use crate::models::{EmbeddingPayload, EmbeddingResponse};
+
+/// 每次请求嵌入接口的默认最大文本数
+pub const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// 构建带鉴权信息的请求头
+pub fn build_headers(api_key: &str) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", api_key))
+            .map_err(|e| e.to_string())?
+    );
+    Ok(headers)
+}
+
+/// 由聊天接口地址推导同一服务下其他接口的地址，例如 `models`、`embeddings`
+pub fn endpoint_url(api_url: &str, endpoint: &str) -> String {
+    if let Some(base) = api_url.strip_suffix("/chat/completions") {
+        format!("{}/{}", base, endpoint)
+    } else if api_url.ends_with("/v1") || api_url.ends_with("/v1/") {
+        format!("{}/{}", api_url.trim_end_matches('/'), endpoint)
+    } else {
+        format!("{}/v1/{}", api_url.trim_end_matches('/'), endpoint)
+    }
+}
+
+/// 一次嵌入请求的结果，多个批次的结果已合并
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EmbeddingResult {
+    pub model: String,
+    /// 与输入顺序一致的向量
+    pub embeddings: Vec<Vec<f32>>,
+    /// 向量维度
+    pub dimensions: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// 模型服务提供方
+pub trait Provider: Send + Sync {
+    /// 生成文本向量，输入较多时分批请求
+    fn embed<'a>(&'a self, model: &'a str, input: Vec<String>) -> BoxFuture<'a, Result<EmbeddingResult, String>>;
+}
+
+/// OpenAI 兼容接口
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    batch_size: usize,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_url: &str, api_key: &str) -> Self {
+        OpenAiProvider {
+            client: reqwest::Client::new(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    async fn embed_batch(&self, url: &str, model: &str, input: &[String]) -> Result<EmbeddingResponse, String> {
+        let payload = EmbeddingPayload {
+            model: model.to_string(),
+            input: input.to_vec(),
+        };
+        let response = self
+            .client
+            .post(url)
+            .headers(build_headers(&self.api_key)?)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| format!("嵌入请求失败: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let error_msg = format!("嵌入请求失败，状态码: {}，{}", status, api_error_message(&body));
+            error!("{}，URL: {}", error_msg, url);
+            return Err(error_msg);
+        }
+
+        let mut embedding_response: EmbeddingResponse =
+            response.json().await.map_err(|e| format!("解析嵌入结果失败: {}", e))?;
+        if embedding_response.data.len() != input.len() {
+            return Err(format!(
+                "嵌入接口返回的向量数量不匹配: 期望 {}，实际 {}",
+                input.len(),
+                embedding_response.data.len()
+            ));
+        }
+        embedding_response.data.sort_by_key(|data| data.index);
+        Ok(embedding_response)
+    }
+}
+
+/// 从错误响应中取出错误信息，非 JSON 时返回原文
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+impl Provider for OpenAiProvider {
+    fn embed<'a>(&'a self, model: &'a str, input: Vec<String>) -> BoxFuture<'a, Result<EmbeddingResult, String>> {
+        async move {
+            if input.is_empty() {
+                return Err("嵌入输入不能为空".to_string());
+            }
+            if let Some(position) = input.iter().position(|text| text.trim().is_empty()) {
+                return Err(format!("第 {} 条嵌入输入为空", position + 1));
+            }
+
+            let url = endpoint_url(&self.api_url, "embeddings");
+            let mut result = EmbeddingResult {
+                model: model.to_string(),
+                ..Default::default()
+            };
+            for batch in input.chunks(self.batch_size) {
+                debug!("请求嵌入 {} 条文本: {}", batch.len(), url);
+                let response = self.embed_batch(&url, model, batch).await?;
+                if let Some(actual_model) = response.model {
+                    result.model = actual_model;
+                }
+                if let Some(usage) = response.usage {
+                    let usage = Usage {
+                        prompt_tokens: usage.prompt_tokens,
+                        completion_tokens: 0,
+                        total_tokens: usage.total_tokens,
+                    };
+                    match &mut result.usage {
+                        Some(total) => total.accumulate(&usage),
+                        None => result.usage = Some(usage),
+                    }
+                }
+                result.embeddings.extend(response.data.into_iter().map(|data| data.embedding));
+            }
+
+            result.dimensions = result.embeddings.first().map(Vec::len).unwrap_or_default();
+            if result.embeddings.iter().any(|embedding| embedding.len() != result.dimensions) {
+                return Err("嵌入接口返回的向量维度不一致".to_string());
+            }
+            Ok(result)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::attachment::{chunk_text, extract_text, DocumentKind};
+use crate::cache::{get_cache_dir, read_json_file, write_json_file};
+use crate::persist::FileLock;
+use crate::chat::generate_id;
+use crate::provider::DEFAULT_EMBEDDING_BATCH_SIZE;
+use crate::session::now_millis;
+
+const INDEXES_DIR: &str = "indexes";
+
+/// 索引分块的目标字符数
+pub const INDEX_CHUNK_CHARS: usize = 1500;
+
+/// 检索时默认返回的分块数
+pub const DEFAULT_TOP_K: usize = 5;
+
+/// 超过该大小的文件不建立索引
+const MAX_INDEXED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// 遍历时跳过的目录，以 `.` 开头的目录也会被跳过
+const IGNORED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "__pycache__", "venv"];
+
+/// 文件中的一个分块及其向量
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexedChunk {
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// 已建立索引的文件，按修改时间和大小判断是否需要重新索引
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexedFile {
+    /// 修改时间，毫秒时间戳
+    pub modified: i64,
+    pub size: u64,
+    pub chunks: Vec<IndexedChunk>,
+}
+
+/// 某个目录的向量索引
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct FolderIndex {
+    pub id: String,
+    pub root: PathBuf,
+    /// 生成向量使用的嵌入模型，更换模型后需要全量重建
+    pub model: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// 键为相对于 `root` 的路径，统一使用 `/` 分隔
+    #[serde(default)]
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+/// 索引列表中展示的摘要信息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexSummary {
+    pub id: String,
+    pub root: PathBuf,
+    pub model: String,
+    pub updated_at: i64,
+    pub file_count: usize,
+    pub chunk_count: usize,
+}
+
+/// 一次增量索引的结果
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct IndexReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+    /// 二进制或无法读取的文件
+    pub skipped: Vec<String>,
+    /// 本次新生成的向量数
+    pub embedded_chunks: usize,
+}
+
+impl IndexReport {
+    /// 索引内容是否有变化
+    pub fn has_changes(&self) -> bool {
+        !(self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty() && self.skipped.is_empty())
+    }
+}
+
+/// 检索到的分块
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetrievedChunk {
+    pub path: String,
+    pub chunk_index: usize,
+    pub text: String,
+    pub score: f32,
+}
+
+impl FolderIndex {
+    pub fn new(root: PathBuf, model: &str) -> Self {
+        let now = now_millis();
+        FolderIndex {
+            id: generate_id("index"),
+            root,
+            model: model.to_string(),
+            created_at: now,
+            updated_at: now,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// 更换嵌入模型，已有向量全部作废，下次更新时全量重建
+    pub fn set_model(&mut self, model: &str) {
+        if self.model != model {
+            self.model = model.to_string();
+            self.files.clear();
+        }
+    }
+
+    pub fn summary(&self) -> IndexSummary {
+        IndexSummary {
+            id: self.id.clone(),
+            root: self.root.clone(),
+            model: self.model.clone(),
+            updated_at: self.updated_at,
+            file_count: self.files.len(),
+            chunk_count: self.files.values().map(|file| file.chunks.len()).sum(),
+        }
+    }
+
+    /// 按余弦相似度返回与查询向量最接近的 `top_k` 个分块
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<RetrievedChunk> {
+        let mut results: Vec<RetrievedChunk> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().enumerate().map(move |(chunk_index, chunk)| RetrievedChunk {
+                    path: path.clone(),
+                    chunk_index,
+                    text: chunk.text.clone(),
+                    score: cosine_similarity(query, &chunk.embedding),
+                })
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(top_k);
+        results
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// 当前目录中的文件状态
+struct FileState {
+    path: PathBuf,
+    modified: i64,
+    size: u64,
+}
+
+fn relative_key(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 递归收集目录下需要索引的文件
+fn collect_files(root: &Path) -> Result<BTreeMap<String, FileState>, String> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| format!("读取目录失败 {}: {}", dir.display(), e))?;
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if !metadata.is_file() || name.starts_with('.') || metadata.len() > MAX_INDEXED_FILE_BYTES {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_millis() as i64)
+                .unwrap_or_default();
+            files.insert(
+                relative_key(root, &path),
+                FileState {
+                    path,
+                    modified,
+                    size: metadata.len(),
+                },
+            );
+        }
+    }
+    Ok(files)
+}
+
+/// 增量更新索引：只为新增或修改过的文件重新分块并生成向量，删除已不存在的文件。
+///
+/// `embed` 接收一批文本，按相同顺序返回向量。
+pub async fn update_index<F, Fut>(index: &mut FolderIndex, embed: F) -> Result<IndexReport, String>
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<Vec<f32>>, String>>,
+{
+    let current = collect_files(&index.root)?;
+    let mut report = IndexReport::default();
+
+    let removed: Vec<String> = index.files.keys().filter(|key| !current.contains_key(*key)).cloned().collect();
+    for key in removed {
+        index.files.remove(&key);
+        report.removed.push(key);
+    }
+
+    for (key, state) in current {
+        let existing = index.files.get(&key);
+        if existing.is_some_and(|file| file.modified == state.modified && file.size == state.size) {
+            report.unchanged += 1;
+            continue;
+        }
+        let is_new = existing.is_none();
+
+        let text = match extract_text(&state.path, &DocumentKind::from_path(&state.path)) {
+            Ok(text) => text,
+            Err(e) => {
+                // 记录文件状态但不生成分块，文件未变化时不再重复读取
+                debug!("跳过无法索引的文件 {}: {}", key, e);
+                index.files.insert(
+                    key.clone(),
+                    IndexedFile {
+                        modified: state.modified,
+                        size: state.size,
+                        chunks: vec![],
+                    },
+                );
+                report.skipped.push(key);
+                continue;
+            }
+        };
+        let chunks: Vec<String> = chunk_text(&text, INDEX_CHUNK_CHARS)
+            .into_iter()
+            .filter(|chunk| !chunk.trim().is_empty())
+            .collect();
+
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(DEFAULT_EMBEDDING_BATCH_SIZE) {
+            let vectors = embed(batch.to_vec()).await?;
+            if vectors.len() != batch.len() {
+                return Err(format!("嵌入接口返回的向量数量不匹配: 期望 {}，实际 {}", batch.len(), vectors.len()));
+            }
+            embeddings.extend(vectors);
+        }
+        report.embedded_chunks += embeddings.len();
+
+        index.files.insert(
+            key.clone(),
+            IndexedFile {
+                modified: state.modified,
+                size: state.size,
+                chunks: chunks
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|(text, embedding)| IndexedChunk { text, embedding })
+                    .collect(),
+            },
+        );
+        if is_new {
+            report.added.push(key);
+        } else {
+            report.updated.push(key);
+        }
+    }
+
+    index.updated_at = now_millis();
+    Ok(report)
+}
+
+/// 将检索到的分块作为参考资料拼接在用户消息之前
+pub fn compose_with_context(message: &str, chunks: &[RetrievedChunk]) -> String {
+    if chunks.is_empty() {
+        return message.to_string();
+    }
+
+    let mut composed = String::from("以下是从本地知识库中检索到的相关内容，回答时可以参考：\n\n");
+    for chunk in chunks {
+        composed.push_str(&format!("<excerpt source=\"{}#{}\">\n", chunk.path, chunk.chunk_index + 1));
+        composed.push_str(chunk.text.trim_end());
+        composed.push_str("\n</excerpt>\n\n");
+    }
+    composed.push_str(message);
+    composed
+}
+
+pub fn indexes_dir() -> PathBuf {
+    get_cache_dir().join(INDEXES_DIR)
+}
+
+fn index_file(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    // 索引 ID 直接作为文件名，拒绝可能跳出目录的 ID
+    if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+        return Err(format!("无效的索引 ID: {}", id));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+pub fn load_index(dir: &Path, id: &str) -> Result<FolderIndex, String> {
+    let path = index_file(dir, id)?;
+    if !path.exists() {
+        return Err(format!("索引不存在: {}", id));
+    }
+    read_json_file(&path)
+}
+
+/// 锁定索引文件，持有期间读取、更新并写回索引，多个实例同时更新同一个索引时依次进行
+pub async fn lock_index(dir: &Path, id: &str) -> Result<FileLock, String> {
+    FileLock::acquire_async(&index_file(dir, id)?).await
+}
+
+pub fn save_index(dir: &Path, index: &FolderIndex) -> Result<(), String> {
+    write_json_file(&index_file(dir, &index.id)?, index)
+}
+
+pub fn delete_index(dir: &Path, id: &str) -> Result<(), String> {
+    let path = index_file(dir, id)?;
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| format!("删除索引失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 列出所有索引的摘要，按最近更新时间倒序
+pub fn list_indexes(dir: &Path) -> Result<Vec<IndexSummary>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut summaries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("读取索引目录失败: {}", e))? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match read_json_file::<FolderIndex>(&path) {
+            Ok(index) => summaries.push(index.summary()),
+            Err(e) => warn!("跳过无法解析的索引文件: {}", e),
+        }
+    }
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.updated_at));
+    Ok(summaries)
+}
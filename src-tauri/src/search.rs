@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::{Local, NaiveDate, TimeZone};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use crate::session::Session;
+
+/// 片段中命中位置前后保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// 默认返回的结果数
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// 分词结果，`start`/`end` 为原文中的字节偏移，相邻的词在结果中也相邻
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub term: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{4E00}'..='\u{9FFF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{3040}'..='\u{30FF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{20000}'..='\u{2A6DF}')
+}
+
+/// 分词：字母数字连续片段作为一个词并转小写，中日韩文字按相邻两字切分（单字片段保留单字）
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if is_cjk(c) {
+            let mut run = Vec::new();
+            while let Some(&(offset, c)) = chars.peek().filter(|(_, c)| is_cjk(*c)) {
+                run.push((offset, c));
+                chars.next();
+            }
+            if run.len() == 1 {
+                let (offset, c) = run[0];
+                tokens.push(Token { term: c.to_string(), start: offset, end: offset + c.len_utf8() });
+                continue;
+            }
+            for pair in run.windows(2) {
+                let (offset, first) = pair[0];
+                let (second_offset, second) = pair[1];
+                tokens.push(Token {
+                    term: format!("{}{}", first, second),
+                    start: offset,
+                    end: second_offset + second.len_utf8(),
+                });
+            }
+        } else if c.is_alphanumeric() {
+            let mut end = start;
+            let mut term = String::new();
+            while let Some(&(offset, c)) = chars.peek().filter(|(_, c)| c.is_alphanumeric() && !is_cjk(*c)) {
+                term.extend(c.to_lowercase());
+                end = offset + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token { term, start, end });
+        } else {
+            chars.next();
+        }
+    }
+    tokens
+}
+
+/// 查询中的一个子句：引号中的短语或不带引号的单个词，子句内的词必须相邻出现
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryClause {
+    pub terms: Vec<String>,
+}
+
+impl QueryClause {
+    fn single_cjk_char(&self) -> Option<char> {
+        let [term] = self.terms.as_slice() else {
+            return None;
+        };
+        let mut chars = term.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if is_cjk(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// 解析查询：`"..."` 为短语，其余按空白切分，所有子句都需命中
+pub fn parse_query(query: &str) -> Vec<QueryClause> {
+    let mut clauses = Vec::new();
+    for (i, part) in query.split('"').enumerate() {
+        let pieces: Vec<&str> = if i % 2 == 1 { vec![part] } else { part.split_whitespace().collect() };
+        for piece in pieces {
+            let terms: Vec<String> = tokenize(piece).into_iter().map(|token| token.term).collect();
+            if !terms.is_empty() {
+                clauses.push(QueryClause { terms });
+            }
+        }
+    }
+    clauses
+}
+
+/// 过滤条件，日期按本地时间计算且包含边界
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub model: Option<String>,
+    pub session_id: Option<String>,
+    pub role: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// 搜索结果，`snippet` 已做 HTML 转义，命中部分用 `<mark>` 标出
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub session_title: String,
+    pub message_id: String,
+    pub role: String,
+    pub model: Option<String>,
+    pub created_at: i64,
+    pub score: f64,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedMessage {
+    message_id: String,
+    role: String,
+    model: Option<String>,
+    created_at: i64,
+    content: String,
+    tokens: Vec<Token>,
+}
+
+/// 单个会话的倒排索引，会话更新后整体重建
+#[derive(Debug, Clone)]
+struct SessionIndex {
+    updated_at: i64,
+    title: String,
+    messages: Vec<IndexedMessage>,
+    /// 词 -> (消息序号, 该词在消息中的词序号列表)
+    postings: HashMap<String, Vec<(usize, Vec<usize>)>>,
+}
+
+impl SessionIndex {
+    fn build(session: &Session) -> Self {
+        let mut postings: HashMap<String, Vec<(usize, Vec<usize>)>> = HashMap::new();
+        let messages: Vec<IndexedMessage> = session
+            .messages
+            .iter()
+            .map(|message| IndexedMessage {
+                message_id: message.id.clone(),
+                role: message.role.clone(),
+                model: message.model.clone(),
+                created_at: message.created_at,
+                content: message.content.clone(),
+                tokens: tokenize(&message.content),
+            })
+            .collect();
+
+        for (doc, message) in messages.iter().enumerate() {
+            for (token_index, token) in message.tokens.iter().enumerate() {
+                let entries = postings.entry(token.term.clone()).or_default();
+                match entries.last_mut() {
+                    Some((last_doc, indexes)) if *last_doc == doc => indexes.push(token_index),
+                    _ => entries.push((doc, vec![token_index])),
+                }
+            }
+        }
+
+        SessionIndex {
+            updated_at: session.updated_at,
+            title: session.title.clone(),
+            messages,
+            postings,
+        }
+    }
+
+    fn token_indexes(&self, term: &str, doc: usize) -> &[usize] {
+        self.postings
+            .get(term)
+            .and_then(|entries| entries.iter().find(|(d, _)| *d == doc))
+            .map(|(_, indexes)| indexes.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// 子句在消息中的全部命中，返回原文中的字节范围
+    fn clause_matches(&self, clause: &QueryClause, doc: usize) -> Vec<(usize, usize)> {
+        let tokens = &self.messages[doc].tokens;
+        if let Some(c) = clause.single_cjk_char() {
+            // 单个汉字不会单独成词，在包含该字的双字词中查找
+            let mut ranges: Vec<(usize, usize)> = tokens
+                .iter()
+                .filter(|token| token.term.contains(c))
+                .map(|token| {
+                    if token.term.starts_with(c) {
+                        (token.start, token.start + c.len_utf8())
+                    } else {
+                        (token.end - c.len_utf8(), token.end)
+                    }
+                })
+                .collect();
+            ranges.dedup();
+            return ranges;
+        }
+
+        self.token_indexes(&clause.terms[0], doc)
+            .iter()
+            .copied()
+            .filter(|&start| {
+                clause.terms.iter().enumerate().skip(1).all(|(offset, term)| {
+                    tokens.get(start + offset).is_some_and(|token| &token.term == term)
+                })
+            })
+            .map(|start| (tokens[start].start, tokens[start + clause.terms.len() - 1].end))
+            .collect()
+    }
+
+    /// 包含子句第一个词的消息
+    fn clause_docs(&self, clause: &QueryClause) -> Vec<usize> {
+        if let Some(c) = clause.single_cjk_char() {
+            return (0..self.messages.len())
+                .filter(|&doc| self.messages[doc].tokens.iter().any(|token| token.term.contains(c)))
+                .collect();
+        }
+        self.postings
+            .get(&clause.terms[0])
+            .map(|entries| entries.iter().map(|(doc, _)| *doc).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn message_date(created_at: i64) -> Option<NaiveDate> {
+    Local.timestamp_millis_opt(created_at).single().map(|time| time.date_naive())
+}
+
+fn matches_filters(filters: &SearchFilters, session_id: &str, message: &IndexedMessage) -> bool {
+    if filters.session_id.as_deref().is_some_and(|id| id != session_id) {
+        return false;
+    }
+    if filters.role.as_deref().is_some_and(|role| role != message.role) {
+        return false;
+    }
+    if filters.model.is_some() && filters.model != message.model {
+        return false;
+    }
+    if filters.from.is_some() || filters.to.is_some() {
+        let Some(date) = message_date(message.created_at) else {
+            return false;
+        };
+        if filters.from.is_some_and(|from| date < from) || filters.to.is_some_and(|to| date > to) {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// 截取第一个命中附近的文本，并用 `<mark>` 标出窗口内的所有命中
+pub fn build_snippet(content: &str, ranges: &[(usize, usize)]) -> String {
+    let mut ranges = ranges.to_vec();
+    ranges.sort();
+    // 合并重叠的命中范围
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let Some(&(first_start, first_end)) = merged.first() else {
+        return escape_html(&content.chars().take(SNIPPET_CONTEXT_CHARS * 2).collect::<String>());
+    };
+
+    let window_start = content[..first_start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS - 1)
+        .map(|(offset, _)| offset)
+        .unwrap_or(0);
+    let window_end = content[first_end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map(|(offset, _)| first_end + offset)
+        .unwrap_or(content.len());
+
+    let mut snippet = String::new();
+    if window_start > 0 {
+        snippet.push('…');
+    }
+    let mut cursor = window_start;
+    for (start, end) in merged.into_iter().filter(|(start, _)| *start < window_end) {
+        let end = end.min(window_end);
+        snippet.push_str(&escape_html(&content[cursor..start]));
+        snippet.push_str("<mark>");
+        snippet.push_str(&escape_html(&content[start..end]));
+        snippet.push_str("</mark>");
+        cursor = end;
+    }
+    snippet.push_str(&escape_html(&content[cursor..window_end]));
+    if window_end < content.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// 全部会话的全文索引，按会话更新时间增量重建
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    sessions: HashMap<String, SessionIndex>,
+}
+
+lazy_static! {
+    pub static ref SEARCH_INDEX: Mutex<SearchIndex> = Mutex::new(SearchIndex::default());
+}
+
+impl SearchIndex {
+    /// 与当前会话同步：重建有更新的会话，删除已不存在的会话
+    pub fn sync(&mut self, sessions: &[Session]) {
+        self.sessions.retain(|id, _| sessions.iter().any(|session| &session.id == id));
+        for session in sessions {
+            let stale = self
+                .sessions
+                .get(&session.id)
+                .is_none_or(|index| index.updated_at != session.updated_at);
+            if stale {
+                self.sessions.insert(session.id.clone(), SessionIndex::build(session));
+            }
+        }
+    }
+
+    /// 搜索消息，按相关度排序，相关度相同时较新的在前
+    pub fn search(&self, query: &str, filters: &SearchFilters, limit: usize) -> Vec<SearchHit> {
+        let clauses = parse_query(query);
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+
+        let total_docs: usize = self.sessions.values().map(|index| index.messages.len()).sum();
+        let mut hits = Vec::new();
+        for (session_id, index) in &self.sessions {
+            for doc in index.clause_docs(&clauses[0]) {
+                let message = &index.messages[doc];
+                if !matches_filters(filters, session_id, message) {
+                    continue;
+                }
+
+                let mut score = 0.0;
+                let mut ranges = Vec::new();
+                let all_matched = clauses.iter().all(|clause| {
+                    let matches = index.clause_matches(clause, doc);
+                    if matches.is_empty() {
+                        return false;
+                    }
+                    let doc_freq = self.document_frequency(clause).max(1);
+                    let idf = ((total_docs as f64 + 1.0) / doc_freq as f64).ln() + 1.0;
+                    score += (1.0 + (matches.len() as f64).ln()) * idf * clause.terms.len() as f64;
+                    ranges.extend(matches);
+                    true
+                });
+                if !all_matched {
+                    continue;
+                }
+
+                hits.push(SearchHit {
+                    session_id: session_id.clone(),
+                    session_title: index.title.clone(),
+                    message_id: message.message_id.clone(),
+                    role: message.role.clone(),
+                    model: message.model.clone(),
+                    created_at: message.created_at,
+                    score,
+                    snippet: build_snippet(&message.content, &ranges),
+                });
+            }
+        }
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.created_at.cmp(&a.created_at)));
+        hits.truncate(limit);
+        hits
+    }
+
+    fn document_frequency(&self, clause: &QueryClause) -> usize {
+        self.sessions.values().map(|index| index.clause_docs(clause).len()).sum()
+    }
+}
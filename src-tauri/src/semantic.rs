@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use crate::cache::{get_cache_dir, read_json_file, write_json_file};
+use crate::persist::FileLock;
+use crate::provider::DEFAULT_EMBEDDING_BATCH_SIZE;
+use crate::rag::cosine_similarity;
+use crate::session::{now_millis, Session, StoredMessage};
+
+const MESSAGE_VECTORS_FILE: &str = "message_vectors.json";
+
+/// 单条消息参与嵌入的最大字符数，超出部分截断
+const MAX_EMBEDDED_CHARS: usize = 4000;
+
+/// 语义搜索默认返回的问答数
+pub const DEFAULT_SEMANTIC_TOP_K: usize = 10;
+
+lazy_static! {
+    // 串行化向量库的读写，避免后台更新与手动重建互相覆盖
+    static ref MESSAGE_VECTORS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// 向量库的锁，同时在进程内和多个实例之间串行化读写，释放时自动解锁
+pub struct MessageVectorsLock {
+    _guard: tokio::sync::MutexGuard<'static, ()>,
+    _file: FileLock,
+}
+
+/// 锁定向量库，持有期间读取、更新并写回向量库
+pub async fn lock_message_vectors() -> Result<MessageVectorsLock, String> {
+    let guard = MESSAGE_VECTORS_LOCK.lock().await;
+    let file = FileLock::acquire_async(&message_vectors_file()).await?;
+    Ok(MessageVectorsLock { _guard: guard, _file: file })
+}
+
+/// 一条消息的向量
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MessageVector {
+    pub session_id: String,
+    /// 消息内容的哈希，内容变化（如续写）后重新生成向量
+    pub content_hash: u64,
+    pub embedding: Vec<f32>,
+}
+
+/// 历史消息的向量库，`model` 为空时表示未启用语义搜索
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MessageVectorStore {
+    /// 生成向量使用的嵌入模型，更换模型后需要全量重建
+    pub model: String,
+    pub updated_at: i64,
+    /// 键为消息 ID
+    #[serde(default)]
+    pub messages: BTreeMap<String, MessageVector>,
+}
+
+/// 向量库的状态
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MessageVectorSummary {
+    pub model: String,
+    pub updated_at: i64,
+    pub message_count: usize,
+}
+
+/// 一次增量更新的结果
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MessageVectorReport {
+    /// 本次新生成的向量数
+    pub embedded: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl MessageVectorReport {
+    pub fn has_changes(&self) -> bool {
+        self.embedded > 0 || self.removed > 0
+    }
+}
+
+/// 语义搜索命中的一轮问答
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SemanticHit {
+    pub session_id: String,
+    pub session_title: String,
+    /// 与查询最接近的消息
+    pub message_id: String,
+    pub score: f32,
+    pub created_at: i64,
+    pub question: Option<String>,
+    pub answer: Option<String>,
+}
+
+impl MessageVectorStore {
+    pub fn new(model: &str) -> Self {
+        MessageVectorStore {
+            model: model.to_string(),
+            updated_at: now_millis(),
+            messages: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.model.is_empty()
+    }
+
+    /// 更换嵌入模型，已有向量全部作废
+    pub fn set_model(&mut self, model: &str) {
+        if self.model != model {
+            self.model = model.to_string();
+            self.messages.clear();
+        }
+    }
+
+    pub fn summary(&self) -> MessageVectorSummary {
+        MessageVectorSummary {
+            model: self.model.clone(),
+            updated_at: self.updated_at,
+            message_count: self.messages.len(),
+        }
+    }
+
+    /// 删除不在 `session_ids` 中的会话的向量，返回删除的条数
+    pub fn retain_sessions(&mut self, session_ids: &HashSet<&str>) -> usize {
+        let before = self.messages.len();
+        self.messages.retain(|_, vector| session_ids.contains(vector.session_id.as_str()));
+        before - self.messages.len()
+    }
+
+    /// 按余弦相似度返回与查询最接近的 `top_k` 轮问答，同一轮问答只保留得分最高的消息，忽略得分不大于 0 的消息
+    pub fn search(&self, sessions: &[Session], query: &[f32], top_k: usize) -> Vec<SemanticHit> {
+        let mut hits: Vec<SemanticHit> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut scored: Vec<(f32, &Session, &StoredMessage)> = sessions
+            .iter()
+            .flat_map(|session| {
+                session.messages.iter().filter_map(move |message| {
+                    let vector = self.messages.get(&message.id)?;
+                    let score = cosine_similarity(query, &vector.embedding);
+                    (score > 0.0).then_some((score, session, message))
+                })
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (score, session, message) in scored {
+            if hits.len() >= top_k {
+                break;
+            }
+            let (question, answer) = exchange_of(session, message);
+            let key = (session.id.as_str(), question.or(answer).map(|m| m.id.as_str()));
+            if !seen.insert(key) {
+                continue;
+            }
+            hits.push(SemanticHit {
+                session_id: session.id.clone(),
+                session_title: session.title.clone(),
+                message_id: message.id.clone(),
+                score,
+                created_at: message.created_at,
+                question: question.map(|m| m.content.clone()),
+                answer: answer.map(|m| m.content.clone()),
+            });
+        }
+        hits
+    }
+}
+
+/// 找出消息所在的一轮问答：用户消息取最近的一个回答，助手消息取其父消息作为问题
+fn exchange_of<'a>(session: &'a Session, message: &'a StoredMessage) -> (Option<&'a StoredMessage>, Option<&'a StoredMessage>) {
+    if message.role == "user" {
+        let answer = session
+            .children(Some(&message.id))
+            .into_iter()
+            .rfind(|child| child.role == "assistant");
+        return (Some(message), answer);
+    }
+    let question = message
+        .parent_id
+        .as_deref()
+        .and_then(|id| session.find_message(id))
+        .filter(|parent| parent.role == "user");
+    (question, Some(message))
+}
+
+/// 参与嵌入的文本，过长时截断
+fn embedded_text(content: &str) -> String {
+    content.trim().chars().take(MAX_EMBEDDED_CHARS).collect()
+}
+
+/// FNV-1a 哈希，结果写入磁盘，需要在不同版本间保持稳定
+pub fn content_hash(content: &str) -> u64 {
+    content.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// 增量更新 `sessions` 中用户和助手消息的向量：为新增或内容变化的消息生成向量，删除这些会话中已不存在的消息。
+///
+/// 不在 `sessions` 中的会话不受影响；`embed` 接收一批文本，按相同顺序返回向量。
+pub async fn update_message_vectors<F, Fut>(
+    store: &mut MessageVectorStore,
+    sessions: &[Session],
+    embed: F,
+) -> Result<MessageVectorReport, String>
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<Vec<f32>>, String>>,
+{
+    let mut report = MessageVectorReport::default();
+    let mut pending = Vec::new();
+    for session in sessions {
+        let current: HashSet<&str> = session.messages.iter().map(|m| m.id.as_str()).collect();
+        let before = store.messages.len();
+        store
+            .messages
+            .retain(|id, vector| vector.session_id != session.id || current.contains(id.as_str()));
+        report.removed += before - store.messages.len();
+
+        for message in &session.messages {
+            if !matches!(message.role.as_str(), "user" | "assistant") || message.content.trim().is_empty() {
+                continue;
+            }
+            let hash = content_hash(&message.content);
+            if store.messages.get(&message.id).is_some_and(|vector| vector.content_hash == hash) {
+                report.unchanged += 1;
+                continue;
+            }
+            pending.push((session.id.clone(), message.id.clone(), hash, embedded_text(&message.content)));
+        }
+    }
+
+    for batch in pending.chunks(DEFAULT_EMBEDDING_BATCH_SIZE) {
+        let texts: Vec<String> = batch.iter().map(|(_, _, _, text)| text.clone()).collect();
+        let vectors = embed(texts).await?;
+        if vectors.len() != batch.len() {
+            return Err(format!("嵌入接口返回的向量数量不匹配: 期望 {}，实际 {}", batch.len(), vectors.len()));
+        }
+        for ((session_id, message_id, content_hash, _), embedding) in batch.iter().zip(vectors) {
+            store.messages.insert(
+                message_id.clone(),
+                MessageVector {
+                    session_id: session_id.clone(),
+                    content_hash: *content_hash,
+                    embedding,
+                },
+            );
+        }
+        report.embedded += batch.len();
+    }
+
+    store.updated_at = now_millis();
+    Ok(report)
+}
+
+pub fn message_vectors_file() -> PathBuf {
+    get_cache_dir().join(MESSAGE_VECTORS_FILE)
+}
+
+pub fn load_message_vectors(path: &Path) -> Result<MessageVectorStore, String> {
+    read_json_file(path)
+}
+
+pub fn save_message_vectors(path: &Path, store: &MessageVectorStore) -> Result<(), String> {
+    write_json_file(path, store)
+}
@@ -0,0 +1,409 @@
+use std::path::Path;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use crate::cache::read_json_file;
+use crate::attachment::{attachment_message, DocumentAttachment, ImageAttachment};
+use crate::chat::{
+    generate_id, generate_message_id, ChatMessage, ChatResult, ChatTimings, FinishReason, GenerationParams, Usage,
+};
+use crate::storage::with_storage;
+
+/// 新建会话的默认标题，仍为默认标题的会话会在第一轮问答后自动生成标题
+pub const DEFAULT_SESSION_TITLE: &str = "新对话";
+
+/// 会话中保存的一条消息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub id: String,
+    /// 父消息 ID，为空表示会话的第一条消息；编辑问题或重新生成回答会在同一父消息下产生新的分支
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub role: String,
+    pub content: String,
+    /// 用户消息附带的图片
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageAttachment>,
+    /// 用户消息附带的文档，发送给模型时拼接在消息内容之前
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<DocumentAttachment>,
+    /// 推理模型的思考过程，仅用于展示，不进入发送给模型的历史
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// 毫秒时间戳
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// 生成回答的耗时，重新生成时用于对比不同模型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<ChatTimings>,
+    /// 服务端返回的响应 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+}
+
+/// 会话较早部分的滚动摘要，发送历史时替换被摘要的消息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContextSummary {
+    pub content: String,
+    /// 摘要覆盖到的最后一条消息，该消息及之前的消息不再发送给模型
+    pub until_message_id: String,
+    pub model: String,
+    pub created_at: i64,
+}
+
+/// 持久化的会话：会话级配置和消息记录
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 会话使用的人设预设名称
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// 会话级生成参数，覆盖配置中的默认值
+    #[serde(default)]
+    pub params: GenerationParams,
+    /// 是否允许模型调用工具
+    #[serde(default)]
+    pub tools_enabled: bool,
+    /// 每轮对话的工具调用步数上限，未设置时使用默认值
+    #[serde(default)]
+    pub max_tool_steps: Option<u32>,
+    /// 关联的目录索引 ID，发送消息时从中检索参考内容
+    #[serde(default)]
+    pub index_id: Option<String>,
+    /// 导入来源的唯一标识，如 `chatgpt:<会话 ID>`，重复导入时据此去重
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_source: Option<String>,
+    /// 当前分支最后一条消息的 ID，为空时取最后写入的消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_leaf: Option<String>,
+    /// 由 `summarize_session` 生成的摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_summary: Option<ContextSummary>,
+    /// 所在文件夹 ID，为空表示在根目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 置顶的会话在列表中排在最前
+    #[serde(default)]
+    pub pinned: bool,
+    /// 归档的会话默认不出现在列表中
+    #[serde(default)]
+    pub archived: bool,
+    /// 移入回收站的时间，超过保留天数后被永久删除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<i64>,
+    /// 所有分支的消息，按写入顺序保存
+    #[serde(default)]
+    pub messages: Vec<StoredMessage>,
+}
+
+/// 当前分支上的一条消息及其所有兄弟消息的 ID（含自身），用于展示和切换分支
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BranchMessage {
+    #[serde(flatten)]
+    pub message: StoredMessage,
+    pub sibling_ids: Vec<String>,
+}
+
+/// 会话列表中展示的摘要信息
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub id: String,
+    pub title: String,
+    pub updated_at: i64,
+    pub message_count: usize,
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+}
+
+pub fn now_millis() -> i64 {
+    Local::now().timestamp_millis()
+}
+
+impl Session {
+    pub fn new(title: &str, profile: Option<String>) -> Self {
+        let now = now_millis();
+        Session {
+            id: generate_id("session"),
+            title: title.to_string(),
+            created_at: now,
+            updated_at: now,
+            profile,
+            ..Default::default()
+        }
+    }
+
+    pub fn find_message(&self, id: &str) -> Option<&StoredMessage> {
+        self.messages.iter().find(|message| message.id == id)
+    }
+
+    /// 从第一条消息到 `id` 的路径，消息不存在时为空
+    pub fn path_to(&self, id: &str) -> Vec<&StoredMessage> {
+        let mut path = Vec::new();
+        let mut current = self.find_message(id);
+        while let Some(message) = current {
+            // 防止损坏的数据中出现环
+            if path.len() > self.messages.len() {
+                break;
+            }
+            path.push(message);
+            current = message.parent_id.as_deref().and_then(|parent| self.find_message(parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// 当前分支最后一条消息的 ID
+    pub fn active_leaf_id(&self) -> Option<&str> {
+        self.active_leaf
+            .as_deref()
+            .filter(|id| self.find_message(id).is_some())
+            .or_else(|| self.messages.last().map(|message| message.id.as_str()))
+    }
+
+    /// 当前分支上的消息
+    pub fn active_path(&self) -> Vec<&StoredMessage> {
+        self.active_leaf_id().map(|id| self.path_to(id)).unwrap_or_default()
+    }
+
+    /// `parent_id` 下的直接子消息，按写入顺序排列；`parent_id` 为空时返回第一层消息
+    pub fn children(&self, parent_id: Option<&str>) -> Vec<&StoredMessage> {
+        self.messages
+            .iter()
+            .filter(|message| message.parent_id.as_deref() == parent_id)
+            .collect()
+    }
+
+    /// 当前分支上的消息及各自的兄弟消息
+    pub fn active_branch(&self) -> Vec<BranchMessage> {
+        self.active_path()
+            .into_iter()
+            .map(|message| BranchMessage {
+                sibling_ids: self
+                    .children(message.parent_id.as_deref())
+                    .into_iter()
+                    .map(|sibling| sibling.id.clone())
+                    .collect(),
+                message: message.clone(),
+            })
+            .collect()
+    }
+
+    /// 切换到包含 `message_id` 的分支，从该消息沿最近写入的子消息走到叶子
+    pub fn switch_branch(&mut self, message_id: &str) -> bool {
+        if self.find_message(message_id).is_none() {
+            return false;
+        }
+        let mut leaf = message_id.to_string();
+        while let Some(child) = self.children(Some(&leaf)).last() {
+            leaf = child.id.clone();
+        }
+        self.active_leaf = Some(leaf);
+        self.updated_at = now_millis();
+        true
+    }
+
+    /// 为旧版本保存的线性会话补上父消息 ID
+    pub fn migrate_linear(&mut self) {
+        if self.messages.len() < 2 || self.messages.iter().any(|message| message.parent_id.is_some()) {
+            return;
+        }
+        for i in 1..self.messages.len() {
+            self.messages[i].parent_id = Some(self.messages[i - 1].id.clone());
+        }
+    }
+
+    /// 转换为发送给模型的历史消息，只包含当前分支，不包含思考过程
+    pub fn history(&self) -> Vec<ChatMessage> {
+        self.context_messages(self.active_path())
+    }
+
+    /// 从第一条消息到 `message_id` 之前（不含）的历史消息，用于续写或重新生成该消息
+    pub fn history_before(&self, message_id: &str) -> Vec<ChatMessage> {
+        let mut path = self.path_to(message_id);
+        path.pop();
+        self.context_messages(path)
+    }
+
+    /// 摘要覆盖的消息在路径上时，以一条系统消息形式的摘要替换这些消息
+    fn context_messages(&self, path: Vec<&StoredMessage>) -> Vec<ChatMessage> {
+        let Some(summary) = &self.context_summary else {
+            return to_chat_messages(path);
+        };
+        match path.iter().position(|message| message.id == summary.until_message_id) {
+            Some(position) => {
+                let mut messages = vec![ChatMessage::new("system", &format!("以下是之前对话的摘要：\n{}", summary.content))];
+                messages.extend(to_chat_messages(path[position + 1..].to_vec()));
+                messages
+            }
+            None => to_chat_messages(path),
+        }
+    }
+
+    /// 在当前分支末尾追加一轮问答
+    pub fn push_exchange(&mut self, user_content: &str, result: &ChatResult) {
+        self.push_exchange_with_attachments(user_content, vec![], vec![], result);
+    }
+
+    /// 在当前分支末尾追加一轮用户消息附带图片或文档的问答
+    pub fn push_exchange_with_attachments(
+        &mut self,
+        user_content: &str,
+        images: Vec<ImageAttachment>,
+        documents: Vec<DocumentAttachment>,
+        result: &ChatResult,
+    ) {
+        let parent_id = self.active_leaf_id().map(str::to_string);
+        self.push_exchange_under(parent_id, user_content, images, documents, result);
+    }
+
+    /// 在 `parent_id` 下追加一轮问答并切换到该分支，编辑之前的问题时与原问题成为兄弟消息
+    pub fn push_exchange_under(
+        &mut self,
+        parent_id: Option<String>,
+        user_content: &str,
+        images: Vec<ImageAttachment>,
+        documents: Vec<DocumentAttachment>,
+        result: &ChatResult,
+    ) {
+        let now = now_millis();
+        let user_id = generate_message_id();
+        self.messages.push(StoredMessage {
+            id: user_id.clone(),
+            parent_id,
+            role: "user".to_string(),
+            content: user_content.to_string(),
+            images,
+            documents,
+            reasoning: None,
+            created_at: now,
+            model: None,
+            finish_reason: None,
+            usage: None,
+            timings: None,
+            response_id: None,
+        });
+        self.push_answer(&user_id, result);
+    }
+
+    /// 为用户消息追加一个回答并切换到该分支，重新生成时与原回答成为兄弟消息
+    pub fn push_answer(&mut self, user_message_id: &str, result: &ChatResult) {
+        let now = now_millis();
+        self.messages.push(StoredMessage {
+            id: result.message_id.clone(),
+            parent_id: Some(user_message_id.to_string()),
+            role: "assistant".to_string(),
+            content: result.content.clone(),
+            images: vec![],
+            documents: vec![],
+            reasoning: result.reasoning.clone(),
+            created_at: now,
+            model: Some(result.model.clone()),
+            finish_reason: result.finish_reason,
+            usage: result.usage.clone(),
+            timings: Some(result.timings.clone()),
+            response_id: result.response_id.clone(),
+        });
+        self.active_leaf = Some(result.message_id.clone());
+        self.updated_at = now;
+    }
+
+    /// 用续写后的结果更新同一条助手消息
+    pub fn update_message(&mut self, result: &ChatResult) -> bool {
+        let Some(message) = self.messages.iter_mut().find(|m| m.id == result.message_id) else {
+            return false;
+        };
+        message.content = result.content.clone();
+        message.reasoning = result.reasoning.clone();
+        message.finish_reason = result.finish_reason;
+        message.usage = result.usage.clone();
+        message.timings = Some(result.timings.clone());
+        self.updated_at = now_millis();
+        true
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            updated_at: self.updated_at,
+            message_count: self.messages.len(),
+            folder_id: self.folder_id.clone(),
+            tags: self.tags.clone(),
+            pinned: self.pinned,
+            archived: self.archived,
+            deleted_at: self.deleted_at,
+        }
+    }
+
+    /// 是否在回收站中
+    pub fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+fn to_chat_messages(messages: Vec<&StoredMessage>) -> Vec<ChatMessage> {
+    messages
+        .into_iter()
+        .map(|message| attachment_message(&message.role, &message.content, &message.images, &message.documents))
+        .collect()
+}
+
+/// 读取旧版本保存的会话 JSON 文件，导入数据库时使用
+pub fn read_session_file(path: &Path) -> Result<Session, String> {
+    let mut session: Session = read_json_file(path)?;
+    session.migrate_linear();
+    Ok(session)
+}
+
+pub fn load_session(id: &str) -> Result<Session, String> {
+    with_storage(|storage| storage.load_session(id))
+}
+
+pub fn save_session(session: &Session) -> Result<(), String> {
+    with_storage(|storage| storage.save_session(session))
+}
+
+/// 在一个事务中重新读取会话、修改并保存，不会覆盖读取之后其他操作对会话的修改。
+///
+/// `f` 返回错误时不保存。
+pub fn update_session<R>(id: &str, f: impl FnOnce(&mut Session) -> Result<R, String>) -> Result<R, String> {
+    let mut f = Some(f);
+    let mut output = None;
+    with_storage(|storage| {
+        storage.transaction(&mut |storage| {
+            let mut session = storage.load_session(id)?;
+            let f = f.take().ok_or("会话更新只能执行一次")?;
+            output = Some(f(&mut session)?);
+            storage.save_session(&session)
+        })
+    })?;
+    output.ok_or_else(|| "会话更新未执行".to_string())
+}
+
+pub fn delete_session(id: &str) -> Result<(), String> {
+    with_storage(|storage| storage.delete_session(id))
+}
+
+/// 列出所有会话，按最近更新时间倒序
+pub fn list_sessions() -> Result<Vec<Session>, String> {
+    with_storage(|storage| storage.list_sessions())
+}
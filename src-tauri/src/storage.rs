@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::builtin_tools::APPROVED_DIRS_KEY;
+use crate::cache::{get_cache_dir, API_KEY_SETTING, API_URL_SETTING};
+use crate::mcp::MCP_SERVERS_KEY;
+use crate::models::ModelFrequency;
+use crate::organize::{FOLDERS_KEY, TRASH_CONFIG_KEY};
+use crate::persona::PERSONAS_KEY;
+use crate::profile::PROFILES_KEY;
+use crate::session::{read_session_file, Session, StoredMessage};
+use crate::summary::SUMMARY_CONFIG_KEY;
+use crate::template::TEMPLATES_KEY;
+use crate::usage::{load_records, UsageRecord, BUDGETS_KEY, PRICES_KEY};
+
+const DATABASE_FILE: &str = "chat-ai.db";
+
+/// 等待其他实例释放数据库写锁的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 导入过旧版本 JSON 文件后写入的设置项
+const LEGACY_IMPORTED_KEY: &str = "legacy_files_imported";
+
+/// 数据库迁移，按顺序执行；已执行到第几个记录在 `PRAGMA user_version` 中，只能追加不能修改
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE sessions (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE messages (
+        session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        parent_id TEXT,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        model TEXT,
+        data TEXT NOT NULL,
+        PRIMARY KEY (session_id, id)
+    );
+    CREATE INDEX messages_by_position ON messages(session_id, position);
+    CREATE TABLE models (
+        name TEXT PRIMARY KEY,
+        frequency INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE usage_records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        message_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        model TEXT NOT NULL,
+        profile TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX usage_by_timestamp ON usage_records(timestamp);
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+];
+
+/// 持久化存储：会话和消息、模型目录、用量记录和设置
+pub trait Storage: Send {
+    fn load_session(&self, id: &str) -> Result<Session, String>;
+    /// 保存会话及其全部消息，已存在时整体替换
+    fn save_session(&mut self, session: &Session) -> Result<(), String>;
+    fn delete_session(&mut self, id: &str) -> Result<(), String>;
+    /// 所有会话，按最近更新时间倒序
+    fn list_sessions(&self) -> Result<Vec<Session>, String>;
+
+    /// 模型及使用次数，请求失败的模型为 -1
+    fn load_models(&self) -> Result<HashMap<String, i32>, String>;
+    /// 写入模型使用次数，不在 `frequencies` 中的模型保持不变
+    fn save_models(&mut self, frequencies: &HashMap<String, i32>) -> Result<(), String>;
+
+    fn append_usage(&mut self, record: &UsageRecord) -> Result<(), String>;
+    /// 所有用量记录，按记账顺序排列
+    fn load_usage(&self) -> Result<Vec<UsageRecord>, String>;
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>, String>;
+    fn set_setting(&mut self, key: &str, value: &str) -> Result<(), String>;
+    fn delete_setting(&mut self, key: &str) -> Result<(), String>;
+
+    /// 在一个事务中执行 `f`，`f` 返回错误时回滚其中的全部写入
+    fn transaction(&mut self, f: &mut dyn FnMut(&mut dyn Storage) -> Result<(), String>) -> Result<(), String>;
+}
+
+/// 读取 JSON 格式的设置项，未设置时返回默认值
+pub fn read_setting<T: Default + DeserializeOwned>(storage: &dyn Storage, key: &str) -> Result<T, String> {
+    match storage.get_setting(key)? {
+        Some(value) => serde_json::from_str(&value).map_err(|e| format!("解析设置 {} 失败: {}", key, e)),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn write_setting<T: Serialize>(storage: &mut dyn Storage, key: &str, value: &T) -> Result<(), String> {
+    let value = serde_json::to_string(value).map_err(|e| format!("序列化设置 {} 失败: {}", key, e))?;
+    storage.set_setting(key, &value)
+}
+
+/// 在一个事务中读取、修改并写回设置项，多个实例同时修改时不会互相覆盖。
+///
+/// `f` 返回错误时不写回。
+pub fn update_setting<T, R, F>(storage: &mut dyn Storage, key: &str, f: F) -> Result<R, String>
+where
+    T: Default + DeserializeOwned + Serialize,
+    F: FnOnce(&mut T) -> Result<R, String>,
+{
+    let mut f = Some(f);
+    let mut output = None;
+    storage.transaction(&mut |storage| {
+        let mut value: T = read_setting(storage, key)?;
+        let f = f.take().ok_or("设置更新只能执行一次")?;
+        output = Some(f(&mut value)?);
+        write_setting(storage, key, &value)
+    })?;
+    output.ok_or_else(|| "设置更新未执行".to_string())
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+    format!("数据库操作失败: {}", e)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("序列化失败: {}", e))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|e| format!("解析数据库中的数据失败: {}", e))
+}
+
+/// 基于 SQLite 的存储
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    /// 打开数据库文件，不存在时创建，并执行未执行过的迁移
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("打开数据库 {} 失败: {}", path.display(), e))?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(db_error)?;
+        // 其他实例正在写入时等待，而不是立即返回“数据库被锁定”
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(db_error)?;
+        Self::init(conn)
+    }
+
+    /// 内存数据库，用于测试
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.pragma_update(None, "foreign_keys", true).map_err(db_error)?;
+        let mut storage = SqliteStorage { conn };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    /// 已执行的迁移数
+    pub fn schema_version(&self) -> Result<usize, String> {
+        self.conn
+            .pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0))
+            .map(|version| version as usize)
+            .map_err(db_error)
+    }
+
+    fn migrate(&mut self) -> Result<(), String> {
+        let version = self.schema_version()?;
+        if version > MIGRATIONS.len() {
+            return Err(format!("数据库版本 {} 高于当前程序支持的版本 {}", version, MIGRATIONS.len()));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.savepoint().map_err(db_error)?;
+            tx.execute_batch(migration).map_err(|e| format!("执行第 {} 个数据库迁移失败: {}", index + 1, e))?;
+            tx.pragma_update(None, "user_version", (index + 1) as i64).map_err(db_error)?;
+            tx.commit().map_err(db_error)?;
+        }
+        Ok(())
+    }
+
+    fn load_messages(&self, session_id: &str) -> Result<Vec<StoredMessage>, String> {
+        let mut statement = self
+            .conn
+            .prepare_cached("SELECT data FROM messages WHERE session_id = ?1 ORDER BY position")
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([session_id], |row| row.get::<_, String>(0))
+            .map_err(db_error)?;
+        rows.map(|data| from_json(&data.map_err(db_error)?)).collect()
+    }
+
+    fn session_from_row(&self, data: &str) -> Result<Session, String> {
+        let mut session: Session = from_json(data)?;
+        session.messages = self.load_messages(&session.id)?;
+        session.migrate_linear();
+        Ok(session)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_session(&self, id: &str) -> Result<Session, String> {
+        let data: Option<String> = self
+            .conn
+            .query_row("SELECT data FROM sessions WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+            .map_err(db_error)?;
+        match data {
+            Some(data) => self.session_from_row(&data),
+            None => Err(format!("会话不存在: {}", id)),
+        }
+    }
+
+    fn save_session(&mut self, session: &Session) -> Result<(), String> {
+        // 会话配置和消息分表保存，会话行中不重复保存消息
+        let data = to_json(&Session {
+            messages: Vec::new(),
+            ..session.clone()
+        })?;
+        let tx = self.conn.savepoint().map_err(db_error)?;
+        tx.execute(
+            "INSERT INTO sessions (id, title, created_at, updated_at, data) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(id) DO UPDATE SET title = excluded.title, created_at = excluded.created_at,
+                 updated_at = excluded.updated_at, data = excluded.data",
+            params![session.id, session.title, session.created_at, session.updated_at, data],
+        )
+        .map_err(db_error)?;
+        tx.execute("DELETE FROM messages WHERE session_id = ?1", [&session.id]).map_err(db_error)?;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO messages (session_id, id, position, parent_id, role, content, created_at, model, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .map_err(db_error)?;
+            for (position, message) in session.messages.iter().enumerate() {
+                insert
+                    .execute(params![
+                        session.id,
+                        message.id,
+                        position as i64,
+                        message.parent_id,
+                        message.role,
+                        message.content,
+                        message.created_at,
+                        message.model,
+                        to_json(message)?,
+                    ])
+                    .map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)
+    }
+
+    fn delete_session(&mut self, id: &str) -> Result<(), String> {
+        self.conn.execute("DELETE FROM sessions WHERE id = ?1", [id]).map_err(db_error)?;
+        Ok(())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<Session>, String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT data FROM sessions ORDER BY updated_at DESC")
+            .map_err(db_error)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(db_error)?;
+        let mut sessions = Vec::new();
+        for data in rows {
+            match self.session_from_row(&data.map_err(db_error)?) {
+                Ok(session) => sessions.push(session),
+                Err(e) => warn!("跳过无法解析的会话: {}", e),
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn load_models(&self) -> Result<HashMap<String, i32>, String> {
+        let mut statement = self.conn.prepare("SELECT name, frequency FROM models").map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?)))
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    fn save_models(&mut self, frequencies: &HashMap<String, i32>) -> Result<(), String> {
+        let tx = self.conn.savepoint().map_err(db_error)?;
+        {
+            let mut upsert = tx
+                .prepare_cached(
+                    "INSERT INTO models (name, frequency) VALUES (?1, ?2)
+                     ON CONFLICT(name) DO UPDATE SET frequency = excluded.frequency",
+                )
+                .map_err(db_error)?;
+            for (name, frequency) in frequencies {
+                upsert.execute(params![name, frequency]).map_err(db_error)?;
+            }
+        }
+        tx.commit().map_err(db_error)
+    }
+
+    fn append_usage(&mut self, record: &UsageRecord) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO usage_records (message_id, timestamp, model, profile, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![record.message_id, record.timestamp, record.model, record.profile, to_json(record)?],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn load_usage(&self) -> Result<Vec<UsageRecord>, String> {
+        let mut statement = self.conn.prepare("SELECT data FROM usage_records ORDER BY id").map_err(db_error)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(db_error)?;
+        rows.map(|data| from_json(&data.map_err(db_error)?)).collect()
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        self.conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .map_err(db_error)
+    }
+
+    fn set_setting(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn delete_setting(&mut self, key: &str) -> Result<(), String> {
+        self.conn.execute("DELETE FROM settings WHERE key = ?1", [key]).map_err(db_error)?;
+        Ok(())
+    }
+
+    fn transaction(&mut self, f: &mut dyn FnMut(&mut dyn Storage) -> Result<(), String>) -> Result<(), String> {
+        // 最外层立即获取写锁，避免读后写时被其他实例抢先；嵌套时使用保存点
+        let (begin, commit, rollback) = if self.conn.is_autocommit() {
+            ("BEGIN IMMEDIATE", "COMMIT", "ROLLBACK")
+        } else {
+            ("SAVEPOINT nested", "RELEASE nested", "ROLLBACK TO nested; RELEASE nested")
+        };
+        self.conn.execute_batch(begin).map_err(db_error)?;
+        match f(self) {
+            Ok(()) => self.conn.execute_batch(commit).map_err(db_error),
+            Err(e) => {
+                if let Err(rollback_error) = self.conn.execute_batch(rollback) {
+                    warn!("回滚事务失败: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// 导入旧版本的 JSON 文件的结果
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LegacyImportReport {
+    pub sessions: usize,
+    pub models: usize,
+    pub usage_records: usize,
+    pub settings: Vec<String>,
+}
+
+/// 旧版本保存的加密凭证文件及导入后的设置项，导入后删除文件
+const LEGACY_CREDENTIAL_FILES: &[(&str, &str)] = &[("api_keys.enc", API_KEY_SETTING), ("api_url.enc", API_URL_SETTING)];
+
+/// 旧版本保存的 JSON 设置文件及导入后的设置项
+const LEGACY_SETTING_FILES: &[(&str, &str)] = &[
+    ("prices.json", PRICES_KEY),
+    ("budgets.json", BUDGETS_KEY),
+    ("profiles.json", PROFILES_KEY),
+    ("personas.json", PERSONAS_KEY),
+    ("templates.json", TEMPLATES_KEY),
+    ("approved_dirs.json", APPROVED_DIRS_KEY),
+    ("mcp_servers.json", MCP_SERVERS_KEY),
+    ("folders.json", FOLDERS_KEY),
+    ("trash_config.json", TRASH_CONFIG_KEY),
+    ("summary_config.json", SUMMARY_CONFIG_KEY),
+];
+
+/// 导入旧版本保存在缓存目录中的会话、模型使用次数、用量账本、设置和 API 凭证。
+///
+/// 全部数据和导入完成的标记在同一个事务中写入，中途失败时整体回滚，下次启动重新导入不会产生重复记录；
+/// 已导入过时返回 None。会话、账本和设置文件保留作为备份；加密的 API 凭证在提交后删除，
+/// 以免删除凭证后旧文件仍留在磁盘上。
+pub fn import_legacy_files(storage: &mut dyn Storage, cache_dir: &Path) -> Result<Option<LegacyImportReport>, String> {
+    let mut report = None;
+    storage.transaction(&mut |storage| {
+        if storage.get_setting(LEGACY_IMPORTED_KEY)?.is_some() {
+            return Ok(());
+        }
+        report = Some(import_into(storage, cache_dir)?);
+        storage.set_setting(LEGACY_IMPORTED_KEY, "true")
+    })?;
+
+    for (file, key) in LEGACY_CREDENTIAL_FILES {
+        if report.as_ref().is_some_and(|report| report.settings.iter().any(|imported| imported == key)) {
+            let path = cache_dir.join(file);
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("删除已导入的 {} 失败: {}", path.display(), e);
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn import_into(storage: &mut dyn Storage, cache_dir: &Path) -> Result<LegacyImportReport, String> {
+    let mut report = LegacyImportReport::default();
+
+    let sessions_dir = cache_dir.join("sessions");
+    if sessions_dir.is_dir() {
+        for entry in fs::read_dir(&sessions_dir).map_err(|e| format!("读取会话目录失败: {}", e))? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            match read_session_file(&path) {
+                Ok(session) => {
+                    storage.save_session(&session)?;
+                    report.sessions += 1;
+                }
+                Err(e) => warn!("跳过无法解析的会话文件: {}", e),
+            }
+        }
+    }
+
+    let frequency_file = cache_dir.join("frequency.json");
+    if frequency_file.exists() {
+        match fs::read_to_string(&frequency_file)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<ModelFrequency>(&content).map_err(|e| e.to_string()))
+        {
+            Ok(data) => {
+                storage.save_models(&data.frequencies)?;
+                report.models = data.frequencies.len();
+            }
+            Err(e) => warn!("跳过无法解析的模型频率文件: {}", e),
+        }
+    }
+
+    for record in load_records(&cache_dir.join("usage.jsonl"))? {
+        storage.append_usage(&record)?;
+        report.usage_records += 1;
+    }
+
+    for (file, key) in LEGACY_SETTING_FILES {
+        let path = cache_dir.join(file);
+        if !path.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(value) => {
+                storage.set_setting(key, &value.to_string())?;
+                report.settings.push(key.to_string());
+            }
+            Err(e) => warn!("跳过无法解析的设置文件 {}: {}", path.display(), e),
+        }
+    }
+
+    for (file, key) in LEGACY_CREDENTIAL_FILES {
+        let path = cache_dir.join(file);
+        if !path.exists() {
+            continue;
+        }
+        let value = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        storage.set_setting(key, value.trim())?;
+        report.settings.push(key.to_string());
+    }
+    Ok(report)
+}
+
+lazy_static! {
+    static ref STORAGE: Mutex<Option<Box<dyn Storage>>> = Mutex::new(None);
+}
+
+pub fn database_file() -> PathBuf {
+    get_cache_dir().join(DATABASE_FILE)
+}
+
+/// 打开缓存目录中的数据库，首次打开时导入旧版本的 JSON 文件
+fn open_default_storage() -> Result<Box<dyn Storage>, String> {
+    let mut storage = SqliteStorage::open(&database_file())?;
+    if let Some(report) = import_legacy_files(&mut storage, &get_cache_dir())? {
+        info!("已导入旧版本数据: {:?}", report);
+    }
+    Ok(Box::new(storage))
+}
+
+/// 替换全局存储，例如测试中使用内存数据库
+pub fn set_storage(storage: Box<dyn Storage>) {
+    *STORAGE.lock().unwrap_or_else(|e| e.into_inner()) = Some(storage);
+}
+
+/// 在全局存储上执行操作，首次调用时打开缓存目录中的数据库
+pub fn with_storage<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut dyn Storage) -> Result<T, String>,
+{
+    let mut storage = STORAGE.lock().map_err(|e| e.to_string())?;
+    if storage.is_none() {
+        *storage = Some(open_default_storage()?);
+    }
+    f(storage.as_mut().expect("存储已初始化").as_mut())
+}
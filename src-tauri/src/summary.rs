@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use crate::chat::ChatMessage;
+use crate::session::{ContextSummary, Session, StoredMessage, DEFAULT_SESSION_TITLE};
+use crate::storage::{read_setting, write_setting, Storage};
+
+pub(crate) const SUMMARY_CONFIG_KEY: &str = "summary_config";
+
+/// 生成的标题最多保留的字符数
+pub const MAX_TITLE_CHARS: usize = 30;
+
+/// 默认保留不摘要的最近消息数
+pub const DEFAULT_KEEP_RECENT: usize = 6;
+
+/// 生成标题或摘要时单条消息最多发送的字符数
+const MAX_EXCERPT_CHARS: usize = 2000;
+
+const TITLE_PROMPT: &str = "根据下面的对话生成一个简短的标题，不超过 15 个字，使用对话所用的语言。只输出标题本身，不要加引号或标点。";
+
+const SUMMARY_PROMPT: &str = "将下面的对话整理成一段简洁的摘要，保留后续对话需要的事实、结论、用户的要求和尚未解决的问题。\
+如果提供了之前的摘要，将其与新的对话合并成一份完整的摘要。只输出摘要本身。";
+
+/// 自动标题和摘要的配置
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SummaryConfig {
+    /// 生成标题和摘要使用的模型，建议选择便宜的小模型；为空时使用本轮对话的模型
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 第一轮问答后是否自动生成标题
+    #[serde(default = "default_auto_title")]
+    pub auto_title: bool,
+    /// 生成摘要时保留原文的最近消息数
+    #[serde(default = "default_keep_recent")]
+    pub keep_recent: usize,
+}
+
+fn default_auto_title() -> bool {
+    true
+}
+
+fn default_keep_recent() -> usize {
+    DEFAULT_KEEP_RECENT
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        SummaryConfig {
+            model: None,
+            auto_title: default_auto_title(),
+            keep_recent: default_keep_recent(),
+        }
+    }
+}
+
+impl SummaryConfig {
+    /// 生成标题和摘要使用的模型
+    pub fn model_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        self.model.as_deref().filter(|model| !model.trim().is_empty()).unwrap_or(fallback)
+    }
+}
+
+pub fn load_summary_config(storage: &dyn Storage) -> Result<SummaryConfig, String> {
+    read_setting(storage, SUMMARY_CONFIG_KEY)
+}
+
+pub fn save_summary_config(storage: &mut dyn Storage, config: &SummaryConfig) -> Result<(), String> {
+    write_setting(storage, SUMMARY_CONFIG_KEY, config)
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(MAX_EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}……", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn transcript(messages: &[&StoredMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let speaker = if message.role == "user" { "用户" } else { "助手" };
+            format!("{}：{}", speaker, excerpt(&message.content))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 标题仍为默认值且当前分支已有完整的一轮问答时需要生成标题
+pub fn needs_title(session: &Session) -> bool {
+    session.title == DEFAULT_SESSION_TITLE
+        && session
+            .active_path()
+            .iter()
+            .any(|message| message.role == "assistant" && !message.content.trim().is_empty())
+}
+
+/// 以当前分支的第一轮问答构造生成标题的请求
+pub fn title_messages(session: &Session) -> Option<Vec<ChatMessage>> {
+    let path = session.active_path();
+    let question = path.iter().find(|message| message.role == "user")?;
+    let answer = path.iter().find(|message| message.role == "assistant")?;
+    Some(vec![
+        ChatMessage::new("system", TITLE_PROMPT),
+        ChatMessage::new("user", &transcript(&[question, answer])),
+    ])
+}
+
+/// 清理模型返回的标题：取第一行，去掉“标题：”前缀、引号和结尾标点，过长时截断
+pub fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line
+        .strip_prefix("标题：")
+        .or_else(|| line.strip_prefix("标题:"))
+        .or_else(|| line.strip_prefix("Title:"))
+        .unwrap_or(line);
+    let title = line
+        .trim_start_matches(|c: char| c.is_whitespace() || "\"'“‘《#*".contains(c))
+        .trim_end_matches(|c: char| c.is_whitespace() || "\"'”’》*。.！!？?，,".contains(c));
+    let title: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+/// 新摘要覆盖到的消息：保留最近 `keep_recent` 条消息后，当前分支上最后一条助手消息。
+///
+/// 没有比现有摘要更新的内容可摘要时返回 None。
+pub fn summary_cutoff(session: &Session, keep_recent: usize) -> Option<&StoredMessage> {
+    let path = session.active_path();
+    let candidates = &path[..path.len().saturating_sub(keep_recent)];
+    let cutoff = candidates.iter().rposition(|message| message.role == "assistant")?;
+    let previous = session
+        .context_summary
+        .as_ref()
+        .and_then(|summary| path.iter().position(|message| message.id == summary.until_message_id));
+    if previous.is_some_and(|previous| previous >= cutoff) {
+        return None;
+    }
+    Some(candidates[cutoff])
+}
+
+/// 构造生成摘要的请求：现有摘要在当前分支上时只发送它之后的消息，由模型合并成新的摘要
+pub fn summary_messages(session: &Session, until_message_id: &str) -> Vec<ChatMessage> {
+    let path = session.path_to(until_message_id);
+    let previous = session
+        .context_summary
+        .as_ref()
+        .and_then(|summary| Some((summary, path.iter().position(|message| message.id == summary.until_message_id)?)));
+    let content = match previous {
+        Some((summary, position)) => format!(
+            "之前的摘要：\n{}\n\n新的对话：\n{}",
+            summary.content,
+            transcript(&path[position + 1..])
+        ),
+        None => transcript(&path),
+    };
+    vec![ChatMessage::new("system", SUMMARY_PROMPT), ChatMessage::new("user", &content)]
+}
+
+/// 自动生成标题后推送给前端的事件载荷
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TitleEvent {
+    pub session_id: String,
+    pub title: String,
+}
+
+/// 后台摘要完成或失败后推送给前端的事件载荷
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SummaryEvent {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<ContextSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
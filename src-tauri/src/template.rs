@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::storage::{read_setting, with_storage, Storage};
+
+pub const TEMPLATES_KEY: &str = "templates";
+
+/// 模板中声明的变量
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 调用时未提供值则使用默认值
+    #[serde(default)]
+    pub default: Option<String>,
+    /// 可选变量未提供值且没有默认值时渲染为空字符串
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// 带变量的提示词模板，正文中用 `{变量名}` 引用变量，`{{` 和 `}}` 表示字面量花括号
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub body: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+}
+
+/// 通过 `chat` 发送模板时传入的模板名称和变量值
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TemplateInvocation {
+    pub name: String,
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+fn is_valid_variable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// 将模板正文解析为文本片段和变量引用
+fn parse_body(body: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("变量 {{{} 缺少右花括号", name)),
+                    }
+                }
+                let name = name.trim().to_string();
+                if !is_valid_variable_name(&name) {
+                    return Err(format!("无效的变量名: {{{}}}", name));
+                }
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Variable(name));
+            }
+            '}' => return Err("多余的右花括号，字面量请写作 }}".to_string()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+impl PromptTemplate {
+    /// 正文中引用的变量名，按首次出现的顺序
+    pub fn referenced_variables(&self) -> Result<Vec<String>, String> {
+        let mut seen = HashSet::new();
+        Ok(parse_body(&self.body)?
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Variable(name) if seen.insert(name.clone()) => Some(name),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// 检查模板语法，并确保正文引用的变量都已声明、声明的变量名不重复
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("模板名称不能为空".to_string());
+        }
+
+        let mut declared = HashSet::new();
+        for variable in &self.variables {
+            if !is_valid_variable_name(&variable.name) {
+                return Err(format!("无效的变量名: {}", variable.name));
+            }
+            if !declared.insert(variable.name.as_str()) {
+                return Err(format!("变量重复声明: {}", variable.name));
+            }
+        }
+
+        let undeclared: Vec<String> = self
+            .referenced_variables()?
+            .into_iter()
+            .filter(|name| !declared.contains(name.as_str()))
+            .collect();
+        if !undeclared.is_empty() {
+            return Err(format!("模板引用了未声明的变量: {}", undeclared.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// 用变量值渲染模板，缺少必填变量或传入未声明的变量时返回错误
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, String> {
+        self.validate()?;
+
+        let mut unknown: Vec<&str> = values
+            .keys()
+            .filter(|key| !self.variables.iter().any(|v| &v.name == *key))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(format!("未知的模板变量: {}", unknown.join(", ")));
+        }
+
+        let mut resolved = HashMap::new();
+        let mut missing = Vec::new();
+        for variable in &self.variables {
+            match values.get(&variable.name).or(variable.default.as_ref()) {
+                Some(value) => {
+                    resolved.insert(variable.name.as_str(), value.as_str());
+                }
+                None if variable.required => missing.push(variable.name.as_str()),
+                None => {
+                    resolved.insert(variable.name.as_str(), "");
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return Err(format!("缺少必填变量: {}", missing.join(", ")));
+        }
+
+        Ok(parse_body(&self.body)?
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text,
+                Segment::Variable(name) => resolved.get(name.as_str()).copied().unwrap_or_default().to_string(),
+            })
+            .collect())
+    }
+}
+
+pub fn load_templates(storage: &dyn Storage) -> Result<Vec<PromptTemplate>, String> {
+    read_setting(storage, TEMPLATES_KEY)
+}
+
+pub fn find_template(templates: &[PromptTemplate], name: &str) -> Option<PromptTemplate> {
+    templates.iter().find(|t| t.name == name).cloned()
+}
+
+/// 按名称读取已保存的模板并渲染
+pub fn render_saved_template(invocation: &TemplateInvocation) -> Result<String, String> {
+    let templates = with_storage(|storage| load_templates(storage))?;
+    let template = find_template(&templates, &invocation.name)
+        .ok_or_else(|| format!("模板不存在: {}", invocation.name))?;
+    template.render(&invocation.values)
+}
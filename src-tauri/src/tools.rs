@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use futures_util::future::BoxFuture;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+use crate::chat::{FunctionDefinition, ToolCall, ToolDefinition};
+
+/// 每个会话默认允许的工具调用轮数
+pub const DEFAULT_MAX_TOOL_STEPS: u32 = 5;
+
+/// 可供模型调用的本地工具
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// 参数的 JSON Schema，顶层应为 `object`
+    fn parameters(&self) -> Value;
+
+    /// 执行工具，返回发送给模型的文本结果
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>>;
+
+    /// 执行前是否需要用户确认
+    fn requires_approval(&self) -> bool {
+        false
+    }
+}
+
+/// 工具注册表，按名称索引
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+}
+
+/// 推送给前端的工具执行结果
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolOutcome {
+    pub tool_call_id: String,
+    pub name: String,
+    pub content: String,
+    pub is_error: bool,
+}
+
+/// 推送给前端的工具执行确认请求，前端通过 `respond_tool_approval` 回复
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ApprovalRequest {
+    pub request_id: String,
+    pub tool_call: ToolCall,
+    pub description: String,
+}
+
+lazy_static! {
+    pub static ref TOOL_REGISTRY: RwLock<ToolRegistry> = RwLock::new(ToolRegistry::new());
+    // 等待用户确认的工具调用，键为确认请求 ID
+    static ref PENDING_APPROVALS: Mutex<HashMap<String, oneshot::Sender<bool>>> = Mutex::new(HashMap::new());
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册工具，同名工具会被替换
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name().to_string(), tool);
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.tools.remove(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.get(name).cloned()
+    }
+
+    /// 未知工具不需要确认，执行时会直接返回错误
+    pub fn requires_approval(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|tool| tool.requires_approval())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tools.keys().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// 生成请求中的 `tools` 字段
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .values()
+            .map(|tool| ToolDefinition {
+                kind: "function".to_string(),
+                function: FunctionDefinition {
+                    name: tool.name().to_string(),
+                    description: tool.description().to_string(),
+                    parameters: tool.parameters(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// 全局注册表的快照，执行工具时不持有锁
+pub fn registry_snapshot() -> ToolRegistry {
+    TOOL_REGISTRY.read().map(|registry| registry.clone()).unwrap_or_default()
+}
+
+pub fn register_tool(tool: Arc<dyn Tool>) {
+    if let Ok(mut registry) = TOOL_REGISTRY.write() {
+        registry.register(tool);
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// 按 JSON Schema 校验参数：检查必填字段、属性类型和枚举值
+pub fn validate_arguments(schema: &Value, arguments: &Value) -> Result<(), String> {
+    let Some(object) = arguments.as_object() else {
+        return Err("工具参数必须是 JSON 对象".to_string());
+    };
+
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        let missing: Vec<&str> = required
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|name| !object.contains_key(*name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("缺少必填参数: {}", missing.join(", ")));
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in object {
+        let Some(property) = properties.and_then(|p| p.get(name)) else {
+            if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                return Err(format!("未知参数: {}", name));
+            }
+            continue;
+        };
+        if let Some(expected) = property.get("type").and_then(Value::as_str) {
+            if !type_matches(expected, value) {
+                return Err(format!("参数 {} 应为 {} 类型", name, expected));
+            }
+        }
+        if let Some(allowed) = property.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                return Err(format!("参数 {} 的取值不在允许范围内", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl ToolOutcome {
+    /// 用户拒绝执行时返回给模型的结果
+    pub fn denied(call: &ToolCall) -> Self {
+        ToolOutcome {
+            tool_call_id: call.id.clone(),
+            name: call.function.name.clone(),
+            content: "用户拒绝执行该工具调用".to_string(),
+            is_error: true,
+        }
+    }
+}
+
+/// 登记一个等待确认的请求，返回接收用户答复的通道
+pub fn register_pending_approval(request_id: &str) -> oneshot::Receiver<bool> {
+    let (sender, receiver) = oneshot::channel();
+    if let Ok(mut pending) = PENDING_APPROVALS.lock() {
+        pending.insert(request_id.to_string(), sender);
+    }
+    receiver
+}
+
+/// 前端回复确认请求
+pub fn resolve_approval(request_id: &str, approved: bool) -> Result<(), String> {
+    let sender = PENDING_APPROVALS
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(request_id)
+        .ok_or_else(|| format!("确认请求不存在或已过期: {}", request_id))?;
+    sender
+        .send(approved)
+        .map_err(|_| "确认请求已被取消".to_string())
+}
+
+/// 等待用户答复，超时或通道关闭视为拒绝
+pub async fn wait_for_approval(request_id: &str, receiver: oneshot::Receiver<bool>, timeout: Duration) -> bool {
+    let approved = matches!(tokio::time::timeout(timeout, receiver).await, Ok(Ok(true)));
+    if let Ok(mut pending) = PENDING_APPROVALS.lock() {
+        pending.remove(request_id);
+    }
+    approved
+}
+
+/// 解析并校验参数后执行工具调用，错误也作为结果返回，让模型有机会自行修正
+pub async fn execute_tool_call(registry: &ToolRegistry, call: &ToolCall) -> ToolOutcome {
+    let result = run_tool(registry, call).await;
+    let (content, is_error) = match result {
+        Ok(content) => (content, false),
+        Err(e) => (format!("工具执行失败: {}", e), true),
+    };
+    ToolOutcome {
+        tool_call_id: call.id.clone(),
+        name: call.function.name.clone(),
+        content,
+        is_error,
+    }
+}
+
+async fn run_tool(registry: &ToolRegistry, call: &ToolCall) -> Result<String, String> {
+    let tool = registry
+        .get(&call.function.name)
+        .ok_or_else(|| format!("未知的工具: {}", call.function.name))?;
+
+    let raw = call.function.arguments.trim();
+    let arguments: Value = if raw.is_empty() {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_str(raw).map_err(|e| format!("参数不是合法的 JSON: {}", e))?
+    };
+    validate_arguments(&tool.parameters(), &arguments)?;
+
+    tool.call(arguments).await
+}
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::chat::Usage;
+use crate::profile::DEFAULT_PROFILE;
+use crate::storage::{read_setting, with_storage, write_setting, Storage};
+
+pub const PRICES_KEY: &str = "model_prices";
+pub(crate) const BUDGETS_KEY: &str = "budgets";
+
+/// 账本中的一条记录，费用在记账时按当时的价格计算
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub message_id: String,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+    pub model: String,
+    pub profile: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub cost: f64,
+}
+
+/// 模型单价，单位为每百万 token 的价格
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+/// 每月预算上限，`global` 作用于所有配置，`profiles` 按配置单独限制
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub global: Option<f64>,
+    #[serde(default)]
+    pub profiles: HashMap<String, f64>,
+}
+
+impl BudgetConfig {
+    /// 预算上限必须是非负的有限数
+    pub fn validate(&self) -> Result<(), String> {
+        fn check_limit(name: &str, limit: f64) -> Result<(), String> {
+            if !limit.is_finite() || limit < 0.0 {
+                return Err(format!("{} 的预算必须是非负数，当前为 {}", name, limit));
+            }
+            Ok(())
+        }
+
+        if let Some(global) = self.global {
+            check_limit("全局", global)?;
+        }
+        for (profile, limit) in &self.profiles {
+            check_limit(profile, *limit)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendGroup {
+    Day,
+    Model,
+    Profile,
+}
+
+/// 按维度汇总后的花费
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SpendSummary {
+    pub key: String,
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+pub fn load_prices(storage: &dyn Storage) -> Result<HashMap<String, ModelPrice>, String> {
+    read_setting(storage, PRICES_KEY)
+}
+
+pub fn load_budgets(storage: &dyn Storage) -> Result<BudgetConfig, String> {
+    read_setting(storage, BUDGETS_KEY)
+}
+
+pub fn save_budgets(storage: &mut dyn Storage, budgets: &BudgetConfig) -> Result<(), String> {
+    write_setting(storage, BUDGETS_KEY, budgets)
+}
+
+/// 查找模型单价：优先精确匹配，否则取最长的前缀匹配（如 `gpt-4o` 匹配 `gpt-4o-2024-08-06`）
+pub fn find_price<'a>(prices: &'a HashMap<String, ModelPrice>, model: &str) -> Option<&'a ModelPrice> {
+    prices.get(model).or_else(|| {
+        prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    })
+}
+
+pub fn compute_cost(price: Option<&ModelPrice>, usage: &Usage) -> f64 {
+    match price {
+        Some(price) => {
+            (usage.prompt_tokens as f64 * price.prompt + usage.completion_tokens as f64 * price.completion) / 1_000_000.0
+        }
+        None => 0.0,
+    }
+}
+
+/// 读取旧版本的 JSONL 账本，跳过无法解析的行（例如写入中断留下的半行）
+pub fn load_records(ledger_file: &Path) -> Result<Vec<UsageRecord>, String> {
+    if !ledger_file.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(ledger_file).map_err(|e| format!("读取账本失败: {}", e))?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!("跳过无法解析的账本记录: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// 按当前价格表计算费用并记账
+pub fn record_usage(message_id: &str, model: &str, profile: Option<&str>, usage: &Usage) -> Result<UsageRecord, String> {
+    with_storage(|storage| {
+        let prices = load_prices(storage)?;
+        let record = UsageRecord {
+            message_id: message_id.to_string(),
+            timestamp: Local::now().timestamp_millis(),
+            model: model.to_string(),
+            profile: profile.unwrap_or(DEFAULT_PROFILE).to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cost: compute_cost(find_price(&prices, model), usage),
+        };
+        storage.append_usage(&record)?;
+        Ok(record)
+    })
+}
+
+fn record_date(record: &UsageRecord) -> Option<NaiveDate> {
+    Local
+        .timestamp_millis_opt(record.timestamp)
+        .single()
+        .map(|time| time.date_naive())
+}
+
+/// 按维度汇总花费，`from`/`to` 为包含边界的本地日期
+pub fn summarize(records: &[UsageRecord], group: SpendGroup, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<SpendSummary> {
+    let mut groups: BTreeMap<String, SpendSummary> = BTreeMap::new();
+
+    for record in records {
+        let Some(date) = record_date(record) else {
+            continue;
+        };
+        if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+            continue;
+        }
+
+        let key = match group {
+            SpendGroup::Day => date.format("%Y-%m-%d").to_string(),
+            SpendGroup::Model => record.model.clone(),
+            SpendGroup::Profile => record.profile.clone(),
+        };
+        let summary = groups.entry(key.clone()).or_insert_with(|| SpendSummary {
+            key,
+            ..Default::default()
+        });
+        summary.requests += 1;
+        summary.prompt_tokens += record.prompt_tokens as u64;
+        summary.completion_tokens += record.completion_tokens as u64;
+        summary.cost += record.cost;
+    }
+
+    groups.into_values().collect()
+}
+
+/// 计算 `now` 所在自然月的花费，`profile` 为 None 时统计所有配置
+pub fn monthly_spend(records: &[UsageRecord], profile: Option<&str>, now: DateTime<Local>) -> f64 {
+    records
+        .iter()
+        .filter(|record| profile.is_none_or(|profile| record.profile == profile))
+        .filter(|record| {
+            record_date(record).is_some_and(|date| date.year() == now.year() && date.month() == now.month())
+        })
+        .map(|record| record.cost)
+        .sum()
+}
+
+/// 检查本月花费是否已超出全局预算或配置预算
+pub fn check_budget(records: &[UsageRecord], budgets: &BudgetConfig, profile: Option<&str>, now: DateTime<Local>) -> Result<(), String> {
+    if let Some(limit) = budgets.global {
+        let spent = monthly_spend(records, None, now);
+        if spent >= limit {
+            return Err(format!("本月花费 {:.4} 已达到全局预算 {:.4}", spent, limit));
+        }
+    }
+
+    let profile = profile.unwrap_or(DEFAULT_PROFILE);
+    if let Some(limit) = budgets.profiles.get(profile) {
+        let spent = monthly_spend(records, Some(profile), now);
+        if spent >= *limit {
+            return Err(format!("配置 {} 本月花费 {:.4} 已达到预算 {:.4}", profile, spent, limit));
+        }
+    }
+
+    Ok(())
+}
+
+/// `chat` 发送请求前调用，超出预算时拒绝请求
+pub fn ensure_within_budget(profile: Option<&str>) -> Result<(), String> {
+    with_storage(|storage| {
+        let budgets = load_budgets(storage)?;
+        if budgets.global.is_none() && budgets.profiles.is_empty() {
+            return Ok(());
+        }
+        check_budget(&storage.load_usage()?, &budgets, profile, Local::now())
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 将账本记录转换为 CSV 文本
+pub fn records_to_csv(records: &[UsageRecord]) -> String {
+    let mut csv = String::from("time,message_id,model,profile,prompt_tokens,completion_tokens,cost\n");
+    for record in records {
+        let time = Local
+            .timestamp_millis_opt(record.timestamp)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:.6}\n",
+            time,
+            csv_field(&record.message_id),
+            csv_field(&record.model),
+            csv_field(&record.profile),
+            record.prompt_tokens,
+            record.completion_tokens,
+            record.cost
+        ));
+    }
+    csv
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use image::{ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use chat_ai_lib::attachment::{
+    attachment_message, chunk_text, compose_with_documents, encode_image, load_document, load_image, DocumentKind,
+    DEFAULT_DOCUMENT_BUDGET, MAX_DOCUMENTS_CHARS,
+    MAX_IMAGE_DIMENSION,
+};
+
+fn png_bytes(image: impl Into<image::DynamicImage>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.into().write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+    bytes
+}
+
+#[test]
+fn test_encode_image_resizes_large_images() {
+    let bytes = png_bytes(RgbImage::from_pixel(4096, 1024, Rgb([200, 10, 10])));
+    let attachment = encode_image("wide.png", &bytes).unwrap();
+
+    assert_eq!(attachment.name, "wide.png");
+    assert_eq!((attachment.width, attachment.height), (MAX_IMAGE_DIMENSION, 512));
+    // 不透明图片重新编码为 JPEG
+    assert_eq!(attachment.mime_type, "image/jpeg");
+    assert!(attachment.data_url.starts_with("data:image/jpeg;base64,"));
+}
+
+#[test]
+fn test_encode_image_keeps_transparency() {
+    let bytes = png_bytes(RgbaImage::from_pixel(16, 8, Rgba([0, 0, 0, 128])));
+    let attachment = encode_image("icon.png", &bytes).unwrap();
+
+    assert_eq!((attachment.width, attachment.height), (16, 8));
+    assert_eq!(attachment.mime_type, "image/png");
+    assert!(attachment.data_url.starts_with("data:image/png;base64,"));
+}
+
+#[test]
+fn test_load_image_errors() {
+    assert!(encode_image("notes.txt", b"not an image").is_err());
+    assert!(load_image(Path::new("/nonexistent/image.png")).is_err());
+}
+
+#[test]
+fn test_chunk_text() {
+    let text = "第一行\n第二行\n第三行\n";
+    assert_eq!(chunk_text(text, 8), ["第一行\n第二行\n", "第三行\n"]);
+    // 超长的行按字符切开
+    assert_eq!(chunk_text("abcdefg", 3), ["abc", "def", "g"]);
+    assert!(chunk_text("", 10).is_empty());
+}
+
+#[test]
+fn test_document_kind() {
+    assert_eq!(DocumentKind::from_path(Path::new("README.md")), DocumentKind::Markdown);
+    assert_eq!(DocumentKind::from_path(Path::new("paper.PDF")), DocumentKind::Pdf);
+    assert_eq!(
+        DocumentKind::from_path(Path::new("src/main.rs")),
+        DocumentKind::Code { language: "rust".to_string() }
+    );
+    assert_eq!(DocumentKind::from_path(Path::new("notes")), DocumentKind::Text);
+}
+
+#[test]
+fn test_load_document_within_budget() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-documents-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("main.rs");
+    fs::write(&source, "fn main() {}\n").unwrap();
+    let long = dir.join("long.txt");
+    fs::write(&long, "一二三四五六七八九十\n".repeat(1000)).unwrap();
+    let binary = dir.join("data.txt");
+    fs::write(&binary, [0u8, 159, 146, 150]).unwrap();
+    let pdf = dir.join("broken.pdf");
+    fs::write(&pdf, "not a pdf").unwrap();
+
+    let document = load_document(&source, 1000).unwrap();
+    assert_eq!(document.name, "main.rs");
+    assert_eq!(document.text, "fn main() {}\n");
+    assert!(!document.is_truncated());
+
+    let document = load_document(&long, 5000).unwrap();
+    assert_eq!(document.total_chars, 11000);
+    assert!(document.is_truncated());
+    assert!(document.text.chars().count() <= 5000);
+    assert_eq!(document.included_chunks, 2);
+
+    assert!(load_document(&binary, 1000).is_err());
+    assert!(load_document(&pdf, 1000).is_err());
+
+    let composed = compose_with_documents("解释这段代码", &[load_document(&source, 1000).unwrap()]);
+    assert_eq!(composed, "<document name=\"main.rs\" type=\"rust\">\nfn main() {}\n</document>\n\n解释这段代码");
+    let truncated = compose_with_documents("总结", std::slice::from_ref(&document));
+    assert!(truncated.contains("[文件过长，仅包含前 2/6 个分块]"));
+
+    let message = attachment_message("user", "总结", &[], std::slice::from_ref(&document));
+    assert_eq!(message.content.text(), truncated);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_documents_share_context_budget() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-document-budget-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let documents: Vec<_> = (0..3)
+        .map(|i| {
+            let path = dir.join(format!("part{}.txt", i));
+            fs::write(&path, "一二三四五六七八九十\n".repeat(3000)).unwrap();
+            load_document(&path, DEFAULT_DOCUMENT_BUDGET).unwrap()
+        })
+        .collect();
+    let loaded: usize = documents.iter().map(|d| d.text.chars().count()).sum();
+    assert!(loaded > MAX_DOCUMENTS_CHARS);
+
+    // 合计超出预算时放不下的文档被截断并注明，注入的内容不超过预算
+    let composed = compose_with_documents("总结", &documents);
+    let injected: usize = composed.lines().filter(|line| line.starts_with('一')).map(|line| line.chars().count() + 1).sum();
+    assert!(injected <= MAX_DOCUMENTS_CHARS);
+    assert_eq!(composed.matches("[文件过长，仅包含前").count(), 2);
+    assert!(composed.contains("<document name=\"part2.txt\" type=\"text\">\n\n[附件总长度超出上下文预算，仅包含前 0/17 个分块]"));
+    assert!(composed.ends_with("总结"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
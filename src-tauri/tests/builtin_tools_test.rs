@@ -0,0 +1,98 @@
+use std::fs;
+use std::time::Duration;
+use serde_json::json;
+use chat_ai_lib::builtin_tools::{
+    evaluate_expression, format_number, resolve_sandboxed_path, CalculatorTool, ReadFileTool, APPROVED_DIRS_KEY,
+};
+use chat_ai_lib::storage::{set_storage, update_setting, with_storage, SqliteStorage};
+use chat_ai_lib::tools::{register_pending_approval, resolve_approval, wait_for_approval, Tool};
+
+#[test]
+fn test_evaluate_expression() {
+    assert_eq!(evaluate_expression("1 + 2 * 3").unwrap(), 7.0);
+    assert_eq!(evaluate_expression("(1 + 2) * 3").unwrap(), 9.0);
+    assert_eq!(evaluate_expression("2 ^ 3 ^ 2").unwrap(), 512.0);
+    assert_eq!(evaluate_expression("-2 ^ 2").unwrap(), -4.0);
+    assert_eq!(evaluate_expression("10 % 4 + sqrt(16)").unwrap(), 6.0);
+    assert_eq!(evaluate_expression("max(1, min(5, 3))").unwrap(), 3.0);
+    assert_eq!(evaluate_expression("1.5e2").unwrap(), 150.0);
+    assert!((evaluate_expression("cos(pi)").unwrap() + 1.0).abs() < 1e-12);
+
+    assert!(evaluate_expression("1 / 0").is_err());
+    assert!(evaluate_expression("(1 + 2").is_err());
+    assert!(evaluate_expression("2 +").is_err());
+    assert!(evaluate_expression("foo(1)").is_err());
+    assert!(evaluate_expression("sqrt(1, 2)").is_err());
+
+    // 过深的嵌套返回错误而不是栈溢出
+    let nested = format!("{}1{}", "(".repeat(20), ")".repeat(20));
+    assert_eq!(evaluate_expression(&nested).unwrap(), 1.0);
+    let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert!(evaluate_expression(&deep).unwrap_err().contains("嵌套"));
+    assert!(evaluate_expression(&format!("{}1", "-".repeat(100_000))).is_err());
+
+    assert_eq!(format_number(42.0), "42");
+    assert_eq!(format_number(0.5), "0.5");
+}
+
+#[tokio::test]
+async fn test_calculator_tool() {
+    let tool = CalculatorTool;
+    assert!(tool.requires_approval());
+    let result = tool.call(json!({ "expression": "3 * (4 + 5)" })).await.unwrap();
+    assert_eq!(result, "27");
+}
+
+#[tokio::test]
+async fn test_read_file_sandboxed() {
+    let root = std::env::temp_dir().join(format!("chat-ai-builtin-{}", std::process::id()));
+    let approved = root.join("approved");
+    let outside = root.join("outside");
+    fs::create_dir_all(&approved).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(approved.join("notes.txt"), "你好").unwrap();
+    fs::write(approved.join("data.bin"), [0u8, 1, 2]).unwrap();
+    fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+    set_storage(Box::new(SqliteStorage::open_in_memory().unwrap()));
+    with_storage(|storage| {
+        update_setting(storage, APPROVED_DIRS_KEY, |dirs: &mut Vec<std::path::PathBuf>| {
+            dirs.push(approved.clone());
+            Ok(())
+        })
+    })
+    .unwrap();
+
+    let dirs = vec![approved.clone()];
+    assert!(resolve_sandboxed_path(&dirs, approved.join("notes.txt").to_str().unwrap()).is_ok());
+    let escaped = approved.join("..").join("outside").join("secret.txt");
+    assert!(resolve_sandboxed_path(&dirs, escaped.to_str().unwrap()).is_err());
+
+    let tool = ReadFileTool;
+    let path = approved.join("notes.txt");
+    assert_eq!(tool.call(json!({ "path": path })).await.unwrap(), "你好");
+    let binary = approved.join("data.bin");
+    assert!(tool.call(json!({ "path": binary })).await.is_err());
+    let secret = outside.join("secret.txt");
+    assert!(tool.call(json!({ "path": secret })).await.is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_approval_round_trip() {
+    let receiver = register_pending_approval("approval-test-1");
+    resolve_approval("approval-test-1", true).unwrap();
+    assert!(wait_for_approval("approval-test-1", receiver, Duration::from_secs(1)).await);
+    // 已答复的请求不能再次答复
+    assert!(resolve_approval("approval-test-1", false).is_err());
+
+    let receiver = register_pending_approval("approval-test-2");
+    resolve_approval("approval-test-2", false).unwrap();
+    assert!(!wait_for_approval("approval-test-2", receiver, Duration::from_secs(1)).await);
+
+    // 超时视为拒绝，且请求被移除
+    let receiver = register_pending_approval("approval-test-3");
+    assert!(!wait_for_approval("approval-test-3", receiver, Duration::from_millis(10)).await);
+    assert!(resolve_approval("approval-test-3", true).is_err());
+}
@@ -1,4 +1,3 @@
-use std::fs;
 use chat_ai_lib::cache::{
     get_cache_dir,
     encrypt_api_key,
@@ -11,6 +10,7 @@ use chat_ai_lib::cache::{
     save_frequencies,
     MODEL_FREQUENCIES,
 };
+use chat_ai_lib::storage::with_storage;
 
 #[test]
 fn test_cache_directory() {
@@ -139,13 +139,9 @@ fn test_model_frequencies() {
     // 测试保存频率数据
     save_frequencies();
     
-    // 验证频率文件是否创建
-    let cache_dir = get_cache_dir();
-    let frequency_file = cache_dir.join("frequency.json");
-    assert!(frequency_file.exists());
-    
-    // 清理测试数据
-    let _ = fs::remove_file(frequency_file);
+    // 验证频率数据已写入数据库
+    let stored = with_storage(|storage| storage.load_models()).unwrap();
+    assert_eq!(stored.get(&model), Some(&-1));
 }
 
 #[test]
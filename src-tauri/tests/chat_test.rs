@@ -1,10 +1,14 @@
-use chat_ai_lib::chat::{ChatMessage, ChatPayload, StreamResponse, StreamChoice, DeltaContent};
+use chat_ai_lib::chat::{
+    parse_stream_line, ChatMessage, ContentPart, MessageContent, ChatPayload, ChatResult, ChatTimings, DeltaContent, FinishReason,
+    GenerationParams, ResponseFormat, SseLineBuffer, StreamAccumulator, StreamChoice, StreamDelta, StreamResponse, Usage,
+};
 
 #[test]
 fn test_chat_message() {
     let message = ChatMessage {
         role: "user".to_string(),
-        content: "Hello".to_string(),
+        content: "Hello".into(),
+        ..Default::default()
     };
     
     assert_eq!(message.role, "user");
@@ -16,7 +20,8 @@ fn test_chat_payload() {
     let messages = vec![
         ChatMessage {
             role: "user".to_string(),
-            content: "Hello".to_string(),
+            content: "Hello".into(),
+            ..Default::default()
         }
     ];
     
@@ -24,6 +29,9 @@ fn test_chat_payload() {
         model: "gpt-3.5-turbo".to_string(),
         messages: messages.clone(),
         stream: true,
+        stream_options: None,
+        params: GenerationParams::default(),
+        tools: None,
     };
     
     assert_eq!(payload.model, "gpt-3.5-turbo");
@@ -37,6 +45,8 @@ fn test_chat_payload() {
 fn test_stream_response() {
     let delta = DeltaContent {
         content: Some("Hello".to_string()),
+        reasoning_content: None,
+        tool_calls: None,
     };
     
     let choice = StreamChoice {
@@ -45,7 +55,10 @@ fn test_stream_response() {
     };
     
     let response = StreamResponse {
+        id: None,
+        model: None,
         choices: vec![choice],
+        usage: None,
     };
     
     assert_eq!(response.choices.len(), 1);
@@ -57,6 +70,8 @@ fn test_stream_response() {
 fn test_stream_response_serialization() {
     let delta = DeltaContent {
         content: Some("Hello".to_string()),
+        reasoning_content: None,
+        tool_calls: None,
     };
     
     let choice = StreamChoice {
@@ -65,7 +80,10 @@ fn test_stream_response_serialization() {
     };
     
     let response = StreamResponse {
+        id: None,
+        model: None,
         choices: vec![choice],
+        usage: None,
     };
     
     let serialized = serde_json::to_string(&response).unwrap();
@@ -86,7 +104,8 @@ fn test_stream_response_serialization() {
 fn test_chat_message_serialization() {
     let message = ChatMessage {
         role: "user".to_string(),
-        content: "Hello".to_string(),
+        content: "Hello".into(),
+        ..Default::default()
     };
     
     let serialized = serde_json::to_string(&message).unwrap();
@@ -96,12 +115,35 @@ fn test_chat_message_serialization() {
     assert_eq!(message.content, deserialized.content);
 }
 
+#[test]
+fn test_multi_part_content() {
+    let message = ChatMessage::with_images("user", "这是什么？", &["data:image/png;base64,AAAA".to_string()]);
+    let value = serde_json::to_value(&message).unwrap();
+    assert_eq!(value["content"][0], serde_json::json!({ "type": "text", "text": "这是什么？" }));
+    assert_eq!(
+        value["content"][1],
+        serde_json::json!({ "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } })
+    );
+    assert_eq!(message.content.text(), "这是什么？");
+    assert_eq!(message.content.image_urls(), ["data:image/png;base64,AAAA"]);
+
+    // 没有图片时仍按字符串序列化
+    let plain = ChatMessage::with_images("user", "你好", &[]);
+    assert_eq!(serde_json::to_value(&plain).unwrap()["content"], "你好");
+
+    let parsed: ChatMessage = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+    assert!(matches!(&parsed.content, MessageContent::Parts(parts) if matches!(parts[1], ContentPart::ImageUrl { .. })));
+    let parsed: ChatMessage = serde_json::from_str(r#"{"role":"user","content":"旧格式"}"#).unwrap();
+    assert_eq!(parsed.content, "旧格式");
+}
+
 #[test]
 fn test_chat_payload_serialization() {
     let messages = vec![
         ChatMessage {
             role: "user".to_string(),
-            content: "Hello".to_string(),
+            content: "Hello".into(),
+            ..Default::default()
         }
     ];
     
@@ -109,6 +151,9 @@ fn test_chat_payload_serialization() {
         model: "gpt-3.5-turbo".to_string(),
         messages: messages.clone(),
         stream: true,
+        stream_options: None,
+        params: GenerationParams::default(),
+        tools: None,
     };
     
     let serialized = serde_json::to_string(&payload).unwrap();
@@ -119,4 +164,245 @@ fn test_chat_payload_serialization() {
     assert_eq!(payload.messages[0].role, deserialized.messages[0].role);
     assert_eq!(payload.messages[0].content, deserialized.messages[0].content);
     assert_eq!(payload.stream, deserialized.stream);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parse_stream_line() {
+    let line = r#"data: {"id":"chatcmpl-1","model":"deepseek-chat","choices":[{"delta":{"content":"Hi"},"finish_reason":null}]}"#;
+    let response = parse_stream_line(line).unwrap();
+
+    assert_eq!(response.id, Some("chatcmpl-1".to_string()));
+    assert_eq!(response.model, Some("deepseek-chat".to_string()));
+    assert_eq!(response.choices[0].delta.content, Some("Hi".to_string()));
+
+    assert!(parse_stream_line("data: [DONE]").is_none());
+    assert!(parse_stream_line(": keep-alive").is_none());
+    assert!(parse_stream_line("data: {broken").is_none());
+}
+
+#[test]
+fn test_stream_accumulator() {
+    let lines = [
+        r#"data: {"id":"chatcmpl-1","model":"gpt-4o-2024-08-06","choices":[{"delta":{"content":"Hel"},"finish_reason":null}]}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{"content":"lo"},"finish_reason":null}]}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}"#,
+    ];
+
+    let mut accumulator = StreamAccumulator::new();
+    let emitted: Vec<String> = lines
+        .iter()
+        .filter_map(|line| parse_stream_line(line))
+        .filter_map(|response| accumulator.push(response).content)
+        .collect();
+    assert_eq!(emitted, vec!["Hel", "lo"]);
+
+    let result = accumulator.into_result("gpt-4o", ChatTimings { first_token_ms: Some(10), total_ms: 20 });
+    // 消息 ID 总是本地生成，服务端的 ID 单独保存
+    assert!(result.message_id.starts_with("msg-"));
+    assert_eq!(result.response_id.as_deref(), Some("chatcmpl-1"));
+    assert_eq!(result.content, "Hello");
+    assert_eq!(result.finish_reason, Some(FinishReason::Stop));
+    assert!(!result.is_truncated());
+    assert_eq!(result.model, "gpt-4o-2024-08-06");
+    assert_eq!(
+        result.usage,
+        Some(Usage { prompt_tokens: 5, completion_tokens: 2, total_tokens: 7 })
+    );
+}
+
+#[test]
+fn test_chat_result_defaults() {
+    let result = StreamAccumulator::new().into_result("gpt-4", ChatTimings::default());
+
+    assert!(result.message_id.starts_with("msg-"));
+    assert!(result.response_id.is_none());
+    assert_eq!(result.model, "gpt-4");
+    assert!(result.content.is_empty());
+    assert!(result.usage.is_none());
+}
+
+#[test]
+fn test_sse_line_buffer() {
+    let line = r#"data: {"id":"","choices":[{"delta":{"content":"你好"},"finish_reason":null}]}"#;
+    let bytes = format!("{}\r\n", line).into_bytes();
+    // 在“你”的 UTF-8 编码中间切开
+    let split = line.find("你").unwrap() + 1;
+
+    let mut lines = SseLineBuffer::new();
+    assert!(lines.push(&bytes[..split]).is_empty());
+    let complete = lines.push(&bytes[split..]);
+    assert_eq!(complete, [line]);
+
+    let mut accumulator = StreamAccumulator::new();
+    accumulator.push(parse_stream_line(&complete[0]).unwrap());
+    let result = accumulator.into_result("gpt-4o", ChatTimings::default());
+    assert_eq!(result.content, "你好");
+    assert!(result.response_id.is_none());
+
+    let mut lines = SseLineBuffer::new();
+    assert!(lines.push("data: [DONE]".as_bytes()).is_empty());
+    assert_eq!(lines.finish().as_deref(), Some("data: [DONE]"));
+}
+
+#[test]
+fn test_finish_reason_parse() {
+    assert_eq!(FinishReason::parse("stop"), FinishReason::Stop);
+    assert_eq!(FinishReason::parse("length"), FinishReason::Length);
+    assert_eq!(FinishReason::parse("content_filter"), FinishReason::ContentFilter);
+    assert_eq!(FinishReason::parse("tool_calls"), FinishReason::ToolCalls);
+    assert_eq!(FinishReason::parse("something_new"), FinishReason::Other);
+
+    let serialized = serde_json::to_string(&FinishReason::ContentFilter).unwrap();
+    assert_eq!(serialized, "\"content_filter\"");
+}
+
+#[test]
+fn test_append_continuation() {
+    let mut result = ChatResult {
+        message_id: "chatcmpl-1".to_string(),
+        response_id: None,
+        content: "第一部分".to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Length),
+        usage: Some(Usage { prompt_tokens: 10, completion_tokens: 100, total_tokens: 110 }),
+        timings: ChatTimings { first_token_ms: Some(50), total_ms: 1000 },
+        model: "deepseek-chat".to_string(),
+    };
+    assert!(result.is_truncated());
+
+    let continuation = ChatResult {
+        message_id: "chatcmpl-2".to_string(),
+        response_id: None,
+        content: "，第二部分".to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: Some(Usage { prompt_tokens: 120, completion_tokens: 20, total_tokens: 140 }),
+        timings: ChatTimings { first_token_ms: Some(40), total_ms: 500 },
+        model: "deepseek-chat".to_string(),
+    };
+    result.append_continuation(continuation);
+
+    assert_eq!(result.message_id, "chatcmpl-1");
+    assert_eq!(result.content, "第一部分，第二部分");
+    assert_eq!(result.finish_reason, Some(FinishReason::Stop));
+    assert_eq!(result.timings.total_ms, 1500);
+    assert_eq!(result.timings.first_token_ms, Some(50));
+    assert_eq!(result.usage.unwrap().total_tokens, 250);
+}
+
+#[test]
+fn test_generation_params_omitted_when_unset() {
+    let payload = ChatPayload {
+        model: "gpt-4o".to_string(),
+        messages: vec![],
+        stream: true,
+        stream_options: None,
+        params: GenerationParams::default(),
+        tools: None,
+    };
+
+    let value = serde_json::to_value(&payload).unwrap();
+    let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+    assert_eq!(keys.len(), 3);
+    assert!(value.get("temperature").is_none());
+    assert!(value.get("stream_options").is_none());
+}
+
+#[test]
+fn test_generation_params_serialization() {
+    let payload = ChatPayload {
+        model: "gpt-4o".to_string(),
+        messages: vec![],
+        stream: true,
+        stream_options: None,
+        params: GenerationParams {
+            temperature: Some(0.2),
+            max_tokens: Some(512),
+            stop: Some(vec!["###".to_string()]),
+            seed: Some(42),
+            response_format: Some(ResponseFormat::JsonObject),
+            ..Default::default()
+        },
+        tools: None,
+    };
+
+    let value = serde_json::to_value(&payload).unwrap();
+    assert_eq!(value["max_tokens"], 512);
+    assert_eq!(value["stop"][0], "###");
+    assert_eq!(value["seed"], 42);
+    assert_eq!(value["response_format"]["type"], "json_object");
+    assert!(value.get("top_p").is_none());
+
+    let deserialized: ChatPayload = serde_json::from_value(value).unwrap();
+    assert_eq!(deserialized.params, payload.params);
+}
+
+#[test]
+fn test_generation_params_merge_and_validate() {
+    let base = GenerationParams {
+        temperature: Some(0.7),
+        top_p: Some(0.9),
+        ..Default::default()
+    };
+    let request = GenerationParams {
+        temperature: Some(0.1),
+        ..Default::default()
+    };
+
+    let merged = request.merged_over(&base);
+    assert_eq!(merged.temperature, Some(0.1));
+    assert_eq!(merged.top_p, Some(0.9));
+    assert!(merged.validate().is_ok());
+
+    let invalid = GenerationParams {
+        temperature: Some(3.0),
+        ..Default::default()
+    };
+    assert!(invalid.validate().is_err());
+
+    let too_many_stops = GenerationParams {
+        stop: Some(vec!["a".into(), "b".into(), "c".into(), "d".into(), "e".into()]),
+        ..Default::default()
+    };
+    assert!(too_many_stops.validate().is_err());
+}
+
+#[test]
+fn test_reasoning_content_separated() {
+    let lines = [
+        r#"data: {"id":"r-1","model":"deepseek-reasoner","choices":[{"delta":{"reasoning_content":"先分析"},"finish_reason":null}]}"#,
+        r#"data: {"id":"r-1","choices":[{"delta":{"reasoning_content":"问题。","content":null},"finish_reason":null}]}"#,
+        r#"data: {"id":"r-1","choices":[{"delta":{"content":"答案是 42"},"finish_reason":"stop"}]}"#,
+    ];
+
+    let mut accumulator = StreamAccumulator::new();
+    let deltas: Vec<StreamDelta> = lines
+        .iter()
+        .filter_map(|line| parse_stream_line(line))
+        .map(|response| accumulator.push(response))
+        .collect();
+
+    assert_eq!(deltas[0], StreamDelta { reasoning: Some("先分析".to_string()), content: None });
+    assert_eq!(deltas[2], StreamDelta { reasoning: None, content: Some("答案是 42".to_string()) });
+
+    let result = accumulator.into_result("deepseek-reasoner", ChatTimings::default());
+    assert_eq!(result.content, "答案是 42");
+    assert_eq!(result.reasoning, Some("先分析问题。".to_string()));
+
+    let serialized = serde_json::to_value(&result).unwrap();
+    assert_eq!(serialized["reasoning"], "先分析问题。");
+}
+
+#[test]
+fn test_reasoning_alias_and_absent() {
+    let line = r#"data: {"choices":[{"delta":{"reasoning":"thinking"},"finish_reason":null}]}"#;
+    let response = parse_stream_line(line).unwrap();
+    assert_eq!(response.choices[0].delta.reasoning_content, Some("thinking".to_string()));
+
+    let result = StreamAccumulator::new().into_result("gpt-4o", ChatTimings::default());
+    assert!(result.reasoning.is_none());
+    assert!(serde_json::to_value(&result).unwrap().get("reasoning").is_none());
+}
@@ -0,0 +1,66 @@
+use chat_ai_lib::chat::{generate_message_id, ChatResult, ChatTimings, FinishReason, Usage};
+use chat_ai_lib::compare::{
+    delete_comparison, list_comparisons, load_comparison, save_comparison, validate_models, Comparison, ComparisonEntry,
+};
+
+fn result(model: &str, completion_tokens: u32) -> ChatResult {
+    ChatResult {
+        message_id: generate_message_id(),
+        response_id: None,
+        content: format!("{} 的回答", model),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: Some(Usage { prompt_tokens: 10, completion_tokens, total_tokens: 10 + completion_tokens }),
+        timings: ChatTimings { first_token_ms: Some(500), total_ms: 2500 },
+        model: model.to_string(),
+    }
+}
+
+#[test]
+fn test_comparison_entries() {
+    let models = vec!["gpt-4o".to_string(), "deepseek-chat".to_string()];
+    assert!(validate_models(&models).is_ok());
+    assert!(validate_models(&models[..1]).is_err());
+    assert!(validate_models(&["gpt-4o".to_string(), " ".to_string()]).is_err());
+    assert!(validate_models(&vec!["m".to_string(); 9]).is_err());
+
+    let mut comparison = Comparison::new("什么是闭包？", None);
+    assert_eq!(comparison.stream_id(1), format!("{}-1", comparison.id));
+
+    let ok = ComparisonEntry::new(&comparison.stream_id(0), "gpt-4o", Ok(result("gpt-4o", 100)));
+    assert_eq!(ok.content, "gpt-4o 的回答");
+    assert_eq!(ok.tokens_per_second, Some(50.0));
+    assert_eq!(ok.error, None);
+
+    let failed = ComparisonEntry::new(&comparison.stream_id(1), "deepseek-chat", Err("超时".to_string()));
+    assert_eq!(failed.error.as_deref(), Some("超时"));
+    assert_eq!(failed.tokens_per_second, None);
+
+    comparison.entries = vec![ok, failed];
+    assert_eq!(comparison.summary().models, models);
+}
+
+#[test]
+fn test_comparison_store() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-compare-{}", std::process::id()));
+    assert!(list_comparisons(&dir).unwrap().is_empty());
+
+    let mut first = Comparison::new("第一个问题", Some("session-1".to_string()));
+    first.created_at = 1000;
+    first.entries.push(ComparisonEntry::new(&first.stream_id(0), "gpt-4o", Ok(result("gpt-4o", 20))));
+    let mut second = Comparison::new("第二个问题", None);
+    second.created_at = 2000;
+    save_comparison(&dir, &first).unwrap();
+    save_comparison(&dir, &second).unwrap();
+
+    assert_eq!(load_comparison(&dir, &first.id).unwrap(), first);
+    let prompts: Vec<String> = list_comparisons(&dir).unwrap().into_iter().map(|s| s.prompt).collect();
+    assert_eq!(prompts, ["第二个问题", "第一个问题"]);
+    assert!(load_comparison(&dir, "../secret").is_err());
+
+    delete_comparison(&dir, &first.id).unwrap();
+    assert!(load_comparison(&dir, &first.id).is_err());
+    assert_eq!(list_comparisons(&dir).unwrap().len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
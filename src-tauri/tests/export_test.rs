@@ -0,0 +1,86 @@
+use std::path::Path;
+use chat_ai_lib::attachment::ImageAttachment;
+use chat_ai_lib::chat::{generate_message_id, ChatResult, ChatTimings, FinishReason};
+use chat_ai_lib::export::{export_session, render_markdown, to_html, to_json, to_markdown, to_pdf, ExportFormat};
+use chat_ai_lib::session::Session;
+
+fn answer(content: &str) -> ChatResult {
+    ChatResult {
+        message_id: generate_message_id(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: "gpt-4o".to_string(),
+    }
+}
+
+fn sample_session() -> Session {
+    let mut session = Session::new("Rust question", None);
+    let image = ImageAttachment {
+        name: "screen.png".to_string(),
+        mime_type: "image/png".to_string(),
+        width: 640,
+        height: 480,
+        data_url: "data:image/png;base64,AAAA".to_string(),
+    };
+    session.push_exchange_with_attachments(
+        "How do I print? <script>alert(1)</script>",
+        vec![image],
+        vec![],
+        &answer("Use the macro:\n\n```rust\nfn main() { println!(\"hi\"); }\n```"),
+    );
+    session
+}
+
+#[test]
+fn test_export_text_formats() {
+    let session = sample_session();
+
+    let markdown = to_markdown(&session);
+    assert!(markdown.starts_with("# Rust question\n"));
+    assert!(markdown.contains("模型：gpt-4o"));
+    assert!(markdown.contains("### 助手 · "));
+    assert!(markdown.contains("> 图片：screen.png（640×480）"));
+    assert!(markdown.contains("```rust\nfn main()"));
+
+    let html = to_html(&session);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("<img src=\"data:image/png;base64,AAAA\""));
+    // 代码块经过语法高亮，使用内联样式
+    assert!(html.contains("<pre style="));
+    assert!(render_markdown("`x`").contains("<code>x</code>"));
+
+    let json = to_json(&session).unwrap();
+    assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session);
+
+    assert_eq!(ExportFormat::from_path(Path::new("a.MD")), Some(ExportFormat::Markdown));
+    assert_eq!(ExportFormat::from_path(Path::new("a.htm")), Some(ExportFormat::Html));
+    assert_eq!(ExportFormat::from_path(Path::new("a.txt")), None);
+}
+
+#[test]
+fn test_export_pdf() {
+    let session = sample_session();
+    assert!(to_pdf(&session, b"not a font").is_err());
+
+    // 需要系统中有可嵌入的 TrueType 字体
+    let font_path = Path::new("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
+    if !font_path.exists() {
+        return;
+    }
+    let pdf = to_pdf(&session, &std::fs::read(font_path).unwrap()).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("chat-ai-export-{}.pdf", std::process::id()));
+    export_session(&session, &path, None, Some(font_path)).unwrap();
+    assert!(std::fs::read(&path).unwrap().starts_with(b"%PDF"));
+    std::fs::remove_file(&path).unwrap();
+    assert!(export_session(&session, &dir.join("export.txt"), None, Some(font_path)).is_err());
+}
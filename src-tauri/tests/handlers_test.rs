@@ -6,7 +6,7 @@ use chat_ai_lib::handlers::{
     get_api_url,
     remove_api_url,
 };
-use chat_ai_lib::chat::{ChatMessage, ChatPayload};
+use chat_ai_lib::chat::{ChatMessage, ChatPayload, GenerationParams};
 use chat_ai_lib::models::{ModelsResponse, ModelData, AvailableModelsResponse};
 
 #[test]
@@ -54,7 +54,8 @@ fn test_chat_payload() {
     let messages = vec![
         ChatMessage {
             role: "user".to_string(),
-            content: "Hello".to_string(),
+            content: "Hello".into(),
+            ..Default::default()
         }
     ];
     
@@ -62,6 +63,9 @@ fn test_chat_payload() {
         model: "gpt-3.5-turbo".to_string(),
         messages: messages.clone(),
         stream: true,
+        stream_options: None,
+        params: GenerationParams::default(),
+        tools: None,
     };
     
     assert_eq!(payload.model, "gpt-3.5-turbo");
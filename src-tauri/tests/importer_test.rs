@@ -0,0 +1,122 @@
+use std::fs;
+use serde_json::json;
+use chat_ai_lib::importer::{import_file, parse_chatgpt_conversation, parse_import, ImportFormat};
+
+/// ChatGPT 导出中的一个会话：第二个问题被编辑过，`current_node` 指向编辑后的分支
+fn chatgpt_conversation() -> serde_json::Value {
+    json!({
+        "title": "排序算法",
+        "create_time": 1700000000.5,
+        "update_time": 1700000100.0,
+        "conversation_id": "conv-1",
+        "current_node": "a2",
+        "mapping": {
+            "root": { "id": "root", "message": null, "parent": null, "children": ["sys"] },
+            "sys": {
+                "id": "sys",
+                "message": { "author": { "role": "system" }, "content": { "content_type": "text", "parts": [""] }, "metadata": { "is_visually_hidden_from_conversation": true } },
+                "parent": "root", "children": ["u1"]
+            },
+            "u1": {
+                "id": "u1",
+                "message": { "author": { "role": "user" }, "create_time": 1700000001.0, "content": { "content_type": "text", "parts": ["什么是快速排序？"] } },
+                "parent": "sys", "children": ["a1"]
+            },
+            "a1": {
+                "id": "a1",
+                "message": { "author": { "role": "assistant" }, "content": { "content_type": "text", "parts": ["一种分治算法"] }, "metadata": { "model_slug": "gpt-4o" } },
+                "parent": "u1", "children": ["u2-old", "u2"]
+            },
+            "u2-old": {
+                "id": "u2-old",
+                "message": { "author": { "role": "user" }, "content": { "content_type": "text", "parts": ["旧问题"] } },
+                "parent": "a1", "children": []
+            },
+            "u2": {
+                "id": "u2",
+                "message": { "author": { "role": "user" }, "content": { "content_type": "multimodal_text", "parts": [{ "content_type": "image_asset_pointer" }, "复杂度是多少？"] } },
+                "parent": "a1", "children": ["tool", "a2"]
+            },
+            "tool": {
+                "id": "tool",
+                "message": { "author": { "role": "tool" }, "content": { "content_type": "text", "parts": ["搜索结果"] } },
+                "parent": "u2", "children": []
+            },
+            "a2": {
+                "id": "a2",
+                "message": { "author": { "role": "assistant" }, "content": { "content_type": "code", "text": "O(n log n)" }, "metadata": { "model_slug": "gpt-4o" } },
+                "parent": "u2", "children": []
+            }
+        }
+    })
+}
+
+#[test]
+fn test_parse_chatgpt_branch() {
+    let session = parse_chatgpt_conversation(&chatgpt_conversation()).unwrap();
+    assert_eq!(session.title, "排序算法");
+    assert_eq!(session.created_at, 1700000000500);
+    assert_eq!(session.updated_at, 1700000100000);
+    assert_eq!(session.import_source.as_deref(), Some("chatgpt:conv-1"));
+
+    let contents: Vec<&str> = session.messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["什么是快速排序？", "一种分治算法", "复杂度是多少？", "O(n log n)"]);
+    assert_eq!(session.messages[0].created_at, 1700000001000);
+    assert_eq!(session.messages[1].model.as_deref(), Some("gpt-4o"));
+    assert_eq!(session.messages[2].model, None);
+
+    assert!(parse_chatgpt_conversation(&json!({ "title": "空" })).is_err());
+}
+
+#[test]
+fn test_parse_jsonl() {
+    let content = [
+        r#"{"messages":[{"role":"system","content":"你是助手"},{"role":"user","content":"你好，请介绍一下你自己"},{"role":"assistant","content":[{"type":"text","text":"我是助手"}]}],"model":"gpt-4o-mini"}"#,
+        "",
+        "not json",
+        r#"{"messages":[]}"#,
+    ]
+    .join("\n");
+    let (sessions, skipped) = parse_import(&content, ImportFormat::Jsonl).unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].title, "你好，请介绍一下你自己");
+    assert_eq!(sessions[0].messages.len(), 3);
+    assert_eq!(sessions[0].messages[2].content, "我是助手");
+    assert_eq!(sessions[0].messages[2].model.as_deref(), Some("gpt-4o-mini"));
+    assert_eq!(skipped.len(), 2);
+    assert!(skipped[0].starts_with("第 3 行"));
+}
+
+#[test]
+fn test_import_dry_run_and_dedup() {
+    let path = std::env::temp_dir().join(format!("chat-ai-import-{}.json", std::process::id()));
+    let conversations = json!([chatgpt_conversation(), chatgpt_conversation(), { "title": "损坏的会话" }]);
+    fs::write(&path, conversations.to_string()).unwrap();
+
+    let mut saved = Vec::new();
+    let report = import_file(&path, None, &[], true, |session| {
+        saved.push(session.clone());
+        Ok(())
+    })
+    .unwrap();
+    assert!(report.dry_run);
+    assert!(saved.is_empty());
+    assert_eq!(report.imported.len(), 1);
+    assert_eq!(report.imported[0].message_count, 4);
+    assert_eq!(report.duplicates, ["排序算法"]);
+    assert_eq!(report.skipped.len(), 1);
+
+    import_file(&path, Some(ImportFormat::Chatgpt), &[], false, |session| {
+        saved.push(session.clone());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(saved.len(), 1);
+
+    // 再次导入时与已有会话去重
+    let report = import_file(&path, None, &saved, false, |_| panic!("不应保存重复的会话")).unwrap();
+    assert!(report.imported.is_empty());
+    assert_eq!(report.duplicates.len(), 2);
+
+    fs::remove_file(&path).unwrap();
+}
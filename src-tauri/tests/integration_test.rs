@@ -1,6 +1,6 @@
 use chat_ai_lib::{
-    cache::{self, get_cache_dir, update_frequency, save_frequencies},
-    chat::{ChatMessage, ChatPayload},
+    cache::{get_cache_dir, update_frequency, save_frequencies},
+    chat::{ChatMessage, ChatPayload, GenerationParams},
     handlers::{
         save_api_key,
         get_api_key,
@@ -9,6 +9,7 @@ use chat_ai_lib::{
         get_api_url,
         remove_api_url,
     },
+    storage::with_storage,
 };
 
 #[test]
@@ -24,7 +25,8 @@ fn test_complete_workflow() {
     // 3. 创建聊天消息
     let message = ChatMessage {
         role: "user".to_string(),
-        content: "Test integration message".to_string(),
+        content: "Test integration message".into(),
+        ..Default::default()
     };
     
     // 4. 创建聊天请求
@@ -32,6 +34,9 @@ fn test_complete_workflow() {
         model: "gpt-3.5-turbo".to_string(),
         messages: vec![message],
         stream: true,
+        stream_options: None,
+        params: GenerationParams::default(),
+        tools: None,
     };
     
     // 5. 更新模型使用频率
@@ -55,9 +60,10 @@ fn test_cache_operations() {
     // 3. 保存频率数据
     save_frequencies();
     
-    // 4. 验证频率文件存在
-    let frequency_file = cache_dir.join("frequency.json");
-    assert!(frequency_file.exists());
+    // 4. 验证频率数据已写入数据库
+    let stored = with_storage(|storage| storage.load_models()).unwrap();
+    assert!(stored.contains_key("gpt-3.5-turbo"));
+    assert!(stored.contains_key("gpt-4"));
 }
 
 #[test]
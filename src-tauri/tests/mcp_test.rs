@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use chat_ai_lib::chat::{FunctionCall, ToolCall};
+use chat_ai_lib::mcp::{
+    attach_client, disconnect_server, exposed_tool_name, get_prompt, McpClient, McpServerConfig, McpTransportConfig,
+    StdioTransport,
+};
+use chat_ai_lib::tools::{execute_tool_call, registry_snapshot};
+
+/// 本地测试服务：提供 echo 工具、一个文本资源和一个提示词
+fn handle_request(method: &str, params: &Value) -> Result<Value, (i64, &'static str)> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": { "tools": {}, "resources": {}, "prompts": {} },
+            "serverInfo": { "name": "test-server", "version": "1.0.0" }
+        })),
+        "tools/list" if params.get("cursor").is_none() => Ok(json!({
+            "tools": [{
+                "name": "echo",
+                "description": "原样返回文本",
+                "inputSchema": { "type": "object", "properties": { "text": { "type": "string" } }, "required": ["text"] }
+            }],
+            "nextCursor": "page-2"
+        })),
+        "tools/list" => Ok(json!({
+            "tools": [{ "name": "fail", "inputSchema": { "type": "object" } }]
+        })),
+        "tools/call" => match params["name"].as_str() {
+            Some("echo") => Ok(json!({ "content": [{ "type": "text", "text": params["arguments"]["text"] }] })),
+            _ => Ok(json!({ "content": [{ "type": "text", "text": "出错了" }], "isError": true })),
+        },
+        "resources/list" => Ok(json!({
+            "resources": [{ "uri": "file:///readme.md", "name": "readme", "mimeType": "text/markdown" }]
+        })),
+        "resources/read" => Ok(json!({
+            "contents": [{ "uri": params["uri"], "text": "# 说明" }]
+        })),
+        "prompts/list" => Ok(json!({
+            "prompts": [{ "name": "review", "arguments": [{ "name": "code", "required": true }] }]
+        })),
+        "prompts/get" => Ok(json!({
+            "messages": [{ "role": "user", "content": { "type": "text", "text": format!("请审查: {}", params["arguments"]["code"].as_str().unwrap_or_default()) } }]
+        })),
+        _ => Err((-32601, "Method not found")),
+    }
+}
+
+/// 在内存管道上启动测试服务，返回连接到该服务的客户端
+fn start_test_server() -> McpClient {
+    let (client_side, server_side) = tokio::io::duplex(64 * 1024);
+    let (server_reader, mut server_writer) = tokio::io::split(server_side);
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(server_reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = request.get("id") else {
+                continue;
+            };
+            // 先推送一条通知和一行日志，客户端应跳过
+            server_writer
+                .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"notifications/message\",\"params\":{}}\nnot json\n")
+                .await
+                .unwrap();
+            let response = match handle_request(request["method"].as_str().unwrap_or_default(), &request["params"]) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+            };
+            let mut out = serde_json::to_string(&response).unwrap();
+            out.push('\n');
+            server_writer.write_all(out.as_bytes()).await.unwrap();
+        }
+    });
+
+    let (client_reader, client_writer) = tokio::io::split(client_side);
+    McpClient::new(Box::new(StdioTransport::new(client_reader, client_writer)))
+}
+
+fn call(id: &str, name: &str, arguments: &str) -> ToolCall {
+    ToolCall {
+        id: id.to_string(),
+        kind: "function".to_string(),
+        function: FunctionCall {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_mcp_client_discovery() {
+    let client = start_test_server();
+    let info = client.initialize().await.unwrap();
+    assert_eq!(info.server_info.name, "test-server");
+
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["echo", "fail"]);
+    assert_eq!(client.call_tool("echo", json!({ "text": "你好" })).await.unwrap(), "你好");
+    assert_eq!(client.call_tool("fail", json!({})).await.unwrap_err(), "出错了");
+
+    let resources = client.list_resources().await.unwrap();
+    assert_eq!(resources[0].mime_type.as_deref(), Some("text/markdown"));
+    assert_eq!(client.read_resource("file:///readme.md").await.unwrap(), "# 说明");
+
+    let prompts = client.list_prompts().await.unwrap();
+    assert!(prompts[0].arguments[0].required);
+    let arguments = HashMap::from([("code".to_string(), "fn main() {}".to_string())]);
+    let messages = client.get_prompt("review", &arguments).await.unwrap();
+    assert_eq!(messages[0].role, "user");
+    assert_eq!(messages[0].content, "请审查: fn main() {}");
+}
+
+#[tokio::test]
+async fn test_mcp_tools_registered() {
+    let status = attach_client("local", start_test_server(), false).await.unwrap();
+    assert_eq!(status.tools, ["local__echo", "local__fail", "local__read_resource"]);
+    assert_eq!(status.prompts[0].name, "review");
+
+    let registry = registry_snapshot();
+    assert!(!registry.requires_approval("local__echo"));
+    let outcome = execute_tool_call(&registry, &call("call_1", "local__echo", r#"{"text":"hi"}"#)).await;
+    assert!(!outcome.is_error);
+    assert_eq!(outcome.content, "hi");
+    let outcome = execute_tool_call(&registry, &call("call_2", "local__read_resource", r#"{"uri":"file:///other"}"#)).await;
+    assert!(outcome.is_error);
+
+    let arguments = HashMap::from([("code".to_string(), "x".to_string())]);
+    assert_eq!(get_prompt("local", "review", &arguments).await.unwrap().len(), 1);
+
+    assert!(disconnect_server("local").await);
+    assert!(registry_snapshot().get("local__echo").is_none());
+    assert!(get_prompt("local", "review", &arguments).await.is_err());
+}
+
+#[test]
+fn test_mcp_config() {
+    let config: McpServerConfig = serde_json::from_value(json!({
+        "name": "files",
+        "transport": { "type": "stdio", "command": "npx", "args": ["-y", "server-filesystem"] }
+    }))
+    .unwrap();
+    assert!(config.enabled);
+    assert!(config.require_approval);
+    assert!(matches!(config.transport, McpTransportConfig::Stdio { ref args, .. } if args.len() == 2));
+    assert!(config.validate().is_ok());
+
+    let invalid = McpServerConfig {
+        name: "bad name".to_string(),
+        ..config.clone()
+    };
+    assert!(invalid.validate().is_err());
+    let invalid = McpServerConfig {
+        transport: McpTransportConfig::Http {
+            url: "ftp://example.com".to_string(),
+            headers: HashMap::new(),
+        },
+        ..config
+    };
+    assert!(invalid.validate().is_err());
+
+    assert_eq!(exposed_tool_name("files", "read.file"), "files__read_file");
+    assert_eq!(exposed_tool_name("s", &"x".repeat(100)).len(), 64);
+}
@@ -0,0 +1,140 @@
+use chat_ai_lib::organize::{
+    apply_action, create_folder, descendant_ids, expired_trash, move_folder, normalize_tags, purge_expired_trash,
+    purge_session, query_sessions, remove_folder, rename_folder, tag_counts, BulkAction, FolderFilter, SessionQuery, TagCount,
+};
+use chat_ai_lib::session::Session;
+use chat_ai_lib::storage::{SqliteStorage, Storage};
+
+const DAY: i64 = 24 * 60 * 60 * 1000;
+
+#[test]
+fn test_nested_folders() {
+    let mut folders = Vec::new();
+    let work = create_folder(&mut folders, " 工作 ", None).unwrap();
+    assert_eq!(work.name, "工作");
+    let project = create_folder(&mut folders, "项目", Some(work.id.clone())).unwrap();
+    let notes = create_folder(&mut folders, "笔记", Some(project.id.clone())).unwrap();
+    assert!(create_folder(&mut folders, "工作", None).is_err());
+    assert!(create_folder(&mut folders, "", None).is_err());
+    assert!(create_folder(&mut folders, "其他", Some("folder-missing".to_string())).is_err());
+
+    let ids = descendant_ids(&folders, &work.id);
+    assert_eq!(ids.len(), 3);
+    assert!(ids.contains(&notes.id));
+
+    // 不能移动到自己的子文件夹中
+    assert!(move_folder(&mut folders, &work.id, Some(notes.id.clone())).is_err());
+    let moved = move_folder(&mut folders, &notes.id, None).unwrap();
+    assert_eq!(moved.parent_id, None);
+    assert_eq!(rename_folder(&mut folders, &notes.id, "随笔").unwrap().name, "随笔");
+    assert!(rename_folder(&mut folders, &notes.id, "工作").is_err());
+
+    move_folder(&mut folders, &notes.id, Some(project.id.clone())).unwrap();
+    assert_eq!(remove_folder(&mut folders, &project.id).unwrap(), Some(work.id.clone()));
+    assert_eq!(folders.len(), 2);
+    assert_eq!(folders[1].parent_id.as_deref(), Some(work.id.as_str()));
+}
+
+#[test]
+fn test_query_and_bulk_actions() {
+    let mut folders = Vec::new();
+    let work = create_folder(&mut folders, "工作", None).unwrap();
+    let project = create_folder(&mut folders, "项目", Some(work.id.clone())).unwrap();
+
+    let mut sessions: Vec<Session> = (0..4)
+        .map(|i| {
+            let mut session = Session::new(&format!("会话 {}", i), None);
+            session.updated_at = i;
+            session
+        })
+        .collect();
+    assert!(apply_action(&mut sessions[0], &BulkAction::Move { folder_id: Some(work.id.clone()) }, 0));
+    apply_action(&mut sessions[1], &BulkAction::Move { folder_id: Some(project.id.clone()) }, 0);
+    let tags = BulkAction::AddTags { tags: vec!["rust".to_string(), " rust ".to_string(), "".to_string()] };
+    assert!(apply_action(&mut sessions[1], &tags, 0));
+    assert!(!apply_action(&mut sessions[1], &tags, 0));
+    assert_eq!(sessions[1].tags, ["rust"]);
+    apply_action(&mut sessions[2], &BulkAction::AddTags { tags: vec!["rust".to_string(), "ai".to_string()] }, 0);
+    apply_action(&mut sessions[0], &BulkAction::Pin, 0);
+    apply_action(&mut sessions[2], &BulkAction::Archive, 0);
+    assert!(apply_action(&mut sessions[3], &BulkAction::Trash, 100));
+    assert!(!apply_action(&mut sessions[3], &BulkAction::Trash, 200));
+    assert_eq!(sessions[3].deleted_at, Some(100));
+    // 整理操作不影响最近更新时间
+    assert_eq!(sessions[0].updated_at, 0);
+
+    let titles = |query: SessionQuery| -> Vec<String> {
+        query_sessions(&sessions, &folders, &query).into_iter().map(|s| s.title).collect()
+    };
+    // 置顶的在前，回收站中的不出现
+    assert_eq!(titles(SessionQuery::default()), ["会话 0", "会话 2", "会话 1"]);
+    assert_eq!(titles(SessionQuery { archived: Some(false), ..Default::default() }), ["会话 0", "会话 1"]);
+    assert_eq!(titles(SessionQuery { tags: vec!["rust".to_string()], ..Default::default() }), ["会话 2", "会话 1"]);
+    assert_eq!(
+        titles(SessionQuery { tags: vec!["rust".to_string(), "ai".to_string()], ..Default::default() }),
+        ["会话 2"]
+    );
+    let folder = |include_subfolders| SessionQuery {
+        folder: Some(FolderFilter::Folder { id: work.id.clone(), include_subfolders }),
+        ..Default::default()
+    };
+    assert_eq!(titles(folder(false)), ["会话 0"]);
+    assert_eq!(titles(folder(true)), ["会话 0", "会话 1"]);
+    assert_eq!(titles(SessionQuery { folder: Some(FolderFilter::Root), ..Default::default() }), ["会话 2"]);
+    assert_eq!(titles(SessionQuery { trashed: true, ..Default::default() }), ["会话 3"]);
+    assert_eq!(titles(SessionQuery { title: Some("会话 1".to_string()), ..Default::default() }), ["会话 1"]);
+
+    let query: SessionQuery = serde_json::from_str(r#"{"folder":{"type":"root"},"pinned":false}"#).unwrap();
+    assert_eq!(query.folder, Some(FolderFilter::Root));
+    let action: BulkAction = serde_json::from_str(r#"{"action":"remove_tags","tags":["rust"]}"#).unwrap();
+    assert!(apply_action(&mut sessions[2], &action, 0));
+    assert_eq!(sessions[2].tags, ["ai"]);
+
+    assert_eq!(
+        tag_counts(&sessions),
+        [TagCount { tag: "ai".to_string(), count: 1 }, TagCount { tag: "rust".to_string(), count: 1 }]
+    );
+    assert_eq!(normalize_tags(&["b".to_string(), "a".to_string(), "b".to_string()]), ["b", "a"]);
+
+    assert!(apply_action(&mut sessions[3], &BulkAction::Restore, 0));
+    assert_eq!(sessions[3].deleted_at, None);
+}
+
+#[test]
+fn test_trash_expiry() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let now = 100 * DAY;
+    let mut old = Session::new("很久以前删除", None);
+    old.deleted_at = Some(now - 31 * DAY);
+    let mut recent = Session::new("刚删除", None);
+    recent.deleted_at = Some(now - DAY);
+    let kept = Session::new("未删除", None);
+    for session in [&old, &recent, &kept] {
+        storage.save_session(session).unwrap();
+    }
+
+    let sessions = storage.list_sessions().unwrap();
+    assert_eq!(expired_trash(&sessions, 30, now).len(), 1);
+    assert!(expired_trash(&sessions, 0, now).is_empty());
+
+    assert_eq!(purge_expired_trash(&mut storage, 30, now).unwrap(), [old.id.clone()]);
+    let remaining: Vec<String> = storage.list_sessions().unwrap().into_iter().map(|s| s.title).collect();
+    assert_eq!(remaining.len(), 2);
+    assert!(!remaining.contains(&"很久以前删除".to_string()));
+}
+
+#[test]
+fn test_purge_requires_trash() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let mut session = Session::new("会话", None);
+    storage.save_session(&session).unwrap();
+
+    // 未移入回收站的会话不能永久删除
+    assert!(purge_session(&mut storage, &session.id).is_err());
+    assert!(storage.load_session(&session.id).is_ok());
+
+    apply_action(&mut session, &BulkAction::Trash, 100);
+    storage.save_session(&session).unwrap();
+    purge_session(&mut storage, &session.id).unwrap();
+    assert!(storage.list_sessions().unwrap().is_empty());
+}
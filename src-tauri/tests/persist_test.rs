@@ -0,0 +1,106 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use chat_ai_lib::cache::{read_json_file, update_json_file};
+use chat_ai_lib::persist::{write_atomic, write_atomic_with, FileLock};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chat-ai-persist-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_interrupted_write_keeps_original() {
+    let dir = temp_dir("write");
+    let path = dir.join("nested").join("profiles.json");
+    write_atomic(&path, r#"{"default":{}}"#).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"default":{}}"#);
+
+    // 写到一半出错：目标文件保持原样，临时文件被清理
+    let result = write_atomic_with(&path, |file| {
+        file.write_all(br#"{"default":{"par"#)?;
+        Err(io::Error::other("磁盘已满"))
+    });
+    assert!(result.unwrap_err().contains("磁盘已满"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"default":{}}"#);
+    assert_eq!(file_names(path.parent().unwrap()), ["profiles.json"]);
+
+    write_atomic(&path, "{}").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+    // 多个线程同时写入，最终内容是其中某一次的完整内容
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let path = path.clone();
+            thread::spawn(move || write_atomic(&path, i.to_string().repeat(4096)).unwrap())
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let content = fs::read_to_string(&path).unwrap();
+    assert_eq!(content.len(), 4096);
+    assert!(content.chars().all(|c| c == content.chars().next().unwrap()));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_update_json_file() {
+    let dir = temp_dir("update");
+    let path = dir.join("counters.json");
+
+    // 多个线程同时读-改-写，不会丢失更新
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let path = path.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    update_json_file(&path, |count: &mut u32| {
+                        *count += 1;
+                        Ok(())
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(read_json_file::<u32>(&path).unwrap(), 100);
+
+    // 修改失败时不写回
+    let result = update_json_file(&path, |count: &mut u32| {
+        *count = 0;
+        Err::<(), _>("失败".to_string())
+    });
+    assert!(result.is_err());
+    assert_eq!(read_json_file::<u32>(&path).unwrap(), 100);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_async_lock_waits_for_holder() {
+    let dir = temp_dir("lock");
+    let path = dir.join("index.json");
+    let held = FileLock::acquire(&path).unwrap();
+
+    // 锁被占用时一直等待，释放后立即获得
+    let waiting = tokio::time::timeout(std::time::Duration::from_millis(100), FileLock::acquire_async(&path)).await;
+    assert!(waiting.is_err());
+    drop(held);
+    let lock = tokio::time::timeout(std::time::Duration::from_secs(5), FileLock::acquire_async(&path)).await;
+    assert!(lock.unwrap().is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
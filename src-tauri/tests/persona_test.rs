@@ -0,0 +1,101 @@
+use chat_ai_lib::chat::{ChatMessage, GenerationParams};
+use chat_ai_lib::persona::{
+    apply_system_prompt, build_pack, load_personas, merge_pack, upsert_persona, Persona,
+    PersonaPack, PERSONAS_KEY, PERSONA_PACK_VERSION,
+};
+use chat_ai_lib::storage::{update_setting, SqliteStorage};
+
+fn persona(name: &str, prompt: &str) -> Persona {
+    Persona {
+        name: name.to_string(),
+        system_prompt: prompt.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_persona_validate() {
+    assert!(persona("翻译", "你是一名专业翻译").validate().is_ok());
+    assert!(persona("", "你是一名专业翻译").validate().is_err());
+    assert!(persona("翻译", "  ").validate().is_err());
+
+    let mut invalid_params = persona("翻译", "你是一名专业翻译");
+    invalid_params.params = GenerationParams {
+        top_p: Some(1.5),
+        ..Default::default()
+    };
+    assert!(invalid_params.validate().is_err());
+}
+
+#[test]
+fn test_apply_system_prompt() {
+    let reviewer = persona("reviewer", "You review Rust code.");
+    let mut messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: "fn main() {}".into(),
+        ..Default::default()
+    }];
+
+    apply_system_prompt(&reviewer, &mut messages);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].role, "system");
+    assert_eq!(messages[0].content, "You review Rust code.");
+
+    // 已有系统消息时不重复插入
+    apply_system_prompt(&reviewer, &mut messages);
+    assert_eq!(messages.len(), 2);
+}
+
+#[test]
+fn test_upsert_and_store() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let mut personas = vec![persona("a", "first")];
+    upsert_persona(&mut personas, persona("b", "second"));
+    upsert_persona(&mut personas, persona("a", "updated"));
+    assert_eq!(personas.len(), 2);
+    assert_eq!(personas[0].system_prompt, "updated");
+
+    update_setting(&mut storage, PERSONAS_KEY, |stored: &mut Vec<Persona>| {
+        *stored = personas.clone();
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(load_personas(&storage).unwrap(), personas);
+    // 修改失败时不保存
+    assert!(update_setting(&mut storage, PERSONAS_KEY, |stored: &mut Vec<Persona>| {
+        stored.clear();
+        Err::<(), _>("失败".to_string())
+    })
+    .is_err());
+    assert_eq!(load_personas(&storage).unwrap(), personas);
+}
+
+#[test]
+fn test_pack_round_trip() {
+    let personas = vec![persona("a", "first"), persona("b", "second")];
+    let pack = build_pack(&personas, Some(&["b".to_string()]));
+    assert_eq!(pack.version, PERSONA_PACK_VERSION);
+    assert_eq!(pack.personas.len(), 1);
+
+    let mut existing = vec![persona("b", "local"), persona("c", "third")];
+    let incoming = PersonaPack {
+        version: PERSONA_PACK_VERSION,
+        personas: vec![persona("b", "imported"), persona("d", "fourth"), persona("", "invalid")],
+    };
+
+    let report = merge_pack(&mut existing, incoming.clone(), false).unwrap();
+    assert_eq!(report.imported, vec!["d"]);
+    assert_eq!(report.skipped, vec!["b", ""]);
+    assert_eq!(existing[0].system_prompt, "local");
+
+    let report = merge_pack(&mut existing, incoming, true).unwrap();
+    assert_eq!(report.imported, vec!["b", "d"]);
+    assert_eq!(existing[0].system_prompt, "imported");
+    assert_eq!(existing.len(), 3);
+
+    let future = PersonaPack {
+        version: PERSONA_PACK_VERSION + 1,
+        personas: vec![],
+    };
+    assert!(merge_pack(&mut existing, future, true).is_err());
+}
@@ -0,0 +1,103 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use serde_json::{json, Value};
+use chat_ai_lib::provider::{endpoint_url, OpenAiProvider, Provider};
+
+/// 本地测试服务：为每条输入返回 `[字符数, 序号, 1]`，模型为 `bad` 时返回 400
+fn start_embedding_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+
+            let (status, response) = if request["model"] == "bad" {
+                ("400 Bad Request", json!({ "error": { "message": "model not found" } }))
+            } else {
+                let inputs = request["input"].as_array().unwrap();
+                // 倒序返回，客户端应按 index 排序
+                let data: Vec<Value> = inputs
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, text)| {
+                        let chars = text.as_str().unwrap().chars().count() as f32;
+                        json!({ "index": index, "embedding": [chars, index as f32, 1.0] })
+                    })
+                    .collect();
+                let response = json!({
+                    "model": "embedding-test",
+                    "data": data,
+                    "usage": { "prompt_tokens": inputs.len(), "total_tokens": inputs.len() }
+                });
+                ("200 OK", response)
+            };
+            let body = response.to_string();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    format!("http://{}/v1/chat/completions", address)
+}
+
+#[test]
+fn test_endpoint_url() {
+    assert_eq!(endpoint_url("https://api.deepseek.com/v1/chat/completions", "embeddings"), "https://api.deepseek.com/v1/embeddings");
+    assert_eq!(endpoint_url("https://api.openai.com/v1", "models"), "https://api.openai.com/v1/models");
+    assert_eq!(endpoint_url("https://api.openai.com/v1/", "models"), "https://api.openai.com/v1/models");
+    assert_eq!(endpoint_url("http://localhost:11434", "embeddings"), "http://localhost:11434/v1/embeddings");
+}
+
+#[tokio::test]
+async fn test_embed_in_batches() {
+    let api_url = start_embedding_server();
+    let provider = OpenAiProvider::new(&api_url, "test-key").with_batch_size(2);
+    let input: Vec<String> = ["a", "bb", "ccc", "你好"].iter().map(|s| s.to_string()).collect();
+
+    let result = provider.embed("text-embedding", input).await.unwrap();
+    assert_eq!(result.model, "embedding-test");
+    assert_eq!(result.dimensions, 3);
+    // 两个批次内分别按 index 排序，合并后与输入顺序一致
+    assert_eq!(
+        result.embeddings,
+        [vec![1.0, 0.0, 1.0], vec![2.0, 1.0, 1.0], vec![3.0, 0.0, 1.0], vec![2.0, 1.0, 1.0]]
+    );
+    let usage = result.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.total_tokens), (4, 4));
+}
+
+#[tokio::test]
+async fn test_embed_errors() {
+    let api_url = start_embedding_server();
+    let provider = OpenAiProvider::new(&api_url, "test-key");
+
+    let error = provider.embed("bad", vec!["hello".to_string()]).await.unwrap_err();
+    assert!(error.contains("400"));
+    assert!(error.contains("model not found"));
+
+    assert!(provider.embed("text-embedding", vec![]).await.is_err());
+    assert!(provider.embed("text-embedding", vec!["ok".to_string(), " ".to_string()]).await.is_err());
+}
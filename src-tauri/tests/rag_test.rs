@@ -0,0 +1,90 @@
+use std::cell::Cell;
+use std::fs;
+use chat_ai_lib::rag::{
+    compose_with_context, cosine_similarity, list_indexes, load_index, save_index, update_index, FolderIndex,
+    RetrievedChunk,
+};
+
+/// 测试用的嵌入：统计几个关键词出现的次数
+fn fake_embedding(text: &str) -> Vec<f32> {
+    ["rust", "python", "猫"]
+        .iter()
+        .map(|word| text.matches(word).count() as f32)
+        .collect()
+}
+
+#[test]
+fn test_cosine_similarity() {
+    assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
+
+#[tokio::test]
+async fn test_incremental_index_and_search() {
+    let root = std::env::temp_dir().join(format!("chat-ai-rag-{}", std::process::id()));
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::create_dir_all(root.join("node_modules")).unwrap();
+    fs::write(root.join("README.md"), "这个项目讲的是猫").unwrap();
+    fs::write(root.join("src").join("main.rs"), "fn main() { println!(\"rust rust\"); }").unwrap();
+    fs::write(root.join("node_modules").join("dep.js"), "python").unwrap();
+    fs::write(root.join(".env"), "SECRET=python").unwrap();
+    fs::write(root.join("logo.bin"), [0u8, 1, 2, 3]).unwrap();
+
+    let calls = Cell::new(0);
+    let embed = |input: Vec<String>| {
+        calls.set(calls.get() + input.len());
+        async move { Ok(input.iter().map(|text| fake_embedding(text)).collect()) }
+    };
+
+    let mut index = FolderIndex::new(root.clone(), "fake-embedding");
+    let report = update_index(&mut index, embed).await.unwrap();
+    assert_eq!(report.added, ["README.md", "src/main.rs"]);
+    assert_eq!(report.skipped, ["logo.bin"]);
+    assert_eq!(report.embedded_chunks, 2);
+    assert_eq!(index.summary().chunk_count, 2);
+
+    // 文件未变化时不重新生成向量
+    let report = update_index(&mut index, embed).await.unwrap();
+    assert!(!report.has_changes());
+    assert_eq!(report.unchanged, 3);
+    assert_eq!(calls.get(), 2);
+
+    fs::write(root.join("README.md"), "这个项目讲的是猫和 python 以及更多内容").unwrap();
+    fs::remove_file(root.join("src").join("main.rs")).unwrap();
+    let report = update_index(&mut index, embed).await.unwrap();
+    assert_eq!(report.updated, ["README.md"]);
+    assert_eq!(report.removed, ["src/main.rs"]);
+    assert_eq!(calls.get(), 3);
+
+    let results = index.search(&fake_embedding("python"), 5);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].path, "README.md");
+    assert!(results[0].score > 0.0);
+
+    let dir = root.join("indexes");
+    save_index(&dir, &index).unwrap();
+    assert_eq!(load_index(&dir, &index.id).unwrap(), index);
+    assert_eq!(list_indexes(&dir).unwrap()[0].file_count, 2);
+    assert!(load_index(&dir, "../escape").is_err());
+
+    index.set_model("other-model");
+    assert!(index.files.is_empty());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_compose_with_context() {
+    let chunks = vec![RetrievedChunk {
+        path: "docs/intro.md".to_string(),
+        chunk_index: 0,
+        text: "项目介绍\n".to_string(),
+        score: 0.9,
+    }];
+    let composed = compose_with_context("这个项目是做什么的？", &chunks);
+    assert!(composed.contains("<excerpt source=\"docs/intro.md#1\">\n项目介绍\n</excerpt>"));
+    assert!(composed.ends_with("这个项目是做什么的？"));
+    assert_eq!(compose_with_context("你好", &[]), "你好");
+}
@@ -0,0 +1,100 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use chat_ai_lib::chat::{ChatResult, ChatTimings, FinishReason};
+use chat_ai_lib::search::{build_snippet, parse_query, tokenize, SearchFilters, SearchIndex};
+use chat_ai_lib::session::Session;
+
+fn answer(content: &str, model: &str) -> ChatResult {
+    ChatResult {
+        message_id: chat_ai_lib::chat::generate_message_id(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: model.to_string(),
+    }
+}
+
+fn session(title: &str, exchanges: &[(&str, &str, &str)]) -> Session {
+    let mut session = Session::new(title, None);
+    for (question, reply, model) in exchanges {
+        session.push_exchange(question, &answer(reply, model));
+    }
+    session
+}
+
+#[test]
+fn test_tokenize_mixed_text() {
+    let terms: Vec<String> = tokenize("用Rust写数据库, Hello World!").into_iter().map(|t| t.term).collect();
+    assert_eq!(terms, ["用", "rust", "写数", "数据", "据库", "hello", "world"]);
+
+    let tokens = tokenize("中文");
+    assert_eq!((tokens[0].start, tokens[0].end), (0, 6));
+
+    let clauses = parse_query("rust \"连接 池\" 数据库");
+    assert_eq!(clauses.len(), 3);
+    assert_eq!(clauses[1].terms, ["连接", "池"]);
+    assert_eq!(clauses[2].terms, ["数据", "据库"]);
+}
+
+#[test]
+fn test_search_messages() {
+    let first = session(
+        "数据库",
+        &[
+            ("怎么设计数据库连接池？", "连接池可以复用数据库连接，减少建立连接的开销。连接池的大小要适中。", "deepseek-chat"),
+            ("Rust 有哪些连接池库？", "常用的有 r2d2 和 deadpool。", "gpt-4o"),
+        ],
+    );
+    let second = session("闲聊", &[("你喜欢猫吗？", "我喜欢小猫咪。", "deepseek-chat")]);
+
+    let mut index = SearchIndex::default();
+    index.sync(&[first.clone(), second.clone()]);
+    let filters = SearchFilters::default();
+
+    let hits = index.search("连接池", &filters, 10);
+    assert_eq!(hits.len(), 3);
+    // 命中次数更多的消息排在前面
+    assert!(hits[0].snippet.contains("<mark>连接池</mark>可以复用数据库连接"));
+    assert_eq!(hits[0].session_title, "数据库");
+
+    // 所有子句都需命中
+    assert_eq!(index.search("连接池 rust", &filters, 10).len(), 1);
+    // 短语要求相邻
+    assert_eq!(index.search("\"deadpool 常用\"", &filters, 10).len(), 0);
+    assert_eq!(index.search("\"r2d2 和 deadpool\"", &filters, 10).len(), 1);
+    // 单个汉字在双字词中查找
+    let hits = index.search("猫", &filters, 10);
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().any(|hit| hit.snippet == "我喜欢小<mark>猫</mark>咪。"));
+
+    let by_model = SearchFilters { model: Some("gpt-4o".to_string()), ..Default::default() };
+    assert_eq!(index.search("连接池", &by_model, 10).len(), 0);
+    assert_eq!(index.search("deadpool", &by_model, 10).len(), 1);
+
+    let by_session = SearchFilters { session_id: Some(second.id.clone()), ..Default::default() };
+    assert!(index.search("连接池", &by_session, 10).is_empty());
+
+    let today = Local.timestamp_millis_opt(first.created_at).unwrap().date_naive();
+    let future = SearchFilters { from: today.succ_opt(), ..Default::default() };
+    assert!(index.search("连接池", &future, 10).is_empty());
+    let until_today = SearchFilters { to: Some(today), from: NaiveDate::from_ymd_opt(2020, 1, 1), ..Default::default() };
+    assert_eq!(index.search("连接池", &until_today, 10).len(), 3);
+
+    // 删除的会话从索引中移除
+    index.sync(std::slice::from_ref(&second));
+    assert!(index.search("连接池", &filters, 10).is_empty());
+}
+
+#[test]
+fn test_build_snippet() {
+    let content = format!("{}<关键词>{}", "前".repeat(50), "后".repeat(50));
+    let start = content.find("关键词").unwrap();
+    let snippet = build_snippet(&content, &[(start, start + "关键词".len())]);
+    assert!(snippet.starts_with('…'));
+    assert!(snippet.ends_with('…'));
+    assert!(snippet.contains("&lt;<mark>关键词</mark>&gt;"));
+    assert_eq!(snippet.chars().filter(|c| *c == '前').count(), 39);
+}
@@ -0,0 +1,80 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use chat_ai_lib::chat::{generate_message_id, ChatResult, ChatTimings, FinishReason};
+use chat_ai_lib::semantic::{load_message_vectors, save_message_vectors, update_message_vectors, MessageVectorStore};
+use chat_ai_lib::session::Session;
+
+/// 测试用的嵌入：统计几个关键词出现的次数
+fn fake_embedding(text: &str) -> Vec<f32> {
+    ["数据库", "猫", "旅行"]
+        .iter()
+        .map(|word| text.matches(word).count() as f32)
+        .collect()
+}
+
+fn answer(content: &str) -> ChatResult {
+    ChatResult {
+        message_id: generate_message_id(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: "test-model".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_incremental_message_vectors() {
+    let mut first = Session::new("数据库", None);
+    first.push_exchange("怎么优化数据库查询？", &answer("给数据库加索引"));
+    let mut second = Session::new("宠物", None);
+    second.push_exchange("猫为什么喜欢纸箱", &answer("纸箱让猫有安全感"));
+
+    let calls = Cell::new(0);
+    let embed = |input: Vec<String>| {
+        calls.set(calls.get() + input.len());
+        async move { Ok(input.iter().map(|text| fake_embedding(text)).collect()) }
+    };
+
+    let mut store = MessageVectorStore::new("fake-embedding");
+    let report = update_message_vectors(&mut store, &[first.clone(), second.clone()], embed).await.unwrap();
+    assert_eq!(report.embedded, 4);
+    assert_eq!(store.summary().message_count, 4);
+
+    // 只更新新写入和内容变化的消息，其他会话不受影响
+    first.push_exchange("周末去哪旅行", &answer("可以去海边旅行"));
+    let mut continued = answer("给数据库加索引，并避免全表扫描");
+    continued.message_id = first.messages[1].id.clone();
+    first.update_message(&continued);
+    let report = update_message_vectors(&mut store, std::slice::from_ref(&first), embed).await.unwrap();
+    assert_eq!((report.embedded, report.unchanged, report.removed), (3, 1, 0));
+    assert_eq!(calls.get(), 7);
+    assert_eq!(store.summary().message_count, 6);
+
+    let sessions = vec![first.clone(), second.clone()];
+    let hits = store.search(&sessions, &fake_embedding("猫"), 5);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].session_title, "宠物");
+    assert_eq!(hits[0].question.as_deref(), Some("猫为什么喜欢纸箱"));
+    assert_eq!(hits[0].answer.as_deref(), Some("纸箱让猫有安全感"));
+
+    // 问题和回答都命中时同一轮问答只返回一次
+    let hits = store.search(&sessions, &fake_embedding("数据库"), 5);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].answer.as_deref(), Some("给数据库加索引，并避免全表扫描"));
+
+    let kept: HashSet<&str> = HashSet::from([first.id.as_str()]);
+    assert_eq!(store.retain_sessions(&kept), 2);
+
+    let path = std::env::temp_dir().join(format!("chat-ai-vectors-{}.json", std::process::id()));
+    save_message_vectors(&path, &store).unwrap();
+    assert_eq!(load_message_vectors(&path).unwrap(), store);
+    std::fs::remove_file(&path).unwrap();
+
+    store.set_model("other-model");
+    assert!(store.messages.is_empty());
+    assert!(!MessageVectorStore::default().is_enabled());
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use chat_ai_lib::attachment::ImageAttachment;
+use chat_ai_lib::chat::{ChatResult, ChatTimings, FinishReason, GenerationParams};
+use chat_ai_lib::profile::{profile_params, Profile, DEFAULT_PROFILE};
+use chat_ai_lib::session::{load_session, save_session, update_session, Session};
+use chat_ai_lib::storage::{set_storage, SqliteStorage, Storage};
+
+fn result(id: &str, content: &str, finish_reason: FinishReason) -> ChatResult {
+    ChatResult {
+        message_id: id.to_string(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(finish_reason),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: "deepseek-chat".to_string(),
+    }
+}
+
+#[test]
+fn test_session_exchange_and_history() {
+    let mut session = Session::new("测试", None);
+    session.push_exchange("你好", &result("chatcmpl-1", "你好！", FinishReason::Stop));
+
+    let history = session.history();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].role, "user");
+    assert_eq!(history[0].content, "你好");
+    assert_eq!(history[1].role, "assistant");
+    assert_eq!(session.messages[1].id, "chatcmpl-1");
+    assert_eq!(session.messages[1].model.as_deref(), Some("deepseek-chat"));
+}
+
+#[test]
+fn test_session_update_message() {
+    let mut session = Session::new("测试", None);
+    session.push_exchange("写一首长诗", &result("chatcmpl-1", "第一段", FinishReason::Length));
+
+    assert!(session.update_message(&result("chatcmpl-1", "第一段第二段", FinishReason::Stop)));
+    assert_eq!(session.messages[1].content, "第一段第二段");
+    assert_eq!(session.messages[1].finish_reason, Some(FinishReason::Stop));
+
+    assert!(!session.update_message(&result("chatcmpl-unknown", "", FinishReason::Stop)));
+}
+
+#[test]
+fn test_session_store() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+
+    let mut first = Session::new("第一个", Some("work".to_string()));
+    first.updated_at = 1;
+    first.params.temperature = Some(0.3);
+    let mut second = Session::new("第二个", None);
+    second.updated_at = 2;
+    storage.save_session(&first).unwrap();
+    storage.save_session(&second).unwrap();
+
+    let loaded = storage.load_session(&first.id).unwrap();
+    assert_eq!(loaded, first);
+
+    let sessions = storage.list_sessions().unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].id, second.id);
+
+    storage.delete_session(&first.id).unwrap();
+    assert!(storage.load_session(&first.id).is_err());
+    assert!(storage.load_session("missing").is_err());
+}
+
+#[test]
+fn test_profile_params_fallback() {
+    let mut profiles = HashMap::new();
+    profiles.insert(DEFAULT_PROFILE.to_string(), Profile {
+        params: GenerationParams {
+            temperature: Some(0.7),
+            max_tokens: Some(1024),
+            ..Default::default()
+        },
+    });
+    profiles.insert("precise".to_string(), Profile {
+        params: GenerationParams {
+            temperature: Some(0.0),
+            ..Default::default()
+        },
+    });
+
+    let precise = profile_params(&profiles, Some("precise"));
+    assert_eq!(precise.temperature, Some(0.0));
+    assert_eq!(precise.max_tokens, Some(1024));
+
+    let unknown = profile_params(&profiles, Some("missing"));
+    assert_eq!(unknown.temperature, Some(0.7));
+    assert_eq!(profile_params(&profiles, None), unknown);
+}
+
+#[test]
+fn test_reasoning_excluded_from_history() {
+    let mut answer = result("chatcmpl-1", "42", FinishReason::Stop);
+    answer.reasoning = Some("先想一想".to_string());
+
+    let mut session = Session::new("推理", None);
+    session.push_exchange("答案是什么？", &answer);
+
+    assert_eq!(session.messages[1].reasoning.as_deref(), Some("先想一想"));
+    let history = session.history();
+    assert_eq!(history[1].content, "42");
+    let serialized = serde_json::to_string(&history).unwrap();
+    assert!(!serialized.contains("先想一想"));
+}
+
+#[test]
+fn test_session_images_in_history() {
+    let image = ImageAttachment {
+        name: "screenshot.png".to_string(),
+        mime_type: "image/png".to_string(),
+        width: 1,
+        height: 1,
+        data_url: "data:image/png;base64,AAAA".to_string(),
+    };
+    let mut session = Session::new("看图", None);
+    let answer = result("chatcmpl-1", "一张截图", FinishReason::Stop);
+    session.push_exchange_with_attachments("这是什么？", vec![image.clone()], vec![], &answer);
+
+    assert_eq!(session.messages[0].images, [image]);
+    let history = session.history();
+    assert_eq!(history[0].content.image_urls(), ["data:image/png;base64,AAAA"]);
+    assert_eq!(history[0].content.text(), "这是什么？");
+    assert_eq!(history[1].content, "一张截图");
+
+    let restored: Session = serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+    assert_eq!(restored, session);
+}
+
+#[test]
+fn test_session_branches() {
+    let mut session = Session::new("测试", None);
+    session.push_exchange("问题一", &result("a1", "回答一", FinishReason::Stop));
+    session.push_exchange("问题二", &result("a2", "回答二", FinishReason::Stop));
+    let question_two = session.messages[2].id.clone();
+
+    // 编辑问题二：新问题挂在回答一下面，与原问题成为兄弟
+    let answer = result("a2-edit", "新回答", FinishReason::Stop);
+    session.push_exchange_under(Some("a1".to_string()), "问题二（修改）", vec![], vec![], &answer);
+    let history = session.history();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[2].content, "问题二（修改）");
+    let branch = session.active_branch();
+    assert_eq!(branch[2].sibling_ids.len(), 2);
+    assert_eq!(branch[2].sibling_ids[0], question_two);
+
+    // 重新生成：同一问题下的兄弟回答
+    let edited = branch[2].message.id.clone();
+    let mut regenerated = result("a2-regen", "另一个回答", FinishReason::Stop);
+    regenerated.model = "gpt-4o".to_string();
+    regenerated.timings.total_ms = 1200;
+    session.push_answer(&edited, &regenerated);
+    let alternatives = session.children(Some(&edited));
+    assert_eq!(alternatives.len(), 2);
+    assert_eq!(alternatives[0].model.as_deref(), Some("deepseek-chat"));
+    assert_eq!(alternatives[1].model.as_deref(), Some("gpt-4o"));
+    assert_eq!(alternatives[1].timings.as_ref().map(|t| t.total_ms), Some(1200));
+    assert_eq!(session.history()[3].content, "另一个回答");
+    assert_eq!(session.history_before("a2-regen").len(), 3);
+
+    // 切回原问题时走到该分支的叶子
+    assert!(session.switch_branch(&question_two));
+    assert_eq!(session.active_leaf_id(), Some("a2"));
+    assert_eq!(session.history()[3].content, "回答二");
+    assert!(!session.switch_branch("unknown"));
+
+    // 旧版本保存的线性会话读取时补上父消息
+    let mut legacy = session.clone();
+    legacy.messages.truncate(4);
+    legacy.active_leaf = None;
+    for message in &mut legacy.messages {
+        message.parent_id = None;
+    }
+    legacy.migrate_linear();
+    assert_eq!(legacy.history().len(), 4);
+    assert_eq!(legacy.messages[3].parent_id.as_deref(), Some(question_two.as_str()));
+}
+
+#[test]
+fn test_update_session_keeps_concurrent_changes() {
+    set_storage(Box::new(SqliteStorage::open_in_memory().unwrap()));
+    let session = Session::new("新会话", None);
+    save_session(&session).unwrap();
+
+    // 生成回答期间会话被重命名，追加问答时保留新标题
+    let mut renamed = load_session(&session.id).unwrap();
+    renamed.title = "改名".to_string();
+    save_session(&renamed).unwrap();
+    update_session(&session.id, |session| {
+        session.push_exchange("问题", &result("a1", "回答", FinishReason::Stop));
+        Ok(())
+    })
+    .unwrap();
+    let stored = load_session(&session.id).unwrap();
+    assert_eq!(stored.title, "改名");
+    assert_eq!(stored.messages.len(), 2);
+
+    // 修改失败时不保存
+    let failed = update_session(&session.id, |session| {
+        session.messages.clear();
+        Err::<(), _>("失败".to_string())
+    });
+    assert!(failed.is_err());
+    assert_eq!(load_session(&session.id).unwrap().messages.len(), 2);
+    assert!(update_session("missing", |_| Ok(())).is_err());
+}
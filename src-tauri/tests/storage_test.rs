@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs;
+use chat_ai_lib::chat::{ChatResult, ChatTimings, FinishReason};
+use chat_ai_lib::session::Session;
+use chat_ai_lib::storage::{import_legacy_files, read_setting, update_setting, write_setting, SqliteStorage, Storage};
+use chat_ai_lib::usage::{load_prices, UsageRecord};
+
+fn answer(id: &str, content: &str) -> ChatResult {
+    ChatResult {
+        message_id: id.to_string(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: "deepseek-chat".to_string(),
+    }
+}
+
+fn usage_record(message_id: &str, cost: f64) -> UsageRecord {
+    UsageRecord {
+        message_id: message_id.to_string(),
+        timestamp: 1,
+        model: "deepseek-chat".to_string(),
+        profile: "default".to_string(),
+        prompt_tokens: 10,
+        completion_tokens: 5,
+        cost,
+    }
+}
+
+#[test]
+fn test_migrations_and_round_trip() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-storage-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("chat-ai.db");
+
+    let mut session = Session::new("数据库", None);
+    session.push_exchange("问题1", &answer("a1", "回答1"));
+    session.push_exchange("问题2", &answer("a2", "回答2"));
+    {
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), 1);
+        storage.save_session(&session).unwrap();
+        storage.append_usage(&usage_record("a1", 0.5)).unwrap();
+    }
+
+    // 再次打开时不重复执行迁移，数据仍在
+    let mut storage = SqliteStorage::open(&path).unwrap();
+    assert_eq!(storage.schema_version().unwrap(), 1);
+    let loaded = storage.load_session(&session.id).unwrap();
+    assert_eq!(loaded, session);
+    assert_eq!(loaded.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["问题1", "回答1", "问题2", "回答2"]);
+
+    // 覆盖保存时替换全部消息
+    session.messages.truncate(2);
+    storage.save_session(&session).unwrap();
+    assert_eq!(storage.load_session(&session.id).unwrap().messages.len(), 2);
+    assert_eq!(storage.load_usage().unwrap(), [usage_record("a1", 0.5)]);
+
+    storage.delete_session(&session.id).unwrap();
+    assert!(storage.list_sessions().unwrap().is_empty());
+    drop(storage);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_models_and_settings() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let mut frequencies = HashMap::from([("gpt-4o".to_string(), 3), ("deepseek-chat".to_string(), 1)]);
+    storage.save_models(&frequencies).unwrap();
+    frequencies.insert("gpt-4o".to_string(), 4);
+    storage.save_models(&HashMap::from([("gpt-4o".to_string(), 4)])).unwrap();
+    assert_eq!(storage.load_models().unwrap(), frequencies);
+
+    assert_eq!(storage.get_setting("missing").unwrap(), None);
+    storage.set_setting("theme", "dark").unwrap();
+    storage.set_setting("theme", "light").unwrap();
+    assert_eq!(storage.get_setting("theme").unwrap().as_deref(), Some("light"));
+    storage.delete_setting("theme").unwrap();
+    assert_eq!(storage.get_setting("theme").unwrap(), None);
+
+    let empty: Vec<String> = read_setting(&storage, "list").unwrap();
+    assert!(empty.is_empty());
+    write_setting(&mut storage, "list", &vec!["a".to_string()]).unwrap();
+    assert_eq!(read_setting::<Vec<String>>(&storage, "list").unwrap(), ["a"]);
+
+    // 修改失败时整个事务回滚
+    let pushed = update_setting(&mut storage, "list", |list: &mut Vec<String>| {
+        list.push("b".to_string());
+        Ok(list.len())
+    });
+    assert_eq!(pushed, Ok(2));
+    let failed = update_setting(&mut storage, "list", |list: &mut Vec<String>| {
+        list.clear();
+        Err::<(), _>("失败".to_string())
+    });
+    assert!(failed.is_err());
+    assert_eq!(read_setting::<Vec<String>>(&storage, "list").unwrap(), ["a", "b"]);
+}
+
+#[test]
+fn test_import_legacy_files() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-legacy-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sessions")).unwrap();
+
+    let mut session = Session::new("旧会话", None);
+    session.push_exchange("问题", &answer("a1", "回答"));
+    fs::write(dir.join("sessions").join(format!("{}.json", session.id)), serde_json::to_string(&session).unwrap()).unwrap();
+    fs::write(dir.join("sessions").join("broken.json"), "{").unwrap();
+    fs::write(dir.join("frequency.json"), r#"{"frequencies":{"gpt-4o":2}}"#).unwrap();
+    fs::write(dir.join("usage.jsonl"), serde_json::to_string(&usage_record("a1", 0.1)).unwrap() + "\n").unwrap();
+    fs::write(dir.join("prices.json"), r#"{"gpt-4o":{"prompt":2.5,"completion":10.0}}"#).unwrap();
+    fs::write(dir.join("profiles.json"), "{").unwrap();
+    fs::write(dir.join("api_keys.enc"), "encrypted-key\n").unwrap();
+
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let report = import_legacy_files(&mut storage, &dir).unwrap().unwrap();
+    assert_eq!(report.sessions, 1);
+    assert_eq!(report.models, 1);
+    assert_eq!(report.usage_records, 1);
+    assert_eq!(report.settings, ["model_prices", "api_key"]);
+
+    assert_eq!(storage.load_session(&session.id).unwrap(), session);
+    assert_eq!(storage.load_models().unwrap()["gpt-4o"], 2);
+    assert_eq!(storage.get_setting("api_key").unwrap().as_deref(), Some("encrypted-key"));
+    // 凭证文件导入后删除，会话文件保留作为备份
+    assert!(!dir.join("api_keys.enc").exists());
+    assert!(dir.join("sessions").join(format!("{}.json", session.id)).exists());
+    assert_eq!(load_prices(&storage).unwrap()["gpt-4o"].completion, 10.0);
+
+    // 已导入过时不再重复导入
+    assert_eq!(import_legacy_files(&mut storage, &dir).unwrap(), None);
+    assert_eq!(storage.load_usage().unwrap().len(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_failed_import_rolls_back() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-legacy-rollback-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sessions")).unwrap();
+
+    let session = Session::new("旧会话", None);
+    fs::write(dir.join("sessions").join(format!("{}.json", session.id)), serde_json::to_string(&session).unwrap()).unwrap();
+    fs::write(dir.join("api_keys.enc"), "encrypted-key\n").unwrap();
+    // 账本无法读取，导入在会话写入之后失败
+    fs::create_dir_all(dir.join("usage.jsonl")).unwrap();
+
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    assert!(import_legacy_files(&mut storage, &dir).is_err());
+    assert!(storage.list_sessions().unwrap().is_empty());
+    assert_eq!(storage.get_setting("api_key").unwrap(), None);
+    assert!(dir.join("api_keys.enc").exists());
+
+    // 问题解决后下次启动重新导入
+    fs::remove_dir(dir.join("usage.jsonl")).unwrap();
+    let report = import_legacy_files(&mut storage, &dir).unwrap().unwrap();
+    assert_eq!(report.sessions, 1);
+    assert_eq!(storage.get_setting("api_key").unwrap().as_deref(), Some("encrypted-key"));
+    fs::remove_dir_all(&dir).unwrap();
+}
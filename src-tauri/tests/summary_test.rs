@@ -0,0 +1,106 @@
+use chat_ai_lib::chat::{ChatResult, ChatTimings, FinishReason, MessageContent};
+use chat_ai_lib::persona::{apply_system_prompt, Persona};
+use chat_ai_lib::session::{ContextSummary, Session, DEFAULT_SESSION_TITLE};
+use chat_ai_lib::storage::{SqliteStorage, Storage};
+use chat_ai_lib::summary::{
+    clean_title, load_summary_config, needs_title, summary_cutoff, summary_messages, title_messages, SummaryConfig,
+    DEFAULT_KEEP_RECENT,
+};
+
+fn answer(id: &str, content: &str) -> ChatResult {
+    ChatResult {
+        message_id: id.to_string(),
+        response_id: None,
+        content: content.to_string(),
+        reasoning: None,
+        tool_calls: vec![],
+        finish_reason: Some(FinishReason::Stop),
+        usage: None,
+        timings: ChatTimings::default(),
+        model: "deepseek-chat".to_string(),
+    }
+}
+
+fn text(content: &MessageContent) -> &str {
+    match content {
+        MessageContent::Text(text) => text,
+        MessageContent::Parts(_) => panic!("应为纯文本消息"),
+    }
+}
+
+#[test]
+fn test_auto_title() {
+    let mut session = Session::new(DEFAULT_SESSION_TITLE, None);
+    assert!(!needs_title(&session));
+    assert!(title_messages(&session).is_none());
+
+    session.push_exchange("怎么用 Rust 读取文件？", &answer("a1", "使用 std::fs::read_to_string"));
+    assert!(needs_title(&session));
+    let messages = title_messages(&session).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(text(&messages[1].content).contains("用户：怎么用 Rust 读取文件？"));
+
+    session.title = "已命名".to_string();
+    assert!(!needs_title(&session));
+
+    assert_eq!(clean_title("标题：“Rust 文件读取”。\n其他内容").as_deref(), Some("Rust 文件读取"));
+    assert_eq!(clean_title("  **Reading files in Rust.**  ").as_deref(), Some("Reading files in Rust"));
+    assert_eq!(clean_title("长".repeat(50).as_str()).map(|t| t.chars().count()), Some(30));
+    assert_eq!(clean_title("\n \"\" \n"), None);
+
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    assert_eq!(load_summary_config(&storage).unwrap(), SummaryConfig::default());
+    storage.set_setting("summary_config", r#"{"model":"gpt-4o-mini"}"#).unwrap();
+    let config = load_summary_config(&storage).unwrap();
+    assert!(config.auto_title);
+    assert_eq!(config.keep_recent, DEFAULT_KEEP_RECENT);
+    assert_eq!(config.model_or("gpt-4o"), "gpt-4o-mini");
+}
+
+#[test]
+fn test_running_summary() {
+    let mut session = Session::new("长对话", None);
+    for i in 1..=4 {
+        session.push_exchange(&format!("问题{}", i), &answer(&format!("a{}", i), &format!("回答{}", i)));
+    }
+    // 8 条消息保留最近 4 条，摘要覆盖到第二个回答
+    assert_eq!(summary_cutoff(&session, 4).unwrap().id, "a2");
+    assert!(summary_cutoff(&session, 8).is_none());
+    let request = summary_messages(&session, "a2");
+    assert_eq!(text(&request[1].content), "用户：问题1\n\n助手：回答1\n\n用户：问题2\n\n助手：回答2");
+
+    session.context_summary = Some(ContextSummary {
+        content: "讨论了问题1和问题2".to_string(),
+        until_message_id: "a2".to_string(),
+        model: "gpt-4o-mini".to_string(),
+        created_at: 0,
+    });
+    let history = session.history();
+    assert_eq!(history.len(), 5);
+    assert_eq!(history[0].role, "system");
+    assert!(text(&history[0].content).contains("讨论了问题1和问题2"));
+    assert_eq!(text(&history[1].content), "问题3");
+    assert_eq!(session.history_before("a4").len(), 4);
+
+    // 人设提示词仍插入在摘要之前
+    let persona = Persona {
+        name: "助手".to_string(),
+        system_prompt: "你是一名助手".to_string(),
+        ..Default::default()
+    };
+    let mut messages = session.history();
+    apply_system_prompt(&persona, &mut messages);
+    apply_system_prompt(&persona, &mut messages);
+    assert_eq!(messages.len(), 6);
+    assert_eq!(text(&messages[0].content), "你是一名助手");
+
+    // 已有摘要时只摘要之后的新消息
+    assert!(summary_cutoff(&session, 4).is_none());
+    session.push_exchange("问题5", &answer("a5", "回答5"));
+    assert_eq!(summary_cutoff(&session, 4).unwrap().id, "a3");
+    let request = summary_messages(&session, "a3");
+    assert_eq!(
+        text(&request[1].content),
+        "之前的摘要：\n讨论了问题1和问题2\n\n新的对话：\n用户：问题3\n\n助手：回答3"
+    );
+}
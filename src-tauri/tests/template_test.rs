@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use chat_ai_lib::template::{PromptTemplate, TemplateVariable};
+
+fn variable(name: &str, default: Option<&str>, required: bool) -> TemplateVariable {
+    TemplateVariable {
+        name: name.to_string(),
+        description: String::new(),
+        default: default.map(str::to_string),
+        required,
+    }
+}
+
+fn review_template() -> PromptTemplate {
+    PromptTemplate {
+        name: "review".to_string(),
+        description: "代码审查".to_string(),
+        body: "Review this diff for {language}, focus on {focus}:\n{diff}".to_string(),
+        variables: vec![
+            variable("language", None, true),
+            variable("focus", Some("correctness"), true),
+            variable("diff", None, true),
+        ],
+    }
+}
+
+fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn test_render_with_defaults() {
+    let rendered = review_template()
+        .render(&values(&[("language", "Rust"), ("diff", "- a\n+ b")]))
+        .unwrap();
+
+    assert_eq!(rendered, "Review this diff for Rust, focus on correctness:\n- a\n+ b");
+}
+
+#[test]
+fn test_render_overrides_default() {
+    let rendered = review_template()
+        .render(&values(&[("language", "Go"), ("focus", "性能"), ("diff", "x")]))
+        .unwrap();
+
+    assert_eq!(rendered, "Review this diff for Go, focus on 性能:\nx");
+}
+
+#[test]
+fn test_render_errors() {
+    let template = review_template();
+
+    let missing = template.render(&values(&[("language", "Rust")])).unwrap_err();
+    assert!(missing.contains("diff"));
+
+    let unknown = template
+        .render(&values(&[("language", "Rust"), ("diff", "x"), ("extra", "y")]))
+        .unwrap_err();
+    assert!(unknown.contains("extra"));
+}
+
+#[test]
+fn test_optional_variable_and_escaping() {
+    let template = PromptTemplate {
+        name: "json".to_string(),
+        description: String::new(),
+        body: "返回 {{\"answer\": ...}} {note}".to_string(),
+        variables: vec![variable("note", None, false)],
+    };
+
+    assert_eq!(template.render(&HashMap::new()).unwrap(), "返回 {\"answer\": ...} ");
+}
+
+#[test]
+fn test_validate() {
+    assert!(review_template().validate().is_ok());
+
+    let mut undeclared = review_template();
+    undeclared.variables.pop();
+    assert!(undeclared.validate().unwrap_err().contains("diff"));
+
+    let mut unclosed = review_template();
+    unclosed.body = "Review {language".to_string();
+    assert!(unclosed.validate().is_err());
+
+    let mut stray = review_template();
+    stray.body = "Review } {language}".to_string();
+    assert!(stray.validate().is_err());
+
+    let mut duplicated = review_template();
+    duplicated.variables.push(variable("language", None, true));
+    assert!(duplicated.validate().is_err());
+}
+
+#[test]
+fn test_referenced_variables_order() {
+    let template = PromptTemplate {
+        name: "t".to_string(),
+        description: String::new(),
+        body: "{b} {a} {b}".to_string(),
+        variables: vec![variable("a", None, true), variable("b", None, true)],
+    };
+
+    assert_eq!(template.referenced_variables().unwrap(), vec!["b", "a"]);
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde_json::{json, Value};
+use chat_ai_lib::chat::{parse_stream_line, ChatMessage, ChatTimings, FinishReason, FunctionCall, StreamAccumulator, ToolCall};
+use chat_ai_lib::tools::{execute_tool_call, validate_arguments, Tool, ToolRegistry};
+
+struct EchoTool;
+
+impl Tool for EchoTool {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn description(&self) -> &str {
+        "原样返回输入文本"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "text": { "type": "string" },
+                "times": { "type": "integer" }
+            },
+            "required": ["text"]
+        })
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<String, String>> {
+        async move {
+            let text = arguments["text"].as_str().unwrap_or_default();
+            let times = arguments["times"].as_u64().unwrap_or(1) as usize;
+            Ok(text.repeat(times))
+        }
+        .boxed()
+    }
+}
+
+fn call(id: &str, name: &str, arguments: &str) -> ToolCall {
+    ToolCall {
+        id: id.to_string(),
+        kind: "function".to_string(),
+        function: FunctionCall {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_tool_call_fragments_accumulated() {
+    let lines = [
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"echo","arguments":""}}]},"finish_reason":null}]}"#,
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"te"}}]},"finish_reason":null}]}"#,
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_2","function":{"name":"echo","arguments":"{}"}}]},"finish_reason":null}]}"#,
+        r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"xt\":\"hi\"}"}}]},"finish_reason":null}]}"#,
+        r#"data: {"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+    ];
+
+    let mut accumulator = StreamAccumulator::new();
+    for line in lines {
+        accumulator.push(parse_stream_line(line).unwrap());
+    }
+    let result = accumulator.into_result("gpt-4o", ChatTimings::default());
+
+    assert!(result.wants_tools());
+    assert_eq!(result.finish_reason, Some(FinishReason::ToolCalls));
+    assert_eq!(result.tool_calls.len(), 2);
+    assert_eq!(result.tool_calls[0], call("call_1", "echo", r#"{"text":"hi"}"#));
+    assert_eq!(result.tool_calls[1].id, "call_2");
+}
+
+#[test]
+fn test_tool_messages_serialization() {
+    let assistant = ChatMessage::assistant_tool_calls("", vec![call("call_1", "echo", "{}")]);
+    let value = serde_json::to_value(&assistant).unwrap();
+    assert_eq!(value["tool_calls"][0]["type"], "function");
+    assert_eq!(value["tool_calls"][0]["function"]["name"], "echo");
+    assert!(value.get("tool_call_id").is_none());
+
+    let tool = ChatMessage::tool_result("call_1", "hi");
+    let value = serde_json::to_value(&tool).unwrap();
+    assert_eq!(value["role"], "tool");
+    assert_eq!(value["tool_call_id"], "call_1");
+    assert!(value.get("tool_calls").is_none());
+}
+
+#[test]
+fn test_registry_definitions() {
+    let mut registry = ToolRegistry::new();
+    assert!(registry.is_empty());
+    registry.register(Arc::new(EchoTool));
+
+    let definitions = registry.definitions();
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].kind, "function");
+    assert_eq!(definitions[0].function.name, "echo");
+    assert_eq!(definitions[0].function.parameters["required"][0], "text");
+
+    assert!(registry.unregister("echo"));
+    assert!(registry.get("echo").is_none());
+}
+
+#[test]
+fn test_validate_arguments() {
+    let schema = EchoTool.parameters();
+
+    assert!(validate_arguments(&schema, &json!({ "text": "a", "times": 2 })).is_ok());
+    assert!(validate_arguments(&schema, &json!({ "times": 2 })).unwrap_err().contains("text"));
+    assert!(validate_arguments(&schema, &json!({ "text": 1 })).is_err());
+    assert!(validate_arguments(&schema, &json!({ "text": "a", "times": 1.5 })).is_err());
+    assert!(validate_arguments(&schema, &json!(["text"])).is_err());
+}
+
+#[tokio::test]
+async fn test_execute_tool_call() {
+    let mut registry = ToolRegistry::new();
+    registry.register(Arc::new(EchoTool));
+
+    let outcome = execute_tool_call(&registry, &call("call_1", "echo", r#"{"text":"ab","times":2}"#)).await;
+    assert!(!outcome.is_error);
+    assert_eq!(outcome.content, "abab");
+    assert_eq!(outcome.tool_call_id, "call_1");
+
+    let invalid_json = execute_tool_call(&registry, &call("call_2", "echo", "{oops")).await;
+    assert!(invalid_json.is_error);
+
+    let unknown = execute_tool_call(&registry, &call("call_3", "missing", "{}")).await;
+    assert!(unknown.is_error);
+    assert!(unknown.content.contains("missing"));
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fs;
+use chrono::{Local, TimeZone};
+use chat_ai_lib::chat::Usage;
+use chat_ai_lib::usage::{
+    check_budget, compute_cost, find_price, load_records, monthly_spend, records_to_csv,
+    summarize, BudgetConfig, ModelPrice, SpendGroup, UsageRecord,
+};
+
+fn record(model: &str, profile: &str, day: u32, cost: f64) -> UsageRecord {
+    UsageRecord {
+        message_id: format!("msg-{}-{}", model, day),
+        timestamp: Local.with_ymd_and_hms(2025, 3, day, 12, 0, 0).unwrap().timestamp_millis(),
+        model: model.to_string(),
+        profile: profile.to_string(),
+        prompt_tokens: 100,
+        completion_tokens: 50,
+        cost,
+    }
+}
+
+#[test]
+fn test_find_price_prefix_match() {
+    let mut prices = HashMap::new();
+    prices.insert("gpt-4o".to_string(), ModelPrice { prompt: 2.5, completion: 10.0 });
+    prices.insert("gpt-4o-mini".to_string(), ModelPrice { prompt: 0.15, completion: 0.6 });
+
+    assert_eq!(find_price(&prices, "gpt-4o").unwrap().prompt, 2.5);
+    assert_eq!(find_price(&prices, "gpt-4o-2024-08-06").unwrap().prompt, 2.5);
+    assert_eq!(find_price(&prices, "gpt-4o-mini-2024-07-18").unwrap().prompt, 0.15);
+    assert!(find_price(&prices, "deepseek-chat").is_none());
+}
+
+#[test]
+fn test_compute_cost() {
+    let price = ModelPrice { prompt: 2.0, completion: 8.0 };
+    let usage = Usage { prompt_tokens: 1_000_000, completion_tokens: 500_000, total_tokens: 1_500_000 };
+
+    assert!((compute_cost(Some(&price), &usage) - 6.0).abs() < 1e-9);
+    assert_eq!(compute_cost(None, &usage), 0.0);
+}
+
+#[test]
+fn test_load_legacy_ledger() {
+    let dir = std::env::temp_dir().join(format!("chat-ai-usage-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ledger = dir.join("usage.jsonl");
+
+    let lines: Vec<String> = [record("gpt-4o", "work", 1, 0.5), record("deepseek-chat", "home", 2, 0.1)]
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap())
+        .collect();
+    // 旧版本写入中断留下的半行
+    fs::write(&ledger, lines.join("\n") + "\n{\"message_id\":").unwrap();
+
+    let records = load_records(&ledger).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].model, "deepseek-chat");
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_summarize() {
+    let records = vec![
+        record("gpt-4o", "work", 1, 0.5),
+        record("gpt-4o", "home", 1, 0.25),
+        record("deepseek-chat", "work", 2, 0.1),
+    ];
+
+    let by_model = summarize(&records, SpendGroup::Model, None, None);
+    assert_eq!(by_model.len(), 2);
+    assert_eq!(by_model[1].key, "gpt-4o");
+    assert_eq!(by_model[1].requests, 2);
+    assert_eq!(by_model[1].prompt_tokens, 200);
+    assert!((by_model[1].cost - 0.75).abs() < 1e-9);
+
+    let by_day = summarize(&records, SpendGroup::Day, None, None);
+    assert_eq!(by_day[0].key, "2025-03-01");
+    assert_eq!(by_day[1].key, "2025-03-02");
+
+    let from = chrono::NaiveDate::from_ymd_opt(2025, 3, 2);
+    let by_profile = summarize(&records, SpendGroup::Profile, from, None);
+    assert_eq!(by_profile.len(), 1);
+    assert_eq!(by_profile[0].key, "work");
+}
+
+#[test]
+fn test_budget_limits() {
+    let records = vec![record("gpt-4o", "work", 1, 3.0), record("gpt-4o", "home", 2, 1.0)];
+    let now = Local.with_ymd_and_hms(2025, 3, 15, 0, 0, 0).unwrap();
+    let next_month = Local.with_ymd_and_hms(2025, 4, 1, 0, 0, 0).unwrap();
+
+    assert!((monthly_spend(&records, None, now) - 4.0).abs() < 1e-9);
+    assert!((monthly_spend(&records, Some("work"), now) - 3.0).abs() < 1e-9);
+    assert_eq!(monthly_spend(&records, None, next_month), 0.0);
+
+    let mut budgets = BudgetConfig::default();
+    assert!(check_budget(&records, &budgets, Some("work"), now).is_ok());
+
+    budgets.profiles.insert("work".to_string(), 2.0);
+    assert!(check_budget(&records, &budgets, Some("work"), now).is_err());
+    assert!(check_budget(&records, &budgets, Some("home"), now).is_ok());
+    assert!(check_budget(&records, &budgets, Some("work"), next_month).is_ok());
+
+    budgets.global = Some(4.0);
+    assert!(check_budget(&records, &budgets, Some("home"), now).is_err());
+    assert!(budgets.validate().is_ok());
+
+    budgets.global = Some(f64::NAN);
+    assert!(budgets.validate().is_err());
+    budgets.global = None;
+    budgets.profiles.insert("home".to_string(), -1.0);
+    assert!(budgets.validate().is_err());
+}
+
+#[test]
+fn test_records_to_csv() {
+    let mut escaped = record("gpt-4o", "team, \"A\"", 1, 0.5);
+    escaped.message_id = "msg-1".to_string();
+
+    let csv = records_to_csv(&[escaped]);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines[0], "time,message_id,model,profile,prompt_tokens,completion_tokens,cost");
+    assert!(lines[1].starts_with("2025-03-01 12:00:00,msg-1,gpt-4o,\"team, \"\"A\"\"\",100,50,0.500000"));
+}
@@ -231,9 +231,7 @@ async function loadSettings() {
   // 从加密存储获取 API key 和 URL
   try {
     const [savedApiKey, savedApiUrl] = await Promise.all([
-      invoke("get_api_key", {
-        api_keys_path: await invoke("get_cache_directory").then(dir => `${dir}/api_keys.enc`)
-      }).catch(() => null),
+      invoke("get_api_key").catch(() => null),
       invoke("get_api_url").catch(() => null),
     ]);
 
@@ -350,6 +348,36 @@ async function setupStreamListener() {
       smartScroll();
     }
   });
+
+  // 推理模型的思考过程显示在思考状态 div 中，收到正式回答后随之移除
+  await listen("stream-reasoning", (event) => {
+    if (currentStreamDiv && !currentStreamContent) {
+      const thinkingDiv = currentStreamDiv.previousSibling;
+      if (thinkingDiv) {
+        let reasoningEl = thinkingDiv.querySelector(".reasoning-text");
+        if (!reasoningEl) {
+          reasoningEl = document.createElement("div");
+          reasoningEl.className = "reasoning-text";
+          thinkingDiv.appendChild(reasoningEl);
+        }
+        reasoningEl.textContent += event.payload;
+        smartScroll();
+      }
+    }
+  });
+
+  // 工具执行前需要用户确认，未回复时后端超时后按拒绝处理
+  await listen("tool-approval-request", async (event) => {
+    const { request_id, tool_call, description } = event.payload;
+    const approved = window.confirm(
+      `模型请求调用工具 ${tool_call.function.name}（${description}）\n参数：${tool_call.function.arguments}\n\n是否允许执行？`
+    );
+    try {
+      await invoke("respond_tool_approval", { requestId: request_id, approved });
+    } catch (error) {
+      console.error("回复工具确认失败:", error);
+    }
+  });
 }
 
 async function chat() {
@@ -426,7 +454,7 @@ async function chat() {
     chatLogEl.appendChild(currentStreamDiv);
 
     try {
-      const response = await invoke("chat", {
+      let response = await invoke("chat", {
         message: contextMessage,
         apiKey,
         apiUrl,
@@ -434,6 +462,17 @@ async function chat() {
         history: [],
       });
 
+      // 回答因长度限制被截断时，自动请求续写并追加到同一条消息
+      if (response.finish_reason === "length") {
+        response = await invoke("continue_response", {
+          apiKey,
+          apiUrl,
+          model,
+          history: [{ role: "user", content: contextMessage }],
+          partial: response,
+        });
+      }
+
       // 流式响应完成后，保存到历史记录
       conversationHistory.push({
         role: "assistant",